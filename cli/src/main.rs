@@ -0,0 +1,731 @@
+//! Admin command line tool for sol-earna.
+
+mod output;
+
+use std::path::PathBuf;
+
+use anchor_lang::{solana_program::instruction::Instruction, AccountDeserialize};
+use anchor_spl::{
+    token::spl_token,
+    token_2022::spl_token_2022::{extension::StateWithExtensions, state::Mint},
+};
+use anyhow::{anyhow, bail, Context as _, Result};
+use base64::Engine;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use sol_earna_client::{
+    instructions::{self, SwapPoolAccounts},
+    pda,
+    states::{FeeConfig, FeeRecipient, StakerInfo, TransferLimit, Treasury},
+    transfer_hook,
+};
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_sdk::{
+    clock::Clock,
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair},
+    signer::Signer,
+    sysvar,
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+
+use output::{Output, Value};
+
+#[derive(Parser)]
+#[command(name = "sol-earna-cli", version, about = "Admin tool for the sol-earna program")]
+struct Cli {
+    /// RPC url
+    #[arg(long, short = 'u', global = true, default_value = "http://127.0.0.1:8899")]
+    url: String,
+
+    /// Payer and signer keypair, defaults to ~/.config/solana/id.json
+    #[arg(long, short = 'k', global = true)]
+    keypair: Option<PathBuf>,
+
+    /// Print the serialized transaction and the simulated logs instead of sending it
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    #[arg(long, global = true, value_enum, default_value_t = Output::Display)]
+    output: Output,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the treasury of a mint, with a new wrapper mint
+    CreateTreasury { treasury_mint: Pubkey },
+
+    /// Initialize the fee config and the ExtraAccountMetaList of a hooked mint
+    InitHook {
+        mint: Pubkey,
+        /// fee percent of every direction (100 = 1%)
+        #[arg(long)]
+        fee: u16,
+        /// label:bps:wsol_token_account, repeated, the bps sum up to 10000
+        #[arg(long = "recipient", value_parser = parse_fee_recipient, required = true)]
+        recipients: Vec<FeeRecipient>,
+        /// raydium clmm pool (wrapper_mint <-> wsol)
+        #[arg(long)]
+        pool_state: Pubkey,
+        #[arg(long, default_value_t = 0)]
+        accrual_threshold: u64,
+    },
+
+    /// Upgrade a treasury created before the accounts were versioned
+    MigrateTreasury { treasury_mint: Pubkey },
+
+    /// Upgrade a fee config created before the accounts were versioned, after its treasury
+    MigrateFeeConfig {
+        mint: Pubkey,
+        /// raydium clmm pool (wrapper_mint <-> wsol)
+        #[arg(long)]
+        pool_state: Pubkey,
+        #[arg(long, default_value_t = 0)]
+        accrual_threshold: u64,
+    },
+
+    /// Update the fee schedules, unset values keep the current config
+    UpdateFees {
+        mint: Pubkey,
+        /// fee percent (100 = 1%)
+        #[arg(long)]
+        transfer_fee: Option<u16>,
+        #[arg(long)]
+        buy_fee: Option<u16>,
+        #[arg(long)]
+        sell_fee: Option<u16>,
+        #[arg(long)]
+        fee_percent_cap: Option<u16>,
+        #[arg(long)]
+        accrual_threshold: Option<u64>,
+        /// label:bps:wsol_token_account, repeated, replaces all the fee recipients
+        #[arg(long = "recipient", value_parser = parse_fee_recipient)]
+        recipients: Vec<FeeRecipient>,
+    },
+
+    /// Open transfers to non-exempt wallets, only once
+    EnableTrading {
+        mint: Pubkey,
+        /// unix timestamp of the launch, now by default
+        #[arg(long, default_value_t = 0)]
+        at: i64,
+        /// fee right after the launch (100 = 1%), decays to the regular fees
+        #[arg(long, default_value_t = 0)]
+        sniper_fee: u16,
+        /// seconds of the sniper fee decay
+        #[arg(long, default_value_t = 0)]
+        sniper_fee_duration: u32,
+    },
+
+    /// Block transfers from and to the token accounts of a wallet, or unblock it with --remove
+    Blocklist {
+        mint: Pubkey,
+        wallet: Pubkey,
+        #[arg(long)]
+        remove: bool,
+    },
+
+    /// Set the seconds between two sells of an owner, 0 disables it
+    SetSellCooldown { mint: Pubkey, sell_cooldown: i64 },
+
+    /// Set the anti-whale limits, unset values keep the current config
+    SetTransferLimits {
+        mint: Pubkey,
+        /// max amount of a transfer: none, a raw amount or <bps>bps of the supply
+        #[arg(long, value_parser = parse_transfer_limit)]
+        max_tx: Option<TransferLimit>,
+        /// max destination balance: none, a raw amount or <bps>bps of the supply
+        #[arg(long, value_parser = parse_transfer_limit)]
+        max_wallet: Option<TransferLimit>,
+    },
+
+    /// Set the min-out of the fee swaps against the pool twap or a pyth price
+    SetSlippage {
+        mint: Pubkey,
+        /// max bps below the reference price, 0 disables the check
+        #[arg(long)]
+        max_slippage_bps: u16,
+        /// seconds of the pool twap, or max age of the oracle price
+        #[arg(long, default_value_t = 300)]
+        price_window: u32,
+        /// pyth price account of wrapper_mint in wsol, the pool twap by default
+        #[arg(long)]
+        price_oracle: Option<Pubkey>,
+    },
+
+    /// Stake treasury tokens of the keypair
+    Stake {
+        treasury_mint: Pubkey,
+        amount: u64,
+        /// index of the lock options (0 = no lock)
+        #[arg(long, default_value_t = 0)]
+        lock_option: u8,
+        /// fail if fewer shares are issued
+        #[arg(long, default_value_t = 0)]
+        min_shares: u64,
+    },
+
+    /// Redeem staked shares of the keypair
+    Redeem { treasury_mint: Pubkey, shares: u64 },
+
+    ShowTreasury { treasury_mint: Pubkey },
+
+    ShowFeeConfig { mint: Pubkey },
+
+    /// Swap the accrued fees and distribute them (process_accrued_fees)
+    CrankSwap {
+        mint: Pubkey,
+        #[arg(long)]
+        amm_config: Pubkey,
+        #[arg(long)]
+        input_vault: Pubkey,
+        #[arg(long)]
+        output_vault: Pubkey,
+        #[arg(long)]
+        observation_state: Pubkey,
+        #[arg(long)]
+        tick_array: Pubkey,
+    },
+
+    /// Swap the buyback fee wsol to wrapper_mint and burn it
+    CrankBuyback {
+        mint: Pubkey,
+        /// max wsol to spend, all of the buyback vault by default
+        #[arg(long, default_value_t = u64::MAX)]
+        amount: u64,
+        #[arg(long)]
+        amm_config: Pubkey,
+        /// wsol vault of the pool
+        #[arg(long)]
+        input_vault: Pubkey,
+        /// wrapper_mint vault of the pool
+        #[arg(long)]
+        output_vault: Pubkey,
+        #[arg(long)]
+        observation_state: Pubkey,
+        #[arg(long)]
+        tick_array: Pubkey,
+    },
+
+    /// Propose a new authority, or accept it with --accept as the pending authority
+    SetAuthority {
+        #[arg(value_enum)]
+        account: AuthorityAccount,
+        /// treasury mint or hooked mint
+        mint: Pubkey,
+        #[arg(required_unless_present = "accept")]
+        new_authority: Option<Pubkey>,
+        #[arg(long)]
+        accept: bool,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum AuthorityAccount {
+    Treasury,
+    FeeConfig,
+}
+
+fn parse_fee_recipient(s: &str) -> Result<FeeRecipient, String> {
+    match s.splitn(3, ':').collect::<Vec<_>>()[..] {
+        [label, bps, recipient] => Ok(FeeRecipient {
+            recipient: recipient.parse().map_err(|e| format!("{e}"))?,
+            bps: bps.parse().map_err(|e| format!("{e}"))?,
+            label: label.to_string(),
+        }),
+        _ => Err("expected label:bps:wsol_token_account".to_string()),
+    }
+}
+
+fn parse_transfer_limit(s: &str) -> Result<TransferLimit, String> {
+    if s == "none" {
+        return Ok(TransferLimit::Unlimited);
+    }
+    match s.strip_suffix("bps") {
+        Some(bps) => bps.parse().map(TransferLimit::SupplyBps),
+        None => s.parse().map(TransferLimit::Amount),
+    }
+    .map_err(|e| format!("{e}, expected none, an amount or <bps>bps"))
+}
+
+struct MintInfo {
+    token_program: Pubkey,
+    decimals: u8,
+    self_hooked: bool, // stake and redeem move it with a top level transfer of the payer
+}
+
+struct Context {
+    rpc: RpcClient,
+    payer: Keypair,
+    dry_run: bool,
+    output: Output,
+}
+
+impl Context {
+    fn fetch<T: AccountDeserialize>(&self, address: &Pubkey) -> Result<T> {
+        let data = self
+            .rpc
+            .get_account_data(address)
+            .with_context(|| format!("fetching {address}"))?;
+        sol_earna_client::accounts::deserialize(&data).map_err(|e| anyhow!("{address}: {e}"))
+    }
+
+    fn token_program_of(&self, mint: &Pubkey) -> Result<Pubkey> {
+        Ok(self.rpc.get_account(mint)?.owner)
+    }
+
+    fn mint(&self, address: &Pubkey) -> Result<MintInfo> {
+        let account = self.rpc.get_account(address)?;
+        let mint = StateWithExtensions::<Mint>::unpack(&account.data)?;
+        Ok(MintInfo {
+            token_program: account.owner,
+            decimals: mint.base.decimals,
+            self_hooked: transfer_hook::is_self_hooked(&account.data)?,
+        })
+    }
+
+    // what redeem pays for `shares` of the payer, less the penalty of a locked stake
+    fn redeem_amount(&self, treasury: &Treasury, shares: u64) -> Result<u64> {
+        let transaction = Transaction::new_with_payer(
+            &[instructions::preview_redeem(treasury, shares)],
+            Some(&self.payer.pubkey()),
+        );
+        let simulation = self
+            .rpc
+            .simulate_transaction_with_config(
+                &transaction,
+                RpcSimulateTransactionConfig {
+                    replace_recent_blockhash: true,
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )?
+            .value;
+        let return_data = simulation
+            .return_data
+            .ok_or_else(|| anyhow!("preview_redeem failed: {:?}", simulation.err))?;
+        let data = base64::engine::general_purpose::STANDARD.decode(&return_data.data.0)?;
+        let amount = u64::from_le_bytes(data.as_slice().try_into()?);
+
+        let staker_info: StakerInfo = self.fetch(&pda::staker_info(
+            &pda::treasury(&treasury.treasury_mint),
+            &self.payer.pubkey(),
+        ))?;
+        let clock: Clock = bincode::deserialize(&self.rpc.get_account_data(&sysvar::clock::ID)?)?;
+        if !staker_info.is_locked(clock.unix_timestamp) {
+            return Ok(amount);
+        }
+        let penalty = amount as u128 * treasury.early_redeem_penalty_bps as u128 / 10_000;
+        Ok(amount - penalty as u64)
+    }
+
+    fn send(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<()> {
+        let blockhash = self.rpc.get_latest_blockhash()?;
+        let mut all_signers = vec![&self.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+
+        if self.dry_run {
+            let serialized =
+                base64::engine::general_purpose::STANDARD.encode(bincode::serialize(&transaction)?);
+            let simulation = self.rpc.simulate_transaction(&transaction)?.value;
+            let logs = simulation.logs.unwrap_or_default();
+            return self.output.print(&[
+                ("transaction", Value::from(serialized)),
+                ("err", json!(simulation.err.map(|e| e.to_string()))),
+                ("logs", json!(logs)),
+            ]);
+        }
+
+        let signature = self.rpc.send_and_confirm_transaction(&transaction)?;
+        self.output
+            .print(&[("signature", Value::from(signature.to_string()))])
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let keypair_path = match cli.keypair {
+        Some(path) => path,
+        None => dirs::home_dir()
+            .ok_or_else(|| anyhow!("no home directory, pass --keypair"))?
+            .join(".config/solana/id.json"),
+    };
+    let payer = read_keypair_file(&keypair_path)
+        .map_err(|e| anyhow!("reading {}: {e}", keypair_path.display()))?;
+    let context = Context {
+        rpc: RpcClient::new_with_commitment(cli.url, CommitmentConfig::confirmed()),
+        payer,
+        dry_run: cli.dry_run,
+        output: cli.output,
+    };
+    run(&context, cli.command)
+}
+
+fn run(context: &Context, command: Command) -> Result<()> {
+    let payer = context.payer.pubkey();
+    match command {
+        Command::CreateTreasury { treasury_mint } => {
+            let token_program = context.token_program_of(&treasury_mint)?;
+            let treasury = pda::treasury(&treasury_mint);
+            let treasury_token_account =
+                get_associated_token_address_with_program_id(&treasury, &treasury_mint, &token_program);
+            let wrapper_mint = Keypair::new();
+            context.send(
+                &[
+                    create_associated_token_account_idempotent(
+                        &payer,
+                        &treasury,
+                        &treasury_mint,
+                        &token_program,
+                    ),
+                    instructions::create_treasury(
+                        &payer,
+                        &treasury_mint,
+                        &wrapper_mint.pubkey(),
+                        &treasury_token_account,
+                    ),
+                    // wsol vault of the holders rewards
+                    create_associated_token_account_idempotent(
+                        &payer,
+                        &pda::reward_authority(&treasury),
+                        &spl_token::native_mint::ID,
+                        &spl_token::ID,
+                    ),
+                ],
+                &[&wrapper_mint],
+            )
+        }
+
+        Command::InitHook {
+            mint,
+            fee,
+            recipients,
+            pool_state,
+            accrual_threshold,
+        } => {
+            let treasury: Treasury = context.fetch(&pda::treasury(&mint))?;
+            context.send(
+                &[
+                    // fees are minted here before the swap
+                    create_associated_token_account_idempotent(
+                        &payer,
+                        &pda::delegate(&mint),
+                        &treasury.wrapper_mint,
+                        &spl_token::ID,
+                    ),
+                    instructions::initialize_extra_account_meta_list(
+                        &payer,
+                        &mint,
+                        &treasury.wrapper_mint,
+                        &pool_state,
+                        fee,
+                        recipients,
+                        accrual_threshold,
+                    ),
+                ],
+                &[],
+            )
+        }
+
+        Command::MigrateTreasury { treasury_mint } => {
+            context.send(&[instructions::migrate_treasury(&payer, &treasury_mint)], &[])
+        }
+
+        Command::MigrateFeeConfig {
+            mint,
+            pool_state,
+            accrual_threshold,
+        } => {
+            let treasury: Treasury = context.fetch(&pda::treasury(&mint))?;
+            context.send(
+                &[
+                    create_associated_token_account_idempotent(
+                        &payer,
+                        &pda::delegate(&mint),
+                        &treasury.wrapper_mint,
+                        &spl_token::ID,
+                    ),
+                    instructions::migrate_fee_config(&payer, &mint, &pool_state, accrual_threshold),
+                ],
+                &[],
+            )
+        }
+
+        Command::UpdateFees {
+            mint,
+            transfer_fee,
+            buy_fee,
+            sell_fee,
+            fee_percent_cap,
+            accrual_threshold,
+            recipients,
+        } => {
+            let fee_config: FeeConfig = context.fetch(&pda::fee_config(&mint))?;
+            let mut ixs = vec![instructions::update_fee_config(
+                &payer,
+                &mint,
+                transfer_fee.unwrap_or(fee_config.transfer_fee),
+                buy_fee.unwrap_or(fee_config.buy_fee),
+                sell_fee.unwrap_or(fee_config.sell_fee),
+                fee_percent_cap.unwrap_or(fee_config.fee_percent_cap),
+                accrual_threshold.unwrap_or(fee_config.accrual_threshold),
+            )];
+            if !recipients.is_empty() {
+                ixs.push(instructions::set_fee_recipients(&payer, &mint, recipients));
+            }
+            context.send(&ixs, &[])
+        }
+
+        Command::EnableTrading {
+            mint,
+            at,
+            sniper_fee,
+            sniper_fee_duration,
+        } => {
+            context.send(
+                &[instructions::enable_trading(
+                    &payer,
+                    &mint,
+                    at,
+                    sniper_fee,
+                    sniper_fee_duration,
+                )],
+                &[],
+            )
+        }
+
+        Command::Blocklist {
+            mint,
+            wallet,
+            remove,
+        } => {
+            let instruction = if remove {
+                instructions::remove_from_blocklist(&payer, &mint, &wallet)
+            } else {
+                instructions::add_to_blocklist(&payer, &mint, &wallet)
+            };
+            context.send(&[instruction], &[])
+        }
+
+        Command::SetSellCooldown {
+            mint,
+            sell_cooldown,
+        } => {
+            context.send(
+                &[instructions::set_sell_cooldown(&payer, &mint, sell_cooldown)],
+                &[],
+            )
+        }
+
+        Command::SetTransferLimits {
+            mint,
+            max_tx,
+            max_wallet,
+        } => {
+            let fee_config: FeeConfig = context.fetch(&pda::fee_config(&mint))?;
+            context.send(
+                &[instructions::set_transfer_limits(
+                    &payer,
+                    &mint,
+                    max_tx.unwrap_or(fee_config.max_tx_amount),
+                    max_wallet.unwrap_or(fee_config.max_wallet_balance),
+                )],
+                &[],
+            )
+        }
+
+        Command::SetSlippage {
+            mint,
+            max_slippage_bps,
+            price_window,
+            price_oracle,
+        } => {
+            context.send(
+                &[instructions::set_slippage_config(
+                    &payer,
+                    &mint,
+                    max_slippage_bps,
+                    price_window,
+                    &price_oracle.unwrap_or_default(),
+                )],
+                &[],
+            )
+        }
+
+        Command::Stake {
+            treasury_mint,
+            amount,
+            lock_option,
+            min_shares,
+        } => {
+            let treasury: Treasury = context.fetch(&pda::treasury(&treasury_mint))?;
+            let mint = context.mint(&treasury_mint)?;
+            let mut stake = vec![instructions::stake(
+                &treasury,
+                &payer,
+                &mint.token_program,
+                amount,
+                lock_option,
+                min_shares,
+            )];
+            if mint.self_hooked {
+                stake.insert(
+                    0,
+                    instructions::hooked_deposit(&treasury, &payer, amount, mint.decimals)?,
+                );
+            }
+            context.send(&stake, &[])
+        }
+
+        Command::Redeem {
+            treasury_mint,
+            shares,
+        } => {
+            let treasury: Treasury = context.fetch(&pda::treasury(&treasury_mint))?;
+            let mint = context.mint(&treasury_mint)?;
+            let mut redeem = vec![instructions::redeem(
+                &treasury,
+                &payer,
+                &mint.token_program,
+                shares,
+            )];
+            if mint.self_hooked {
+                let amount = context.redeem_amount(&treasury, shares)?;
+                redeem.push(instructions::hooked_withdrawal(
+                    &treasury,
+                    &payer,
+                    amount,
+                    mint.decimals,
+                )?);
+            }
+            context.send(&redeem, &[])
+        }
+
+        Command::ShowTreasury { treasury_mint } => {
+            let address = pda::treasury(&treasury_mint);
+            let treasury: Treasury = context.fetch(&address)?;
+            context.output.print(&output::treasury(&address, &treasury))
+        }
+
+        Command::ShowFeeConfig { mint } => {
+            let address = pda::fee_config(&mint);
+            let fee_config: FeeConfig = context.fetch(&address)?;
+            context.output.print(&output::fee_config(&address, &fee_config))
+        }
+
+        Command::CrankSwap {
+            mint,
+            amm_config,
+            input_vault,
+            output_vault,
+            observation_state,
+            tick_array,
+        } => {
+            let fee_config: FeeConfig = context.fetch(&pda::fee_config(&mint))?;
+            context.send(
+                &[instructions::process_accrued_fees(
+                    &mint,
+                    &fee_config,
+                    &SwapPoolAccounts {
+                        amm_config,
+                        input_vault,
+                        output_vault,
+                        observation_state,
+                        tick_array,
+                    },
+                )],
+                &[],
+            )
+        }
+
+        Command::CrankBuyback {
+            mint,
+            amount,
+            amm_config,
+            input_vault,
+            output_vault,
+            observation_state,
+            tick_array,
+        } => {
+            let fee_config: FeeConfig = context.fetch(&pda::fee_config(&mint))?;
+            context.send(
+                &[instructions::buyback_and_burn(
+                    &mint,
+                    &fee_config,
+                    &SwapPoolAccounts {
+                        amm_config,
+                        input_vault,
+                        output_vault,
+                        observation_state,
+                        tick_array,
+                    },
+                    amount,
+                )],
+                &[],
+            )
+        }
+
+        Command::SetAuthority {
+            account,
+            mint,
+            new_authority,
+            accept,
+        } => {
+            let instruction = match (account, accept, new_authority) {
+                (AuthorityAccount::Treasury, true, _) => {
+                    instructions::accept_treasury_authority(&payer, &mint)
+                }
+                (AuthorityAccount::FeeConfig, true, _) => {
+                    instructions::accept_fee_config_authority(&payer, &mint)
+                }
+                (AuthorityAccount::Treasury, false, Some(new_authority)) => {
+                    instructions::propose_treasury_authority(&payer, &mint, &new_authority)
+                }
+                (AuthorityAccount::FeeConfig, false, Some(new_authority)) => {
+                    instructions::propose_fee_config_authority(&payer, &mint, &new_authority)
+                }
+                (_, false, None) => bail!("new authority is required without --accept"),
+            };
+            context.send(&[instruction], &[])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fee_recipients() {
+        let recipient = Pubkey::new_unique();
+        let fee_recipient = parse_fee_recipient(&format!("holders:5000:{recipient}")).unwrap();
+        assert_eq!(fee_recipient.recipient, recipient);
+        assert_eq!(fee_recipient.bps, 5000);
+        assert_eq!(fee_recipient.label, "holders");
+
+        assert!(parse_fee_recipient("holders:5000").is_err());
+        assert!(parse_fee_recipient(&format!("holders:100%:{recipient}")).is_err());
+        assert!(parse_fee_recipient("holders:5000:not-a-pubkey").is_err());
+    }
+
+    #[test]
+    fn parses_transfer_limits() {
+        assert_eq!(parse_transfer_limit("none").unwrap(), TransferLimit::Unlimited);
+        assert_eq!(parse_transfer_limit("1000").unwrap(), TransferLimit::Amount(1000));
+        assert_eq!(parse_transfer_limit("50bps").unwrap(), TransferLimit::SupplyBps(50));
+        assert!(parse_transfer_limit("50%").is_err());
+    }
+}
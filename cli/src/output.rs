@@ -0,0 +1,121 @@
+//! Human readable or json output of the commands.
+
+use anyhow::Result;
+use clap::ValueEnum;
+pub use serde_json::Value;
+use serde_json::{json, Map};
+use sol_earna_client::states::{FeeConfig, FeeRecipient, TransferLimit, Treasury};
+use solana_sdk::pubkey::Pubkey;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Output {
+    Display,
+    Json,
+}
+
+impl Output {
+    pub fn print(&self, fields: &[(&str, Value)]) -> Result<()> {
+        match self {
+            Output::Json => {
+                let object: Map<String, Value> = fields
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.clone()))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&object)?);
+            }
+            Output::Display => {
+                for (key, value) in fields {
+                    match value {
+                        Value::String(s) => println!("{key:<24} {s}"),
+                        Value::Array(items) => {
+                            println!("{key}:");
+                            for item in items {
+                                println!("  {}", item.as_str().map_or(item.to_string(), str::to_string));
+                            }
+                        }
+                        _ => println!("{key:<24} {value}"),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn pubkey(key: &Pubkey) -> Value {
+    Value::from(key.to_string())
+}
+
+fn fee_recipients(fee_recipients: &[FeeRecipient]) -> Value {
+    fee_recipients
+        .iter()
+        .map(|fee_recipient| {
+            json!({
+                "label": fee_recipient.label,
+                "bps": fee_recipient.bps,
+                "recipient": fee_recipient.recipient.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn transfer_limit(limit: &TransferLimit) -> Value {
+    match limit {
+        TransferLimit::Unlimited => Value::Null,
+        TransferLimit::Amount(amount) => json!({ "amount": amount }),
+        TransferLimit::SupplyBps(bps) => json!({ "supply_bps": bps }),
+    }
+}
+
+pub fn treasury(address: &Pubkey, treasury: &Treasury) -> Vec<(&'static str, Value)> {
+    vec![
+        ("address", pubkey(address)),
+        ("version", json!(treasury.version)),
+        ("authority", pubkey(&treasury.authority)),
+        ("pending_authority", pubkey(&treasury.pending_authority)),
+        ("guardian", pubkey(&treasury.guardian)),
+        ("treasury_mint", pubkey(&treasury.treasury_mint)),
+        ("wrapper_mint", pubkey(&treasury.wrapper_mint)),
+        ("treasury_token_account", pubkey(&treasury.treasury_token_account)),
+        ("total_staked", json!(treasury.total_staked)),
+        ("total_weight", json!(treasury.total_weight)),
+        ("total_pending_redeem", json!(treasury.total_pending_redeem)),
+        ("reward_vault_balance", json!(treasury.reward_vault_balance)),
+        // u128 does not fit a json number
+        ("acc_reward_per_share", Value::from(treasury.acc_reward_per_share.to_string())),
+        ("early_redeem_penalty_bps", json!(treasury.early_redeem_penalty_bps)),
+        ("redeem_cooldown", json!(treasury.redeem_cooldown)),
+        ("stake_paused", json!(treasury.stake_paused)),
+        ("redeem_paused", json!(treasury.redeem_paused)),
+    ]
+}
+
+pub fn fee_config(address: &Pubkey, fee_config: &FeeConfig) -> Vec<(&'static str, Value)> {
+    vec![
+        ("address", pubkey(address)),
+        ("version", json!(fee_config.version)),
+        ("authority", pubkey(&fee_config.authority)),
+        ("pending_authority", pubkey(&fee_config.pending_authority)),
+        ("guardian", pubkey(&fee_config.guardian)),
+        ("wsol_mint", pubkey(&fee_config.wsol_mint_address)),
+        ("wrapper_mint", pubkey(&fee_config.wrapper_mint_address)),
+        ("transfer_fee", json!(fee_config.transfer_fee)),
+        ("buy_fee", json!(fee_config.buy_fee)),
+        ("sell_fee", json!(fee_config.sell_fee)),
+        ("fee_recipients", fee_recipients(&fee_config.fee_recipients)),
+        ("fee_percent_cap", json!(fee_config.fee_percent_cap)),
+        ("amm_pool_state", pubkey(&fee_config.amm_pool_state)),
+        ("accrual_threshold", json!(fee_config.accrual_threshold)),
+        ("max_slippage_bps", json!(fee_config.max_slippage_bps)),
+        ("price_window", json!(fee_config.price_window)),
+        ("price_oracle", pubkey(&fee_config.price_oracle)),
+        ("max_tx_amount", transfer_limit(&fee_config.max_tx_amount)),
+        ("max_wallet_balance", transfer_limit(&fee_config.max_wallet_balance)),
+        ("trading_enabled_at", json!(fee_config.trading_enabled_at)),
+        ("sniper_fee", json!(fee_config.sniper_fee)),
+        ("sniper_fee_duration", json!(fee_config.sniper_fee_duration)),
+        ("sell_cooldown", json!(fee_config.sell_cooldown)),
+        ("fee_collection_paused", json!(fee_config.fee_collection_paused)),
+        ("swap_paused", json!(fee_config.swap_paused)),
+    ]
+}
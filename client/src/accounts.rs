@@ -0,0 +1,28 @@
+//! Deserializers of the sol-earna program accounts, the discriminator is checked.
+
+use anchor_lang::{AccountDeserialize, Result};
+use sol_earna::states::{FeeAccrual, FeeConfig, StakerInfo, Treasury, WithdrawalTicket};
+
+pub fn deserialize<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    T::try_deserialize(&mut &data[..])
+}
+
+pub fn treasury(data: &[u8]) -> Result<Treasury> {
+    deserialize(data)
+}
+
+pub fn fee_config(data: &[u8]) -> Result<FeeConfig> {
+    deserialize(data)
+}
+
+pub fn fee_accrual(data: &[u8]) -> Result<FeeAccrual> {
+    deserialize(data)
+}
+
+pub fn staker_info(data: &[u8]) -> Result<StakerInfo> {
+    deserialize(data)
+}
+
+pub fn withdrawal_ticket(data: &[u8]) -> Result<WithdrawalTicket> {
+    deserialize(data)
+}
@@ -0,0 +1,1036 @@
+//! Instruction builders, PDAs and associated token accounts are derived here.
+//!
+//! Treasury instructions take the fetched `Treasury` for its mints and token account,
+//! `token_program_treasury` is the token program of the treasury mint.
+
+use anchor_lang::{
+    prelude::{AccountMeta, Pubkey},
+    solana_program::{instruction::Instruction, program_error::ProgramError, sysvar},
+    system_program, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{self, get_associated_token_address_with_program_id},
+    token::spl_token,
+    token_2022::spl_token_2022,
+};
+use sol_earna::{
+    accounts as ix_accounts, instruction as ix_data,
+    states::{FeeConfig, FeeRecipient, TransferLimit, Treasury},
+};
+
+use crate::{pda, transfer_hook};
+
+// raydium clmm accounts of the wrapper_mint -> wsol swap
+pub struct SwapPoolAccounts {
+    pub amm_config: Pubkey,
+    pub input_vault: Pubkey,
+    pub output_vault: Pubkey,
+    pub observation_state: Pubkey,
+    pub tick_array: Pubkey,
+}
+
+// raydium clmm position accounts of add_auto_liquidity
+pub struct LiquidityPoolAccounts {
+    pub amm_config: Pubkey,
+    pub pool_state: Pubkey,
+    pub protocol_position: Pubkey,
+    pub personal_position: Pubkey,
+    pub tick_array_lower: Pubkey,
+    pub tick_array_upper: Pubkey,
+    pub token_vault_0: Pubkey,
+    pub token_vault_1: Pubkey,
+    pub observation_state: Pubkey,
+    pub swap_tick_array: Pubkey,
+    pub metadata_account: Pubkey,
+}
+
+const METADATA_PROGRAM_ID: Pubkey =
+    anchor_lang::solana_program::pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+// the optional pyth account of the slippage checks
+fn price_oracle(fee_config: &FeeConfig) -> Option<Pubkey> {
+    (fee_config.price_oracle != Pubkey::default()).then_some(fee_config.price_oracle)
+}
+
+fn instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: sol_earna::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+// fee config
+
+pub fn initialize_extra_account_meta_list(
+    payer: &Pubkey,
+    mint: &Pubkey,
+    wrapper_mint: &Pubkey,
+    pool_state: &Pubkey,
+    fee_percent: u16,
+    fee_recipients: Vec<FeeRecipient>,
+    accrual_threshold: u64,
+) -> Instruction {
+    instruction(
+        ix_accounts::InitializeExtraAccountMetaList {
+            payer: *payer,
+            extra_account_meta_list: pda::extra_account_meta_list(mint),
+            mint: *mint,
+            token_program: spl_token_2022::ID,
+            token_program_org: spl_token::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+            fee_config: pda::fee_config(mint),
+            fee_accrual: pda::fee_accrual(mint),
+            treasury: pda::treasury(mint),
+            wsol_mint: spl_token::native_mint::ID,
+            wrapper_mint: *wrapper_mint,
+            pool_state: *pool_state,
+        },
+        ix_data::InitializeExtraAccountMetaList {
+            fee_percent,
+            fee_recipients,
+            accrual_threshold,
+        },
+    )
+}
+
+// upgrades a fee config from before versioning, `authority` is the treasury authority
+pub fn migrate_fee_config(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    pool_state: &Pubkey,
+    accrual_threshold: u64,
+) -> Instruction {
+    instruction(
+        ix_accounts::MigrateFeeConfig {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+            fee_accrual: pda::fee_accrual(mint),
+            extra_account_meta_list: pda::extra_account_meta_list(mint),
+            treasury: pda::treasury(mint),
+            pool_state: *pool_state,
+            token_program: spl_token_2022::ID,
+            token_program_org: spl_token::ID,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        ix_data::MigrateFeeConfig { accrual_threshold },
+    )
+}
+
+pub fn update_fee_config(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    transfer_fee: u16,
+    buy_fee: u16,
+    sell_fee: u16,
+    fee_percent_cap: u16,
+    accrual_threshold: u64,
+) -> Instruction {
+    instruction(
+        ix_accounts::UpdateFeeConfig {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+        },
+        ix_data::UpdateFeeConfig {
+            transfer_fee,
+            buy_fee,
+            sell_fee,
+            fee_percent_cap,
+            accrual_threshold,
+        },
+    )
+}
+
+// `recipient` of each entry is a wsol token account
+pub fn set_fee_recipients(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    fee_recipients: Vec<FeeRecipient>,
+) -> Instruction {
+    instruction(
+        ix_accounts::SetFeeRecipients {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+            system_program: system_program::ID,
+        },
+        ix_data::SetFeeRecipients { fee_recipients },
+    )
+}
+
+// `trading_enabled_at` 0 enables trading now, callable once
+pub fn enable_trading(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    trading_enabled_at: i64,
+    sniper_fee: u16,
+    sniper_fee_duration: u32,
+) -> Instruction {
+    instruction(
+        ix_accounts::EnableTrading {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+        },
+        ix_data::EnableTrading {
+            trading_enabled_at,
+            sniper_fee,
+            sniper_fee_duration,
+        },
+    )
+}
+
+pub fn set_sell_cooldown(authority: &Pubkey, mint: &Pubkey, sell_cooldown: i64) -> Instruction {
+    instruction(
+        ix_accounts::SetSellCooldown {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+        },
+        ix_data::SetSellCooldown { sell_cooldown },
+    )
+}
+
+// idempotent, prepend it to a sell of `owner` while the sell cooldown is set
+pub fn init_sell_cooldown(payer: &Pubkey, mint: &Pubkey, owner: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::InitSellCooldown {
+            payer: *payer,
+            mint: *mint,
+            owner: *owner,
+            sell_cooldown: pda::sell_cooldown(mint, owner),
+            system_program: system_program::ID,
+        },
+        ix_data::InitSellCooldown {},
+    )
+}
+
+pub fn set_transfer_limits(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    max_tx_amount: TransferLimit,
+    max_wallet_balance: TransferLimit,
+) -> Instruction {
+    instruction(
+        ix_accounts::SetTransferLimits {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+        },
+        ix_data::SetTransferLimits {
+            max_tx_amount,
+            max_wallet_balance,
+        },
+    )
+}
+
+// `price_oracle` is a pyth price account of wrapper_mint in wsol, Pubkey::default()
+// checks the fee swaps against the pool twap
+pub fn set_slippage_config(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    max_slippage_bps: u16,
+    price_window: u32,
+    price_oracle: &Pubkey,
+) -> Instruction {
+    instruction(
+        ix_accounts::SetSlippageConfig {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+        },
+        ix_data::SetSlippageConfig {
+            max_slippage_bps,
+            price_window,
+            price_oracle: *price_oracle,
+        },
+    )
+}
+
+fn swap_accounts(
+    mint: &Pubkey,
+    fee_config: &FeeConfig,
+    pool: &SwapPoolAccounts,
+) -> ix_accounts::SwapFeeOnExchange {
+    let delegate = pda::delegate(mint);
+    ix_accounts::SwapFeeOnExchange {
+        mint: *mint,
+        fee_config: pda::fee_config(mint),
+        delegate,
+        wsol_mint: fee_config.wsol_mint_address,
+        wrapper_mint: fee_config.wrapper_mint_address,
+        fee_wrapper_token_account: pda::fee_wrapper_token_account(
+            mint,
+            &fee_config.wrapper_mint_address,
+        ),
+        fee_wsol_token_account: pda::wsol_token_account(&delegate),
+        amm_program: raydium_amm_v3::ID,
+        amm_config: pool.amm_config,
+        pool_state: fee_config.amm_pool_state,
+        input_vault: pool.input_vault,
+        output_vault: pool.output_vault,
+        observation_state: pool.observation_state,
+        tick_array: pool.tick_array,
+        token_program: spl_token::ID,
+        price_oracle: price_oracle(fee_config),
+    }
+}
+
+// the wsol token accounts of the fee recipients, remaining accounts of the swap
+fn with_fee_recipients(mut instruction: Instruction, fee_config: &FeeConfig) -> Instruction {
+    instruction.accounts.extend(
+        fee_config
+            .fee_recipients
+            .iter()
+            .map(|fee_recipient| AccountMeta::new(fee_recipient.recipient, false)),
+    );
+    instruction
+}
+
+pub fn swap_fee_on_exchange(
+    mint: &Pubkey,
+    fee_config: &FeeConfig,
+    pool: &SwapPoolAccounts,
+    amount: u64,
+) -> Instruction {
+    with_fee_recipients(
+        instruction(
+            swap_accounts(mint, fee_config, pool),
+            ix_data::SwapFeeOnExchange { amount },
+        ),
+        fee_config,
+    )
+}
+
+pub fn process_accrued_fees(
+    mint: &Pubkey,
+    fee_config: &FeeConfig,
+    pool: &SwapPoolAccounts,
+) -> Instruction {
+    with_fee_recipients(
+        instruction(
+            ix_accounts::ProcessAccruedFees {
+                swap: swap_accounts(mint, fee_config, pool),
+                fee_accrual: pda::fee_accrual(mint),
+                treasury: pda::treasury(mint),
+            },
+            ix_data::ProcessAccruedFees {},
+        ),
+        fee_config,
+    )
+}
+
+// the first call opens the position with the tick range (authority only), later calls
+// increase it and ignore the ticks
+#[allow(clippy::too_many_arguments)]
+pub fn add_auto_liquidity(
+    payer: &Pubkey,
+    mint: &Pubkey,
+    fee_config: &FeeConfig,
+    pool: &LiquidityPoolAccounts,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    tick_array_lower_start_index: i32,
+    tick_array_upper_start_index: i32,
+) -> Instruction {
+    let liquidity_authority = pda::liquidity_authority(mint);
+    let position_nft_mint = pda::position_nft_mint(mint);
+    instruction(
+        ix_accounts::AddAutoLiquidity {
+            payer: *payer,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+            wsol_mint: fee_config.wsol_mint_address,
+            wrapper_mint: fee_config.wrapper_mint_address,
+            liquidity_authority,
+            liquidity_wsol_token_account: pda::liquidity_wsol_token_account(mint),
+            liquidity_wrapper_token_account: get_associated_token_address_with_program_id(
+                &liquidity_authority,
+                &fee_config.wrapper_mint_address,
+                &spl_token::ID,
+            ),
+            liquidity_position: pda::liquidity_position(mint),
+            position_nft_mint,
+            position_nft_account: get_associated_token_address_with_program_id(
+                &liquidity_authority,
+                &position_nft_mint,
+                &spl_token::ID,
+            ),
+            metadata_account: pool.metadata_account,
+            amm_program: raydium_amm_v3::ID,
+            amm_config: pool.amm_config,
+            pool_state: pool.pool_state,
+            protocol_position: pool.protocol_position,
+            personal_position: pool.personal_position,
+            tick_array_lower: pool.tick_array_lower,
+            tick_array_upper: pool.tick_array_upper,
+            token_vault_0: pool.token_vault_0,
+            token_vault_1: pool.token_vault_1,
+            observation_state: pool.observation_state,
+            swap_tick_array: pool.swap_tick_array,
+            rent: anchor_lang::solana_program::sysvar::rent::ID,
+            system_program: system_program::ID,
+            token_program: spl_token::ID,
+            associated_token_program: associated_token::ID,
+            metadata_program: METADATA_PROGRAM_ID,
+            price_oracle: price_oracle(fee_config),
+        },
+        ix_data::AddAutoLiquidity {
+            tick_lower_index,
+            tick_upper_index,
+            tick_array_lower_start_index,
+            tick_array_upper_start_index,
+        },
+    )
+}
+
+// permissionless, `pool` is the wsol -> wrapper_mint direction
+pub fn buyback_and_burn(
+    mint: &Pubkey,
+    fee_config: &FeeConfig,
+    pool: &SwapPoolAccounts,
+    amount: u64,
+) -> Instruction {
+    let buyback_authority = pda::buyback_authority(mint);
+    instruction(
+        ix_accounts::BuybackAndBurn {
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+            fee_accrual: pda::fee_accrual(mint),
+            wsol_mint: fee_config.wsol_mint_address,
+            wrapper_mint: fee_config.wrapper_mint_address,
+            buyback_authority,
+            buyback_wsol_token_account: pda::buyback_wsol_token_account(mint),
+            buyback_wrapper_token_account: get_associated_token_address_with_program_id(
+                &buyback_authority,
+                &fee_config.wrapper_mint_address,
+                &spl_token::ID,
+            ),
+            amm_program: raydium_amm_v3::ID,
+            amm_config: pool.amm_config,
+            pool_state: fee_config.amm_pool_state,
+            input_vault: pool.input_vault,
+            output_vault: pool.output_vault,
+            observation_state: pool.observation_state,
+            tick_array: pool.tick_array,
+            token_program: spl_token::ID,
+            price_oracle: price_oracle(fee_config),
+        },
+        ix_data::BuybackAndBurn { amount },
+    )
+}
+
+pub fn add_amm_pool_vault(authority: &Pubkey, mint: &Pubkey, vault: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::AddAmmPoolVault {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+            amm_pool_vault: pda::amm_pool_vault(mint, vault),
+            system_program: system_program::ID,
+        },
+        ix_data::AddAmmPoolVault { vault: *vault },
+    )
+}
+
+pub fn remove_amm_pool_vault(authority: &Pubkey, mint: &Pubkey, vault: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::RemoveAmmPoolVault {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+            amm_pool_vault: pda::amm_pool_vault(mint, vault),
+        },
+        ix_data::RemoveAmmPoolVault {},
+    )
+}
+
+pub fn add_fee_exemption(authority: &Pubkey, mint: &Pubkey, wallet: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::AddFeeExemption {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+            fee_exemption: pda::fee_exemption(mint, wallet),
+            system_program: system_program::ID,
+        },
+        ix_data::AddFeeExemption { wallet: *wallet },
+    )
+}
+
+pub fn remove_fee_exemption(authority: &Pubkey, mint: &Pubkey, wallet: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::RemoveFeeExemption {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+            fee_exemption: pda::fee_exemption(mint, wallet),
+        },
+        ix_data::RemoveFeeExemption {},
+    )
+}
+
+pub fn add_to_blocklist(authority: &Pubkey, mint: &Pubkey, wallet: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::AddToBlocklist {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+            blocklist_entry: pda::blocklist_entry(mint, wallet),
+            system_program: system_program::ID,
+        },
+        ix_data::AddToBlocklist { wallet: *wallet },
+    )
+}
+
+pub fn remove_from_blocklist(authority: &Pubkey, mint: &Pubkey, wallet: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::RemoveFromBlocklist {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+            blocklist_entry: pda::blocklist_entry(mint, wallet),
+        },
+        ix_data::RemoveFromBlocklist {},
+    )
+}
+
+pub fn set_fee_config_guardian(authority: &Pubkey, mint: &Pubkey, guardian: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::SetFeeConfigGuardian {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+        },
+        ix_data::SetFeeConfigGuardian { guardian: *guardian },
+    )
+}
+
+// `signer` is the authority or the guardian
+pub fn set_fee_config_pause(
+    signer: &Pubkey,
+    mint: &Pubkey,
+    fee_collection_paused: bool,
+    swap_paused: bool,
+) -> Instruction {
+    instruction(
+        ix_accounts::SetFeeConfigPause {
+            signer: *signer,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+        },
+        ix_data::SetFeeConfigPause {
+            fee_collection_paused,
+            swap_paused,
+        },
+    )
+}
+
+pub fn propose_fee_config_authority(
+    authority: &Pubkey,
+    mint: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    instruction(
+        ix_accounts::ProposeFeeConfigAuthority {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+        },
+        ix_data::ProposeFeeConfigAuthority {
+            new_authority: *new_authority,
+        },
+    )
+}
+
+pub fn accept_fee_config_authority(pending_authority: &Pubkey, mint: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::AcceptFeeConfigAuthority {
+            pending_authority: *pending_authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+        },
+        ix_data::AcceptFeeConfigAuthority {},
+    )
+}
+
+pub fn renounce_fee_config_authority(authority: &Pubkey, mint: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::RenounceFeeConfigAuthority {
+            authority: *authority,
+            mint: *mint,
+            fee_config: pda::fee_config(mint),
+        },
+        ix_data::RenounceFeeConfigAuthority {},
+    )
+}
+
+// treasury
+
+pub fn create_treasury(
+    authority: &Pubkey,
+    treasury_mint: &Pubkey,
+    wrapper_mint: &Pubkey, // new keypair, signs the instruction
+    treasury_token_account: &Pubkey,
+) -> Instruction {
+    instruction(
+        ix_accounts::CreateTreasury {
+            treasury: pda::treasury(treasury_mint),
+            treasury_mint: *treasury_mint,
+            wrapper_mint: *wrapper_mint,
+            treasury_token_account: *treasury_token_account,
+            authority: *authority,
+            system_program: system_program::ID,
+            token_program: spl_token::ID,
+        },
+        ix_data::CreateTreasury {},
+    )
+}
+
+// upgrades a treasury from before versioning
+pub fn migrate_treasury(authority: &Pubkey, treasury_mint: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::MigrateTreasury {
+            authority: *authority,
+            treasury_mint: *treasury_mint,
+            treasury: pda::treasury(treasury_mint),
+            system_program: system_program::ID,
+        },
+        ix_data::MigrateTreasury {},
+    )
+}
+
+pub fn propose_treasury_authority(
+    authority: &Pubkey,
+    treasury_mint: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    instruction(
+        ix_accounts::ProposeTreasuryAuthority {
+            authority: *authority,
+            treasury: pda::treasury(treasury_mint),
+        },
+        ix_data::ProposeTreasuryAuthority {
+            new_authority: *new_authority,
+        },
+    )
+}
+
+pub fn accept_treasury_authority(pending_authority: &Pubkey, treasury_mint: &Pubkey) -> Instruction {
+    instruction(
+        ix_accounts::AcceptTreasuryAuthority {
+            pending_authority: *pending_authority,
+            treasury: pda::treasury(treasury_mint),
+        },
+        ix_data::AcceptTreasuryAuthority {},
+    )
+}
+
+pub fn set_treasury_guardian(
+    authority: &Pubkey,
+    treasury_mint: &Pubkey,
+    guardian: &Pubkey,
+) -> Instruction {
+    instruction(
+        ix_accounts::SetTreasuryGuardian {
+            authority: *authority,
+            treasury: pda::treasury(treasury_mint),
+        },
+        ix_data::SetTreasuryGuardian { guardian: *guardian },
+    )
+}
+
+// `signer` is the authority or the guardian
+pub fn set_treasury_pause(
+    signer: &Pubkey,
+    treasury_mint: &Pubkey,
+    stake_paused: bool,
+    redeem_paused: bool,
+) -> Instruction {
+    instruction(
+        ix_accounts::SetTreasuryPause {
+            signer: *signer,
+            treasury: pda::treasury(treasury_mint),
+        },
+        ix_data::SetTreasuryPause {
+            stake_paused,
+            redeem_paused,
+        },
+    )
+}
+
+pub fn set_redeem_cooldown(
+    authority: &Pubkey,
+    treasury_mint: &Pubkey,
+    redeem_cooldown: i64,
+) -> Instruction {
+    instruction(
+        ix_accounts::SetRedeemCooldown {
+            authority: *authority,
+            treasury: pda::treasury(treasury_mint),
+        },
+        ix_data::SetRedeemCooldown { redeem_cooldown },
+    )
+}
+
+pub fn set_early_redeem_penalty(
+    authority: &Pubkey,
+    treasury_mint: &Pubkey,
+    penalty_bps: u16,
+) -> Instruction {
+    instruction(
+        ix_accounts::SetEarlyRedeemPenalty {
+            authority: *authority,
+            treasury: pda::treasury(treasury_mint),
+        },
+        ix_data::SetEarlyRedeemPenalty { penalty_bps },
+    )
+}
+
+fn user_token_account(treasury: &Treasury, user: &Pubkey, token_program_treasury: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(
+        user,
+        &treasury.treasury_mint,
+        token_program_treasury,
+    )
+}
+
+// a treasury mint hooked by sol-earna can't be moved by a cpi of the program, the runtime
+// rejects the reentrant hook call. the user moves it with these top level transfers, the
+// deposit right before stake and the withdrawal right after redeem or complete_redeem
+
+pub fn hooked_deposit(
+    treasury: &Treasury,
+    user: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    transfer_hook::transfer_checked(
+        &transfer_hook::Transfer {
+            source: user_token_account(treasury, user, &spl_token_2022::ID),
+            mint: treasury.treasury_mint,
+            destination: treasury.treasury_token_account,
+            owner: *user,
+            source_owner: *user,
+            destination_owner: pda::treasury(&treasury.treasury_mint),
+        },
+        amount,
+        decimals,
+    )
+}
+
+// `amount` is what redeem or complete_redeem pays for the shares, the user moves it as
+// delegate of the treasury
+pub fn hooked_withdrawal(
+    treasury: &Treasury,
+    user: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    transfer_hook::transfer_checked(
+        &transfer_hook::Transfer {
+            source: treasury.treasury_token_account,
+            mint: treasury.treasury_mint,
+            destination: user_token_account(treasury, user, &spl_token_2022::ID),
+            owner: *user,
+            source_owner: pda::treasury(&treasury.treasury_mint),
+            destination_owner: *user,
+        },
+        amount,
+        decimals,
+    )
+}
+
+pub fn stake(
+    treasury: &Treasury,
+    user: &Pubkey,
+    token_program_treasury: &Pubkey,
+    amount: u64,
+    lock_option: u8,
+    min_shares: u64,
+) -> Instruction {
+    let treasury_key = pda::treasury(&treasury.treasury_mint);
+    instruction(
+        ix_accounts::Stake {
+            treasury: treasury_key,
+            treasury_mint: treasury.treasury_mint,
+            treasury_token_account: treasury.treasury_token_account,
+            user_token_account: user_token_account(treasury, user, token_program_treasury),
+            staker_info: pda::staker_info(&treasury_key, user),
+            reward_authority: pda::reward_authority(&treasury_key),
+            reward_vault: pda::reward_vault(&treasury_key),
+            wsol_mint: spl_token::native_mint::ID,
+            user: *user,
+            system_program: system_program::ID,
+            token_program_treasury: *token_program_treasury,
+            associated_token_program: associated_token::ID,
+            instructions: sysvar::instructions::ID,
+        },
+        ix_data::Stake {
+            amount,
+            lock_option,
+            min_shares,
+        },
+    )
+}
+
+pub fn redeem(
+    treasury: &Treasury,
+    user: &Pubkey,
+    token_program_treasury: &Pubkey,
+    shares: u64,
+) -> Instruction {
+    let treasury_key = pda::treasury(&treasury.treasury_mint);
+    instruction(
+        ix_accounts::Redeem {
+            treasury: treasury_key,
+            treasury_mint: treasury.treasury_mint,
+            treasury_token_account: treasury.treasury_token_account,
+            user_token_account: user_token_account(treasury, user, token_program_treasury),
+            staker_info: pda::staker_info(&treasury_key, user),
+            reward_authority: pda::reward_authority(&treasury_key),
+            reward_vault: pda::reward_vault(&treasury_key),
+            wsol_mint: spl_token::native_mint::ID,
+            user: *user,
+            system_program: system_program::ID,
+            token_program_treasury: *token_program_treasury,
+            instructions: sysvar::instructions::ID,
+        },
+        ix_data::Redeem { shares },
+    )
+}
+
+pub fn request_redeem(treasury: &Treasury, user: &Pubkey, shares: u64) -> Instruction {
+    let treasury_key = pda::treasury(&treasury.treasury_mint);
+    instruction(
+        ix_accounts::RequestRedeem {
+            treasury: treasury_key,
+            staker_info: pda::staker_info(&treasury_key, user),
+            withdrawal_ticket: pda::withdrawal_ticket(&treasury_key, user),
+            reward_authority: pda::reward_authority(&treasury_key),
+            reward_vault: pda::reward_vault(&treasury_key),
+            wsol_mint: spl_token::native_mint::ID,
+            user: *user,
+            system_program: system_program::ID,
+        },
+        ix_data::RequestRedeem { shares },
+    )
+}
+
+pub fn complete_redeem(
+    treasury: &Treasury,
+    user: &Pubkey,
+    token_program_treasury: &Pubkey,
+) -> Instruction {
+    let treasury_key = pda::treasury(&treasury.treasury_mint);
+    instruction(
+        ix_accounts::CompleteRedeem {
+            treasury: treasury_key,
+            treasury_mint: treasury.treasury_mint,
+            treasury_token_account: treasury.treasury_token_account,
+            user_token_account: user_token_account(treasury, user, token_program_treasury),
+            withdrawal_ticket: pda::withdrawal_ticket(&treasury_key, user),
+            user: *user,
+            token_program_treasury: *token_program_treasury,
+            instructions: sysvar::instructions::ID,
+        },
+        ix_data::CompleteRedeem {},
+    )
+}
+
+pub fn cancel_redeem(treasury: &Treasury, user: &Pubkey) -> Instruction {
+    let treasury_key = pda::treasury(&treasury.treasury_mint);
+    instruction(
+        ix_accounts::CancelRedeem {
+            treasury: treasury_key,
+            staker_info: pda::staker_info(&treasury_key, user),
+            withdrawal_ticket: pda::withdrawal_ticket(&treasury_key, user),
+            reward_authority: pda::reward_authority(&treasury_key),
+            reward_vault: pda::reward_vault(&treasury_key),
+            wsol_mint: spl_token::native_mint::ID,
+            user: *user,
+        },
+        ix_data::CancelRedeem {},
+    )
+}
+
+fn preview_accounts(treasury: &Treasury) -> ix_accounts::Preview {
+    ix_accounts::Preview {
+        treasury: pda::treasury(&treasury.treasury_mint),
+        treasury_token_account: treasury.treasury_token_account,
+    }
+}
+
+// view instructions, simulate and read the return data
+pub fn preview_stake(treasury: &Treasury, amount: u64) -> Instruction {
+    instruction(preview_accounts(treasury), ix_data::PreviewStake { amount })
+}
+
+pub fn preview_redeem(treasury: &Treasury, shares: u64) -> Instruction {
+    instruction(preview_accounts(treasury), ix_data::PreviewRedeem { shares })
+}
+
+pub fn claim_rewards(treasury_mint: &Pubkey, user: &Pubkey) -> Instruction {
+    let treasury_key = pda::treasury(treasury_mint);
+    instruction(
+        ix_accounts::ClaimRewards {
+            treasury: treasury_key,
+            staker_info: pda::staker_info(&treasury_key, user),
+            reward_authority: pda::reward_authority(&treasury_key),
+            reward_vault: pda::reward_vault(&treasury_key),
+            wsol_mint: spl_token::native_mint::ID,
+            user_wsol_token_account: pda::wsol_token_account(user),
+            user: *user,
+            token_program: spl_token::ID,
+        },
+        ix_data::ClaimRewards {},
+    )
+}
+
+// permissionless
+pub fn expire_lock(treasury_mint: &Pubkey, user: &Pubkey) -> Instruction {
+    let treasury_key = pda::treasury(treasury_mint);
+    instruction(
+        ix_accounts::ExpireLock {
+            treasury: treasury_key,
+            staker_info: pda::staker_info(&treasury_key, user),
+            reward_authority: pda::reward_authority(&treasury_key),
+            reward_vault: pda::reward_vault(&treasury_key),
+            wsol_mint: spl_token::native_mint::ID,
+        },
+        ix_data::ExpireLock {},
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sol_earna::states::{FeeConfigV1, TreasuryV1};
+
+    fn fee_config() -> FeeConfig {
+        FeeConfigV1 {
+            wsol_mint_address: spl_token::native_mint::ID,
+            wrapper_mint_address: Pubkey::new_unique(),
+            fee_recipient_liquidity: Pubkey::new_unique(),
+            fee_recipient_marketing: Pubkey::new_unique(),
+            fee_recipient_holders: Pubkey::new_unique(),
+            fee_percent_liquidity: 100,
+            fee_percent_marketing: 100,
+            fee_percent_holders: 100,
+        }
+        .upgrade(Pubkey::new_unique(), Pubkey::new_unique(), 0, 0)
+        .unwrap()
+    }
+
+    fn treasury() -> Treasury {
+        TreasuryV1 {
+            authority: Pubkey::new_unique(),
+            treasury_mint: Pubkey::new_unique(),
+            wrapper_mint: Pubkey::new_unique(),
+            treasury_token_account: Pubkey::new_unique(),
+        }
+        .upgrade()
+    }
+
+    fn pool() -> SwapPoolAccounts {
+        SwapPoolAccounts {
+            amm_config: Pubkey::new_unique(),
+            input_vault: Pubkey::new_unique(),
+            output_vault: Pubkey::new_unique(),
+            observation_state: Pubkey::new_unique(),
+            tick_array: Pubkey::new_unique(),
+        }
+    }
+
+    fn signers(instruction: &Instruction) -> Vec<Pubkey> {
+        instruction
+            .accounts
+            .iter()
+            .filter(|meta| meta.is_signer)
+            .map(|meta| meta.pubkey)
+            .collect()
+    }
+
+    #[test]
+    fn swap_appends_the_fee_recipients_as_writable_accounts() {
+        let mint = Pubkey::new_unique();
+        let fee_config = fee_config();
+        let instruction = swap_fee_on_exchange(&mint, &fee_config, &pool(), 1_000);
+        assert_eq!(instruction.program_id, sol_earna::ID);
+        assert_eq!(instruction.data, ix_data::SwapFeeOnExchange { amount: 1_000 }.data());
+
+        let recipients = &instruction.accounts[instruction.accounts.len() - 3..];
+        for (meta, fee_recipient) in recipients.iter().zip(&fee_config.fee_recipients) {
+            assert_eq!(*meta, AccountMeta::new(fee_recipient.recipient, false));
+        }
+        assert!(signers(&instruction).is_empty());
+    }
+
+    #[test]
+    fn swap_without_an_oracle_passes_the_program_as_the_none_account() {
+        let mint = Pubkey::new_unique();
+        let fee_config = fee_config();
+        assert_eq!(price_oracle(&fee_config), None);
+        let metas = swap_accounts(&mint, &fee_config, &pool()).to_account_metas(None);
+        assert_eq!(
+            metas.last().unwrap(),
+            &AccountMeta::new_readonly(sol_earna::ID, false)
+        );
+        assert_eq!(metas[1], AccountMeta::new(pda::fee_config(&mint), false));
+    }
+
+    #[test]
+    fn stake_derives_the_treasury_accounts() {
+        let treasury = treasury();
+        let user = Pubkey::new_unique();
+        let instruction = stake(&treasury, &user, &spl_token::ID, 1_000, 0, 1);
+        let treasury_key = pda::treasury(&treasury.treasury_mint);
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(keys[0], treasury_key);
+        assert!(keys.contains(&pda::staker_info(&treasury_key, &user)));
+        assert!(keys.contains(&get_associated_token_address_with_program_id(
+            &user,
+            &treasury.treasury_mint,
+            &spl_token::ID
+        )));
+        assert_eq!(signers(&instruction), [user]);
+    }
+
+    #[test]
+    fn hooked_withdrawal_is_the_users_transfer_from_the_treasury() {
+        let treasury = treasury();
+        let user = Pubkey::new_unique();
+        let instruction = hooked_withdrawal(&treasury, &user, 1_000, 9).unwrap();
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        let mint = &treasury.treasury_mint;
+        assert_eq!(
+            keys[..4],
+            [
+                treasury.treasury_token_account,
+                *mint,
+                user_token_account(&treasury, &user, &spl_token_2022::ID),
+                user
+            ]
+        );
+        // the treasury owns the source, the user is its delegate
+        assert!(keys.contains(&pda::fee_exemption(mint, &pda::treasury(mint))));
+        assert!(keys.contains(&pda::sell_cooldown(mint, &user)));
+        assert_eq!(signers(&instruction), [user]);
+    }
+
+    #[test]
+    fn fee_config_instructions_target_the_mint_pdas() {
+        let authority = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let instruction = add_fee_exemption(&authority, &mint, &Pubkey::new_unique());
+        assert!(instruction
+            .accounts
+            .iter()
+            .any(|meta| meta.pubkey == pda::fee_config(&mint)));
+        assert_eq!(signers(&instruction), [authority]);
+    }
+}
@@ -0,0 +1,10 @@
+//! Client helpers for the sol-earna program: PDA derivation, instruction builders,
+//! account deserializers and the extra accounts of a hooked transfer.
+
+pub mod accounts;
+pub mod instructions;
+pub mod pda;
+pub mod transfer_hook;
+
+// seeds and limits are the ones of the program
+pub use sol_earna::{constants, states, ID};
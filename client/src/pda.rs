@@ -0,0 +1,147 @@
+//! Program derived addresses of sol-earna.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::{associated_token::get_associated_token_address_with_program_id, token::spl_token};
+use sol_earna::constants::*;
+
+fn find(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &sol_earna::ID).0
+}
+
+pub fn extra_account_meta_list(mint: &Pubkey) -> Pubkey {
+    find(&[EXTRA_ACCOUNT_METAS_TAG, mint.as_ref()])
+}
+
+pub fn fee_config(mint: &Pubkey) -> Pubkey {
+    find(&[FEE_CONFIG_TAG, mint.as_ref()])
+}
+
+pub fn fee_accrual(mint: &Pubkey) -> Pubkey {
+    find(&[FEE_ACCRUAL_TAG, mint.as_ref()])
+}
+
+pub fn fee_exemption(mint: &Pubkey, wallet: &Pubkey) -> Pubkey {
+    find(&[FEE_EXEMPTION_TAG, mint.as_ref(), wallet.as_ref()])
+}
+
+pub fn amm_pool_vault(mint: &Pubkey, vault: &Pubkey) -> Pubkey {
+    find(&[AMM_POOL_VAULT_TAG, mint.as_ref(), vault.as_ref()])
+}
+
+pub fn blocklist_entry(mint: &Pubkey, wallet: &Pubkey) -> Pubkey {
+    find(&[BLOCKLIST_TAG, mint.as_ref(), wallet.as_ref()])
+}
+
+pub fn sell_cooldown(mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+    find(&[SELL_COOLDOWN_TAG, mint.as_ref(), owner.as_ref()])
+}
+
+pub fn delegate(mint: &Pubkey) -> Pubkey {
+    find(&[DELEGATE_TAG, mint.as_ref()])
+}
+
+pub fn treasury(treasury_mint: &Pubkey) -> Pubkey {
+    find(&[TREASURY_TAG, treasury_mint.as_ref()])
+}
+
+pub fn user_wrapper_token_account(wrapper_mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+    find(&[USER_WRAPPER_TOKEN_ACCOUNT_TAG, wrapper_mint.as_ref(), owner.as_ref()])
+}
+
+pub fn staker_info(treasury: &Pubkey, user: &Pubkey) -> Pubkey {
+    find(&[STAKER_INFO_TAG, treasury.as_ref(), user.as_ref()])
+}
+
+pub fn reward_authority(treasury: &Pubkey) -> Pubkey {
+    find(&[REWARD_AUTHORITY_TAG, treasury.as_ref()])
+}
+
+pub fn withdrawal_ticket(treasury: &Pubkey, user: &Pubkey) -> Pubkey {
+    find(&[WITHDRAWAL_TICKET_TAG, treasury.as_ref(), user.as_ref()])
+}
+
+// wsol ATA of the reward authority, the holders fee recipient
+pub fn reward_vault(treasury: &Pubkey) -> Pubkey {
+    wsol_token_account(&reward_authority(treasury))
+}
+
+// wrapper ATA of the delegate, fees are minted here before the swap
+pub fn fee_wrapper_token_account(mint: &Pubkey, wrapper_mint: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(&delegate(mint), wrapper_mint, &spl_token::ID)
+}
+
+pub fn wsol_token_account(owner: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, &spl_token::native_mint::ID, &spl_token::ID)
+}
+
+pub fn liquidity_authority(mint: &Pubkey) -> Pubkey {
+    find(&[LIQUIDITY_AUTHORITY_TAG, mint.as_ref()])
+}
+
+pub fn liquidity_position(mint: &Pubkey) -> Pubkey {
+    find(&[LIQUIDITY_POSITION_TAG, mint.as_ref()])
+}
+
+pub fn position_nft_mint(mint: &Pubkey) -> Pubkey {
+    find(&[POSITION_NFT_MINT_TAG, mint.as_ref()])
+}
+
+// wsol ATA of the liquidity authority, the liquidity fee recipient
+pub fn liquidity_wsol_token_account(mint: &Pubkey) -> Pubkey {
+    wsol_token_account(&liquidity_authority(mint))
+}
+
+pub fn buyback_authority(mint: &Pubkey) -> Pubkey {
+    find(&[BUYBACK_AUTHORITY_TAG, mint.as_ref()])
+}
+
+// wsol ATA of the buyback authority, the buyback fee vault
+pub fn buyback_wsol_token_account(mint: &Pubkey) -> Pubkey {
+    wsol_token_account(&buyback_authority(mint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn program_addresses_are_off_curve() {
+        let mint = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        for address in [
+            fee_config(&mint),
+            fee_exemption(&mint, &wallet),
+            blocklist_entry(&mint, &wallet),
+            sell_cooldown(&mint, &wallet),
+            treasury(&mint),
+        ] {
+            assert!(!address.is_on_curve());
+        }
+    }
+
+    #[test]
+    fn wallet_entries_are_distinct_per_tag_and_mint() {
+        let mint = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        assert_ne!(fee_exemption(&mint, &wallet), blocklist_entry(&mint, &wallet));
+        assert_ne!(
+            fee_exemption(&mint, &wallet),
+            fee_exemption(&Pubkey::new_unique(), &wallet)
+        );
+    }
+
+    #[test]
+    fn vaults_are_the_token_accounts_of_their_authorities() {
+        let mint = Pubkey::new_unique();
+        let treasury_key = treasury(&mint);
+        assert_eq!(
+            reward_vault(&treasury_key),
+            get_associated_token_address_with_program_id(
+                &reward_authority(&treasury_key),
+                &spl_token::native_mint::ID,
+                &spl_token::ID
+            )
+        );
+        assert_eq!(buyback_wsol_token_account(&mint), wsol_token_account(&buyback_authority(&mint)));
+    }
+}
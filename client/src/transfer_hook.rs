@@ -0,0 +1,137 @@
+//! Extra accounts of a transfer of the hooked mint, resolved from the
+//! ExtraAccountMetaList written by `initialize_extra_account_meta_list`.
+
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_lang::solana_program::{instruction::Instruction, program_error::ProgramError};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_hook, StateWithExtensions},
+    state::Mint,
+};
+use spl_transfer_hook_interface::instruction::TransferHookInstruction;
+
+use crate::pda;
+
+pub struct Transfer {
+    pub source: Pubkey,
+    pub mint: Pubkey,
+    pub destination: Pubkey,
+    pub owner: Pubkey,             // authority of the transfer
+    pub source_owner: Pubkey,      // owner of the source token account, `owner` unless delegated
+    pub destination_owner: Pubkey, // owner of the destination token account
+}
+
+// a mint hooked by sol-earna, its treasury transfers are the hooked_deposit and
+// hooked_withdrawal top level instructions
+pub fn is_self_hooked(mint_data: &[u8]) -> Result<bool, ProgramError> {
+    let mint = StateWithExtensions::<Mint>::unpack(mint_data)?;
+    Ok(transfer_hook::get_program_id(&mint) == Some(sol_earna::ID))
+}
+
+// mint and owner, the token account fields read by the AccountData seeds
+fn token_account_data(mint: &Pubkey, owner: &Pubkey) -> Vec<u8> {
+    [mint.as_ref(), owner.as_ref()].concat()
+}
+
+// accounts 5..=14 of the hook, followed by the hook program and the meta list
+pub fn extra_account_metas(transfer: &Transfer, amount: u64) -> Result<Vec<AccountMeta>, ProgramError> {
+    let mint = &transfer.mint;
+    let meta_list = pda::extra_account_meta_list(mint);
+    let instruction_data = TransferHookInstruction::Execute { amount }.pack();
+
+    // accounts 0..=4 of the Execute instruction, data only for the token accounts
+    let mut accounts = vec![
+        (transfer.source, Some(token_account_data(mint, &transfer.source_owner))),
+        (*mint, None),
+        (transfer.destination, Some(token_account_data(mint, &transfer.destination_owner))),
+        (transfer.owner, None),
+        (meta_list, None),
+    ];
+    let mut metas = vec![];
+    for extra_meta in sol_earna::extra_account_metas(&pda::fee_config(mint))? {
+        let meta = extra_meta.resolve(&instruction_data, &sol_earna::ID, |index| {
+            accounts
+                .get(index)
+                .map(|(key, data)| (key, data.as_deref()))
+        })?;
+        accounts.push((meta.pubkey, None));
+        metas.push(meta);
+    }
+    metas.push(AccountMeta::new_readonly(sol_earna::ID, false));
+    metas.push(AccountMeta::new_readonly(meta_list, false));
+    Ok(metas)
+}
+
+// token-2022 transfer_checked with the extra accounts of the hook
+pub fn transfer_checked(
+    transfer: &Transfer,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = spl_token_2022::instruction::transfer_checked(
+        &spl_token_2022::ID,
+        &transfer.source,
+        &transfer.mint,
+        &transfer.destination,
+        &transfer.owner,
+        &[],
+        amount,
+        decimals,
+    )?;
+    instruction.accounts.extend(extra_account_metas(transfer, amount)?);
+    Ok(instruction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a delegated transfer, the authority differs from the source token account owner
+    fn delegated_transfer() -> Transfer {
+        Transfer {
+            source: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            destination: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            source_owner: Pubkey::new_unique(),
+            destination_owner: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn resolves_the_program_list_in_hook_order() {
+        let transfer = delegated_transfer();
+        let mint = &transfer.mint;
+        assert_eq!(
+            extra_account_metas(&transfer, 1).unwrap(),
+            vec![
+                AccountMeta::new_readonly(pda::fee_config(mint), false),
+                AccountMeta::new_readonly(pda::treasury(mint), false),
+                AccountMeta::new(pda::fee_accrual(mint), false),
+                AccountMeta::new_readonly(pda::fee_exemption(mint, &transfer.source_owner), false),
+                AccountMeta::new_readonly(pda::fee_exemption(mint, &transfer.destination_owner), false),
+                AccountMeta::new_readonly(pda::amm_pool_vault(mint, &transfer.source), false),
+                AccountMeta::new_readonly(pda::amm_pool_vault(mint, &transfer.destination), false),
+                AccountMeta::new(pda::sell_cooldown(mint, &transfer.owner), false),
+                AccountMeta::new_readonly(pda::blocklist_entry(mint, &transfer.source_owner), false),
+                AccountMeta::new_readonly(pda::blocklist_entry(mint, &transfer.destination_owner), false),
+                AccountMeta::new_readonly(sol_earna::ID, false),
+                AccountMeta::new_readonly(pda::extra_account_meta_list(mint), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn transfer_checked_appends_the_extra_accounts() {
+        let transfer = delegated_transfer();
+        let instruction = transfer_checked(&transfer, 1_000, 9).unwrap();
+        assert_eq!(instruction.program_id, spl_token_2022::ID);
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(
+            keys[..4],
+            [transfer.source, transfer.mint, transfer.destination, transfer.owner]
+        );
+        assert!(instruction.accounts[3].is_signer);
+        assert_eq!(instruction.accounts[4..], extra_account_metas(&transfer, 1_000).unwrap()[..]);
+    }
+}
@@ -0,0 +1,462 @@
+//! Minimal stand-in for the Raydium CLMM program, deployed at the raydium program id
+//! on the local validator (see `[[test.genesis]]` in Anchor.toml).
+//! Only `swap`, `open_position` and `increase_liquidity` are implemented, with the same
+//! discriminators and account order as `raydium_amm_v3::cpi`. The swap is always 1:1 and
+//! leaves the min-out to the caller's own slippage check. The deposits take the raydium
+//! amounts at the pool price and reject them above the maxes like raydium does, but no
+//! position nft is minted and no ticks are tracked. `set_observations`,
+//! `set_sqrt_price` and `set_token_vaults` write the raydium pool and oracle layouts
+//! that the caller prices swaps and deposits from.
+
+use anchor_lang::{
+    prelude::*,
+    system_program::{create_account, CreateAccount},
+    Discriminator,
+};
+use anchor_spl::{
+    token::{transfer, Token, Transfer},
+    token_interface::TokenAccount,
+};
+use raydium_amm_v3::{
+    libraries::{big_num::U128, fixed_point_64, full_math::MulDiv, liquidity_math, tick_math},
+    states::{
+        Observation, ObservationState, PersonalPositionState, PoolState, OBSERVATION_NUM,
+        POSITION_SEED,
+    },
+};
+
+declare_id!("6weQP6SNcqqk8KnQGcM2rzU1Xk9o9atJD8kvASVCrN55");
+
+pub const POOL_STATE_TAG: &[u8] = b"pool-state";
+
+#[program]
+pub mod mock_clmm {
+    use super::*;
+
+    pub fn swap(
+        ctx: Context<SwapSingle>,
+        amount: u64,
+        _other_amount_threshold: u64,
+        _sqrt_price_limit_x64: u128,
+        is_base_input: bool,
+    ) -> Result<()> {
+        require!(is_base_input, MockError::NotSupported);
+
+        transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.input_token_account.to_account_info(),
+                    to: ctx.accounts.input_vault.to_account_info(),
+                    authority: ctx.accounts.payer.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+
+        let signer_seeds: &[&[&[u8]]] = &[&[POOL_STATE_TAG, &[ctx.bumps.pool_state]]];
+        transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.output_vault.to_account_info(),
+                    to: ctx.accounts.output_token_account.to_account_info(),
+                    authority: ctx.accounts.pool_state.to_account_info(),
+                },
+            )
+            .with_signer(signer_seeds),
+            amount,
+        )?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn open_position(
+        ctx: Context<OpenPosition>,
+        tick_lower_index: i32,
+        tick_upper_index: i32,
+        _tick_array_lower_start_index: i32,
+        _tick_array_upper_start_index: i32,
+        liquidity: u128,
+        amount_0_max: u64,
+        amount_1_max: u64,
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        let personal_position = &mut accounts.personal_position;
+        personal_position.bump = ctx.bumps.personal_position;
+        personal_position.nft_mint = accounts.position_nft_mint.key();
+        personal_position.pool_id = accounts.pool_state.key();
+        personal_position.tick_lower_index = tick_lower_index;
+        personal_position.tick_upper_index = tick_upper_index;
+        personal_position.liquidity = liquidity;
+
+        deposit(
+            &accounts.pool_state,
+            tick_lower_index,
+            tick_upper_index,
+            liquidity,
+            amount_0_max,
+            amount_1_max,
+            accounts.payer.to_account_info(),
+            [&accounts.token_account_0, &accounts.token_account_1],
+            [&accounts.token_vault_0, &accounts.token_vault_1],
+            accounts.token_program.to_account_info(),
+        )
+    }
+
+    pub fn increase_liquidity(
+        ctx: Context<IncreaseLiquidity>,
+        liquidity: u128,
+        amount_0_max: u64,
+        amount_1_max: u64,
+    ) -> Result<()> {
+        let accounts = ctx.accounts;
+        let personal_position = &mut accounts.personal_position;
+        personal_position.liquidity = personal_position
+            .liquidity
+            .checked_add(liquidity)
+            .ok_or(MockError::NotSupported)?;
+
+        deposit(
+            &accounts.pool_state,
+            personal_position.tick_lower_index,
+            personal_position.tick_upper_index,
+            liquidity,
+            amount_0_max,
+            amount_1_max,
+            accounts.nft_owner.to_account_info(),
+            [&accounts.token_account_0, &accounts.token_account_1],
+            [&accounts.token_vault_0, &accounts.token_vault_1],
+            accounts.token_program.to_account_info(),
+        )
+    }
+
+    // pairs the pool with its vaults, after `set_observations` created it
+    pub fn set_token_vaults(ctx: Context<SetTokenVaults>) -> Result<()> {
+        let mut pool_state = ctx.accounts.pool_state.load_mut()?;
+        pool_state.token_mint_1 = ctx.accounts.token_vault_1.mint;
+        pool_state.token_vault_0 = ctx.accounts.token_vault_0.key();
+        pool_state.token_vault_1 = ctx.accounts.token_vault_1.key();
+        Ok(())
+    }
+
+    // moves the pool price without an observation, like a swap earlier in the block
+    pub fn set_sqrt_price(ctx: Context<SetSqrtPrice>, sqrt_price_x64: u128) -> Result<()> {
+        ctx.accounts.pool_state.load_mut()?.sqrt_price_x64 = sqrt_price_x64;
+        Ok(())
+    }
+
+    // price history of the pool, oldest first, `seconds_ago` is relative to the clock
+    pub fn set_observations(
+        ctx: Context<SetObservations>,
+        token_mint_0: Pubkey,
+        observations: Vec<MockObservation>,
+    ) -> Result<()> {
+        require!(
+            !observations.is_empty() && observations.len() <= OBSERVATION_NUM,
+            MockError::NotSupported
+        );
+        let now = Clock::get()?.unix_timestamp as u32;
+
+        let pool_state_info = ctx.accounts.pool_state.to_account_info();
+        if pool_state_info.data_is_empty() {
+            let signer_seeds: &[&[&[u8]]] = &[&[POOL_STATE_TAG, &[ctx.bumps.pool_state]]];
+            create_account(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    CreateAccount {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: pool_state_info.clone(),
+                    },
+                )
+                .with_signer(signer_seeds),
+                Rent::get()?.minimum_balance(PoolState::LEN),
+                PoolState::LEN as u64,
+                &crate::ID,
+            )?;
+        }
+
+        let observation_state_info = ctx.accounts.observation_state.to_account_info();
+        require!(
+            observation_state_info.data_len() == ObservationState::LEN,
+            MockError::NotSupported
+        );
+        let mut data = observation_state_info.try_borrow_mut_data()?;
+        data[..8].copy_from_slice(&ObservationState::DISCRIMINATOR);
+        let observation_state =
+            bytemuck::from_bytes_mut::<ObservationState>(&mut data[8..ObservationState::LEN]);
+        observation_state.initialized = true;
+        observation_state.pool_id = pool_state_info.key();
+        observation_state.observations = [Observation::default(); OBSERVATION_NUM];
+        // the cumulative adds each price for the time since the previous observation
+        let mut cumulative_time_price_x64 = 0u128;
+        let mut previous_timestamp = None;
+        for (i, observation) in observations.iter().enumerate() {
+            let block_timestamp = now.saturating_sub(observation.seconds_ago);
+            if let Some(previous_timestamp) = previous_timestamp {
+                let price_x64 = U128::from(observation.sqrt_price_x64)
+                    .mul_div_floor(
+                        U128::from(observation.sqrt_price_x64),
+                        U128::from(fixed_point_64::Q64),
+                    )
+                    .ok_or(MockError::NotSupported)?
+                    .as_u128();
+                cumulative_time_price_x64 = cumulative_time_price_x64.wrapping_add(
+                    price_x64 * block_timestamp.saturating_sub(previous_timestamp) as u128,
+                );
+            }
+            observation_state.observations[i] = Observation {
+                block_timestamp,
+                sqrt_price_x64: observation.sqrt_price_x64,
+                cumulative_time_price_x64,
+                padding: 0,
+            };
+            previous_timestamp = Some(block_timestamp);
+        }
+        drop(data);
+
+        let mut data = pool_state_info.try_borrow_mut_data()?;
+        data[..8].copy_from_slice(&PoolState::DISCRIMINATOR);
+        let pool_state = bytemuck::from_bytes_mut::<PoolState>(&mut data[8..PoolState::LEN]);
+        pool_state.token_mint_0 = token_mint_0;
+        pool_state.observation_key = observation_state_info.key();
+        pool_state.observation_index = (observations.len() - 1) as u16;
+        pool_state.sqrt_price_x64 = observations[observations.len() - 1].sqrt_price_x64;
+
+        Ok(())
+    }
+}
+
+// pays the raydium amounts of `liquidity` at the pool price from the token accounts
+#[allow(clippy::too_many_arguments)]
+fn deposit<'info>(
+    pool_state: &AccountLoader<'info, PoolState>,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    liquidity: u128,
+    amount_0_max: u64,
+    amount_1_max: u64,
+    authority: AccountInfo<'info>,
+    token_accounts: [&InterfaceAccount<'info, TokenAccount>; 2],
+    token_vaults: [&InterfaceAccount<'info, TokenAccount>; 2],
+    token_program: AccountInfo<'info>,
+) -> Result<()> {
+    let (tick_current, sqrt_price_x64) = {
+        let pool_state = pool_state.load()?;
+        require_keys_eq!(token_vaults[0].key(), pool_state.token_vault_0);
+        require_keys_eq!(token_vaults[1].key(), pool_state.token_vault_1);
+        (
+            tick_math::get_tick_at_sqrt_price(pool_state.sqrt_price_x64)?,
+            pool_state.sqrt_price_x64,
+        )
+    };
+    let liquidity = i128::try_from(liquidity).map_err(|_| MockError::NotSupported)?;
+    let amounts = liquidity_math::get_delta_amounts_signed(
+        tick_current,
+        sqrt_price_x64,
+        tick_lower_index,
+        tick_upper_index,
+        liquidity,
+    )?;
+
+    for (((amount, amount_max), from), to) in [amounts.0, amounts.1]
+        .into_iter()
+        .zip([amount_0_max, amount_1_max])
+        .zip(token_accounts)
+        .zip(token_vaults)
+    {
+        // raydium doesn't check a zero max
+        require!(
+            amount_max == 0 || amount <= amount_max,
+            MockError::PriceSlippageCheck
+        );
+        if amount == 0 {
+            continue;
+        }
+        transfer(
+            CpiContext::new(
+                token_program.clone(),
+                Transfer {
+                    from: from.to_account_info(),
+                    to: to.to_account_info(),
+                    authority: authority.clone(),
+                },
+            ),
+            amount,
+        )?;
+    }
+    Ok(())
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct MockObservation {
+    pub seconds_ago: u32,
+    pub sqrt_price_x64: u128,
+}
+
+#[derive(Accounts)]
+pub struct SetObservations<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: written with the raydium pool layout, created on first use
+    #[account(
+        mut,
+        seeds = [POOL_STATE_TAG],
+        bump,
+    )]
+    pub pool_state: UncheckedAccount<'info>,
+    /// CHECK: written with the raydium oracle layout, created by the caller
+    #[account(mut, owner = crate::ID)]
+    pub observation_state: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SwapSingle<'info> {
+    pub payer: Signer<'info>,
+    /// CHECK: not used by the mock
+    pub amm_config: UncheckedAccount<'info>,
+    /// CHECK: vault authority only
+    #[account(
+        mut,
+        seeds = [POOL_STATE_TAG],
+        bump,
+    )]
+    pub pool_state: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub input_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub output_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = input_token_account.mint,
+        token::authority = pool_state,
+    )]
+    pub input_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = output_token_account.mint,
+        token::authority = pool_state,
+    )]
+    pub output_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    /// CHECK: not used by the mock
+    #[account(mut)]
+    pub observation_state: UncheckedAccount<'info>,
+    pub token_program: Program<'info, Token>,
+    /// CHECK: not used by the mock
+    #[account(mut)]
+    pub tick_array: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct OpenPosition<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// CHECK: not used by the mock
+    pub position_nft_owner: UncheckedAccount<'info>,
+    /// CHECK: the nft is not minted by the mock
+    #[account(mut)]
+    pub position_nft_mint: UncheckedAccount<'info>,
+    /// CHECK: the nft is not minted by the mock
+    #[account(mut)]
+    pub position_nft_account: UncheckedAccount<'info>,
+    /// CHECK: not used by the mock
+    #[account(mut)]
+    pub metadata_account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub pool_state: AccountLoader<'info, PoolState>,
+    /// CHECK: not used by the mock
+    #[account(mut)]
+    pub protocol_position: UncheckedAccount<'info>,
+    /// CHECK: not used by the mock
+    #[account(mut)]
+    pub tick_array_lower: UncheckedAccount<'info>,
+    /// CHECK: not used by the mock
+    #[account(mut)]
+    pub tick_array_upper: UncheckedAccount<'info>,
+    #[account(
+        init,
+        seeds = [POSITION_SEED.as_bytes(), position_nft_mint.key().as_ref()],
+        bump,
+        payer = payer,
+        space = PersonalPositionState::LEN,
+    )]
+    pub personal_position: Box<Account<'info, PersonalPositionState>>,
+    #[account(mut)]
+    pub token_account_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub token_account_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub token_vault_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub token_vault_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    /// CHECK: not used by the mock
+    pub associated_token_program: UncheckedAccount<'info>,
+    /// CHECK: not used by the mock
+    pub metadata_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct IncreaseLiquidity<'info> {
+    pub nft_owner: Signer<'info>,
+    /// CHECK: the nft is not minted by the mock
+    pub nft_account: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub pool_state: AccountLoader<'info, PoolState>,
+    /// CHECK: not used by the mock
+    #[account(mut)]
+    pub protocol_position: UncheckedAccount<'info>,
+    #[account(mut, constraint = personal_position.pool_id == pool_state.key())]
+    pub personal_position: Box<Account<'info, PersonalPositionState>>,
+    /// CHECK: not used by the mock
+    #[account(mut)]
+    pub tick_array_lower: UncheckedAccount<'info>,
+    /// CHECK: not used by the mock
+    #[account(mut)]
+    pub tick_array_upper: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub token_account_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub token_account_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub token_vault_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub token_vault_1: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetTokenVaults<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_STATE_TAG],
+        bump,
+    )]
+    pub pool_state: AccountLoader<'info, PoolState>,
+    #[account(token::authority = pool_state)]
+    pub token_vault_0: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(token::authority = pool_state)]
+    pub token_vault_1: Box<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct SetSqrtPrice<'info> {
+    #[account(
+        mut,
+        seeds = [POOL_STATE_TAG],
+        bump,
+    )]
+    pub pool_state: AccountLoader<'info, PoolState>,
+}
+
+#[error_code]
+pub enum MockError {
+    #[msg("Not supported by the mock")]
+    NotSupported,
+    #[msg("Deposit above the max amount")]
+    PriceSlippageCheck,
+}
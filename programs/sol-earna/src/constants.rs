@@ -1,6 +1,48 @@
 pub const EXTRA_ACCOUNT_METAS_TAG: &[u8] = b"extra-account-metas";
 pub const FEE_CONFIG_TAG: &[u8] = b"fee-config";
+pub const FEE_ACCRUAL_TAG: &[u8] = b"fee-accrual";
+pub const FEE_EXEMPTION_TAG: &[u8] = b"fee-exemption";
+pub const AMM_POOL_VAULT_TAG: &[u8] = b"amm-pool-vault";
+pub const SELL_COOLDOWN_TAG: &[u8] = b"sell-cooldown";
+pub const BLOCKLIST_TAG: &[u8] = b"blocklist";
+
+pub const FEE_PERCENT_DENOMINATOR: u16 = 10000; // 100%
+pub const DEFAULT_FEE_PERCENT_CAP: u16 = 2000; // 20%
+pub const MAX_FEE_RECIPIENTS: usize = 8;
+pub const MAX_FEE_RECIPIENT_LABEL_LEN: usize = 16;
+pub const MAX_PRICE_WINDOW: u32 = 24 * 60 * 60; // 1 day
+pub const DEFAULT_MAX_SLIPPAGE_BPS: u16 = 100; // 1%
+pub const DEFAULT_PRICE_WINDOW: u32 = 30 * 60; // 30 minutes
+pub const MAX_SNIPER_FEE_DURATION: u32 = 24 * 60 * 60; // 1 day
+pub const MAX_SELL_COOLDOWN: i64 = 24 * 60 * 60; // 1 day
+
+// layout versions of the state accounts, 1 is the unversioned layout (see states::TreasuryV1)
+pub const TREASURY_VERSION: u8 = 2;
+pub const FEE_CONFIG_VERSION: u8 = 2;
+pub const RESERVED_SPACE: usize = 64; // zeroed, taken by fields added without a realloc
 
 pub const DELEGATE_TAG:&[u8] = b"delegate";
 pub const TREASURY_TAG:&[u8] = b"treasury";
-pub const USER_WRAPPER_TOKEN_ACCOUNT_TAG:&[u8] = b"user-wrapper-token-account";
\ No newline at end of file
+pub const USER_WRAPPER_TOKEN_ACCOUNT_TAG:&[u8] = b"user-wrapper-token-account";
+pub const STAKER_INFO_TAG:&[u8] = b"staker-info";
+pub const REWARD_AUTHORITY_TAG:&[u8] = b"reward-authority";
+pub const WITHDRAWAL_TICKET_TAG:&[u8] = b"withdrawal-ticket";
+pub const LIQUIDITY_AUTHORITY_TAG:&[u8] = b"liquidity-authority";
+pub const LIQUIDITY_POSITION_TAG:&[u8] = b"liquidity-position";
+pub const POSITION_NFT_MINT_TAG:&[u8] = b"position-nft-mint";
+pub const BUYBACK_AUTHORITY_TAG:&[u8] = b"buyback-authority";
+
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000; // scale of acc_reward_per_share
+
+pub const VIRTUAL_SHARES: u64 = 1_000; // treasury share price offset, see utils::convert_to_shares
+pub const VIRTUAL_ASSETS: u64 = 1;
+
+pub const BASE_LOCK_MULTIPLIER_BPS: u16 = 10000; // 1x reward weight
+const DAY: i64 = 24 * 60 * 60;
+// (lock duration, reward weight multiplier) by lock option of `stake`
+pub const LOCK_OPTIONS: [(i64, u16); 4] = [
+    (0, BASE_LOCK_MULTIPLIER_BPS),
+    (30 * DAY, 12500),
+    (90 * DAY, 15000),
+    (180 * DAY, 20000),
+];
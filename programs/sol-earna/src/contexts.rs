@@ -5,6 +5,7 @@ use anchor_spl::{
     associated_token::AssociatedToken,
     token_interface::{Mint, TokenAccount, TokenInterface},
 };
+use raydium_amm_v3::{program::AmmV3, states::PoolState};
 
 use crate::*;
 use states::*;
@@ -13,6 +14,7 @@ use constants::*;
 
 
 #[derive(Accounts)]
+#[instruction(fee_percent: u16, fee_recipients: Vec<FeeRecipient>)]
 pub struct InitializeExtraAccountMetaList<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -24,6 +26,9 @@ pub struct InitializeExtraAccountMetaList<'info> {
         bump
     )]
     pub extra_account_meta_list: AccountInfo<'info>,
+    #[account(
+        constraint = treasury.treasury_mint == mint.key() @ XError::InvalidTreasuryMint,
+    )]
     pub mint: InterfaceAccount<'info, Mint>,
     pub token_program: Interface<'info, TokenInterface>,
     pub token_program_org: Interface<'info, TokenInterface>,
@@ -35,27 +40,85 @@ pub struct InitializeExtraAccountMetaList<'info> {
         seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
         bump,
         payer = payer,
-        space = std::mem::size_of::<FeeConfig>() + 8,
+        space = FeeConfig::space(fee_recipients.len()),
     )]
     pub fee_config: Account<'info, FeeConfig>,
 
+    #[account(
+        init_if_needed,
+        seeds = [FEE_ACCRUAL_TAG, mint.key().as_ref()],
+        bump,
+        payer = payer,
+        space = std::mem::size_of::<FeeAccrual>() + 8,
+    )]
+    pub fee_accrual: Box<Account<'info, FeeAccrual>>,
+
     #[account(
         mut,
         seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
         bump,
+        constraint = payer.key() == treasury.authority @ XError::NotAllowed,
     )]
     pub treasury: Account<'info, Treasury>,
 
     pub wsol_mint: InterfaceAccount<'info, Mint>,
 
+    #[account(
+        constraint = treasury.wrapper_mint == wrapper_mint.key() @ XError::InvalidWrapperMint,
+    )]
     pub wrapper_mint: InterfaceAccount<'info, Mint>,
 
+    /// CHECK: raydium clmm pool (wrapper_mint <-> wsol), validated by the raydium program on swap
+    pub pool_state: UncheckedAccount<'info>,
+}
+
+// upgrades a v1 fee config in place, its authority becomes the treasury authority.
+// also rewrites the ExtraAccountMetaList, v1 resolved other extra accounts
+#[derive(Accounts)]
+pub struct MigrateFeeConfig<'info> {
     #[account(mut)]
-    pub fee_recipient_liquidity: SystemAccount<'info>,
-    #[account(mut)]
-    pub fee_recipient_marketing: SystemAccount<'info>,
-    #[account(mut)]
-    pub fee_recipient_holders: SystemAccount<'info>,
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: v1 FeeConfig, parsed by migrate_fee_config
+    #[account(
+        mut,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+    )]
+    pub fee_config: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        seeds = [FEE_ACCRUAL_TAG, mint.key().as_ref()],
+        bump,
+        payer = authority,
+        space = std::mem::size_of::<FeeAccrual>() + 8,
+    )]
+    pub fee_accrual: Box<Account<'info, FeeAccrual>>,
+
+    /// CHECK: ExtraAccountMetaList Account, must use these seeds
+    #[account(
+        mut,
+        seeds = [EXTRA_ACCOUNT_METAS_TAG, mint.key().as_ref()],
+        bump,
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [TREASURY_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    /// CHECK: raydium clmm pool (wrapper_mint <-> wsol), validated by the raydium program on swap
+    pub pool_state: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub token_program_org: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
 }
 
 // Order of accounts matters for this struct.
@@ -64,9 +127,12 @@ pub struct InitializeExtraAccountMetaList<'info> {
 // These accounts are provided via CPI to this program from the token2022 program
 #[derive(Accounts)]
 pub struct TransferHook<'info> {
+    // only the treasury transfers through a delegate, it pays out the mint it holds that way
+    // (see utils::is_self_hooked). the sell cooldown is keyed on the transfer authority
     #[account(
-        token::mint = mint, 
-        token::authority = owner,
+        token::mint = mint,
+        constraint = source_token.owner == owner.key() || source_token.owner == treasury.key()
+            @ anchor_lang::error::ErrorCode::ConstraintTokenOwner,
     )]
     pub source_token: InterfaceAccount<'info, TokenAccount>, // 0
     pub mint: InterfaceAccount<'info, Mint>, // 1
@@ -83,40 +149,71 @@ pub struct TransferHook<'info> {
     )]
     pub extra_account_meta_list: UncheckedAccount<'info>, // 4
 
-    pub token_program: Interface<'info, TokenInterface>, // 5
-    pub token_program_org: Interface<'info, TokenInterface>, // 6
-    pub associated_token_program: Program<'info, AssociatedToken>, // 7
+    pub fee_config: Box<Account<'info, FeeConfig>>, // 5
+
+    #[account(
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>, // 6
 
     #[account(
         mut,
-        seeds = [DELEGATE_TAG, mint.key().as_ref()],
+        seeds = [FEE_ACCRUAL_TAG, mint.key().as_ref()],
         bump
     )]
-    pub delegate: SystemAccount<'info>, // 8
-
-    #[account(mut)]
-    pub fee_config: Box<Account<'info, FeeConfig>>, // 9
+    pub fee_accrual: Box<Account<'info, FeeAccrual>>, // 7
 
+    /// CHECK: FeeExemption of the source token account owner, may not exist
     #[account(
-        mut,
-        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
-        bump,
+        seeds = [FEE_EXEMPTION_TAG, mint.key().as_ref(), source_token.owner.as_ref()],
+        bump
     )]
-    pub treasury: Box<Account<'info, Treasury>>, // 10
+    pub source_fee_exemption: UncheckedAccount<'info>, // 8
+    /// CHECK: FeeExemption of the destination owner, may not exist
+    #[account(
+        seeds = [FEE_EXEMPTION_TAG, mint.key().as_ref(), destination_token.owner.as_ref()],
+        bump
+    )]
+    pub destination_fee_exemption: UncheckedAccount<'info>, // 9
 
-    pub wsol_mint: InterfaceAccount<'info, Mint>, // 11
+    /// CHECK: AmmPoolVault of the source token account, may not exist
+    #[account(
+        seeds = [AMM_POOL_VAULT_TAG, mint.key().as_ref(), source_token.key().as_ref()],
+        bump
+    )]
+    pub source_amm_pool_vault: UncheckedAccount<'info>, // 10
+    /// CHECK: AmmPoolVault of the destination token account, may not exist
+    #[account(
+        seeds = [AMM_POOL_VAULT_TAG, mint.key().as_ref(), destination_token.key().as_ref()],
+        bump
+    )]
+    pub destination_amm_pool_vault: UncheckedAccount<'info>, // 11
 
-    pub wrapper_mint: Box<InterfaceAccount<'info, Mint>>, // 12
+    /// CHECK: SellCooldown of the owner, may not exist
     #[account(
         mut,
-        token::mint = wrapper_mint,
-        token::authority = delegate,
+        seeds = [SELL_COOLDOWN_TAG, mint.key().as_ref(), owner.key().as_ref()],
+        bump
     )]
-    pub fee_wrapper_token_account: InterfaceAccount<'info, TokenAccount>, // 13
+    pub sell_cooldown: UncheckedAccount<'info>, // 12
+
+    /// CHECK: BlocklistEntry of the source token account owner, may not exist
+    #[account(
+        seeds = [BLOCKLIST_TAG, mint.key().as_ref(), source_token.owner.as_ref()],
+        bump
+    )]
+    pub source_blocklist_entry: UncheckedAccount<'info>, // 13
+    /// CHECK: BlocklistEntry of the destination token account owner, may not exist
+    #[account(
+        seeds = [BLOCKLIST_TAG, mint.key().as_ref(), destination_token.owner.as_ref()],
+        bump
+    )]
+    pub destination_blocklist_entry: UncheckedAccount<'info>, // 14
 }
 
+// remaining accounts: the wsol token accounts of fee_config.fee_recipients in order, writable
 #[derive(Accounts)]
-#[instruction(amount: u64)]
 pub struct SwapFeeOnExchange<'info> {
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
@@ -124,174 +221,1064 @@ pub struct SwapFeeOnExchange<'info> {
         seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
         bump,
     )]
-    pub fee_config: Account<'info, FeeConfig>,
+    pub fee_config: Box<Account<'info, FeeConfig>>,
 
     #[account(
         mut,
-        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
-        bump,
+        seeds = [DELEGATE_TAG, mint.key().as_ref()],
+        bump
     )]
-    pub treasury: Account<'info, Treasury>,
-
-    pub wsol_mint: InterfaceAccount<'info, Mint>,
+    pub delegate: SystemAccount<'info>,
 
-    pub wrapper_mint: InterfaceAccount<'info, Mint>,
+    #[account(address = fee_config.wsol_mint_address @ XError::InvalidAddress)]
+    pub wsol_mint: Box<InterfaceAccount<'info, Mint>>,
 
-    #[account(mut)]
-    pub fee_recipient_liquidity: SystemAccount<'info>,
     #[account(
         mut,
-        token::mint = wsol_mint,
-        token::authority = fee_recipient_liquidity,
+        address = fee_config.wrapper_mint_address @ XError::InvalidWrapperMint,
     )]
-    pub fee_liquidity_wsol_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
-    #[account(mut)]
-    pub fee_recipient_marketing: SystemAccount<'info>,
+    pub wrapper_mint: Box<InterfaceAccount<'info, Mint>>,
+
     #[account(
         mut,
-        token::mint = wsol_mint,
-        token::authority = fee_recipient_marketing,
+        token::mint = wrapper_mint,
+        token::authority = delegate,
     )]
-    pub fee_marketing_wsol_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
-    #[account(mut)]
-    pub fee_recipient_holders: SystemAccount<'info>,
+    pub fee_wrapper_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         token::mint = wsol_mint,
-        token::authority = fee_recipient_holders,
+        token::authority = delegate,
     )]
-    pub fee_holders_wsol_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
-}
-
+    pub fee_wsol_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
 
-#[derive(Accounts)]
-#[instruction()]
-pub struct CreateTreasury<'info> {
+    pub amm_program: Program<'info, AmmV3>,
+    /// CHECK: validated by the raydium program on swap
+    pub amm_config: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program on swap
     #[account(
-        init,
-        seeds = [TREASURY_TAG, treasury_mint.key().as_ref()],
-        bump,
-        payer = authority,
-        space = std::mem::size_of::<Treasury>() + 8
+        mut,
+        address = fee_config.amm_pool_state @ XError::InvalidAddress,
     )]
-    pub treasury: Box<Account<'info, Treasury>>,
-
+    pub pool_state: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program on swap
     #[account(mut)]
-    pub treasury_mint: InterfaceAccount<'info, Mint>,
-
-    #[account(
-        init_if_needed,
-        mint::decimals = treasury_mint.decimals,
-        mint::authority = treasury,
-        payer = authority,
-    )]
-    pub wrapper_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub input_vault: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program on swap
+    #[account(mut)]
+    pub output_vault: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program on swap
+    #[account(mut)]
+    pub observation_state: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program on swap
+    #[account(mut)]
+    pub tick_array: UncheckedAccount<'info>,
 
-    // #[account(mut)]
-    // pub wrapper_mint_auth: Signer<'info>,
-    #[account(
-        mut,
-        token::mint = treasury_mint,
-        token::authority = treasury,
-    )]
-    pub treasury_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Interface<'info, TokenInterface>, // should be TOKEN_PROGRAM_ID (not TOKEN_2022_PROGRAM_ID)
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    pub system_program: Program<'info, System>,
-    pub token_program: Interface<'info, TokenInterface>,
+    /// CHECK: pyth price account, required when fee_config.price_oracle is set
+    #[account(address = fee_config.price_oracle @ XError::InvalidAddress)]
+    pub price_oracle: Option<UncheckedAccount<'info>>,
 }
 
 #[derive(Accounts)]
-#[instruction(amount: u64)]
-pub struct Stake<'info> {
+pub struct UpdateFeeConfig<'info> {
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
     #[account(
-        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        mut,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
         bump,
+        has_one = authority @ XError::NotAllowed,
     )]
-    pub treasury: Box<Account<'info, Treasury>>,
+    pub fee_config: Account<'info, FeeConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetSlippageConfig<'info> {
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
     #[account(
         mut,
-        constraint = treasury.wrapper_mint == wrapper_mint.key() @ XError::InvalidWrapperMint,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
     )]
-    pub wrapper_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub fee_config: Account<'info, FeeConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetTransferLimits<'info> {
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
-        constraint = treasury.treasury_mint == treasury_mint.key() @ XError::InvalidTreasuryMint,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
     )]
-    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    pub fee_config: Account<'info, FeeConfig>,
+}
+
+#[derive(Accounts)]
+pub struct EnableTrading<'info> {
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
-        token::mint = treasury_mint,
-        token::authority = treasury,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
     )]
-    pub treasury_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub fee_config: Account<'info, FeeConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetSellCooldown<'info> {
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
-        token::mint = treasury_mint,
-        token::authority = user
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
     )]
-    pub user_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub fee_config: Account<'info, FeeConfig>,
+}
+
+// permissionless, sellers prepend it to their first sell
+#[derive(Accounts)]
+pub struct InitSellCooldown<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: transfer authority of the sells, any address
+    pub owner: UncheckedAccount<'info>,
 
     #[account(
         init_if_needed,
-        token::mint = wrapper_mint,
-        token::authority = user,
-        seeds = [USER_WRAPPER_TOKEN_ACCOUNT_TAG, wrapper_mint.key().as_ref(), user.key().as_ref()],
+        seeds = [SELL_COOLDOWN_TAG, mint.key().as_ref(), owner.key().as_ref()],
         bump,
-        payer = user,
+        payer = payer,
+        space = std::mem::size_of::<SellCooldown>() + 8,
     )]
-    pub user_wrapper_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
-
-    #[account(mut)]
-    pub user: Signer<'info>,
+    pub sell_cooldown: Account<'info, SellCooldown>,
     pub system_program: Program<'info, System>,
-    pub token_program: Interface<'info, TokenInterface>, // should be TOKEN_PROGRAM_ID (not TOKEN_2022_PROGRAM_ID)
-    pub token_program_treasury: Interface<'info, TokenInterface>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
 }
 
 #[derive(Accounts)]
-#[instruction(amount: u64)]
-pub struct Redeem<'info> {
+#[instruction(fee_recipients: Vec<FeeRecipient>)]
+pub struct SetFeeRecipients<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
     #[account(
-        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        mut,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
         bump,
+        has_one = authority @ XError::NotAllowed,
+        realloc = FeeConfig::space(fee_recipients.len()),
+        realloc::payer = authority,
+        realloc::zero = false,
     )]
-    pub treasury: Box<Account<'info, Treasury>>,
+    pub fee_config: Account<'info, FeeConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct AddFeeExemption<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
     #[account(
-        mut,
-        constraint = treasury.wrapper_mint == wrapper_mint.key() @ XError::InvalidWrapperMint,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
     )]
-    pub wrapper_mint: Box<InterfaceAccount<'info, Mint>>,
+    pub fee_config: Account<'info, FeeConfig>,
 
     #[account(
-        mut,
-        constraint = treasury.treasury_mint == treasury_mint.key() @ XError::InvalidTreasuryMint,
+        init,
+        seeds = [FEE_EXEMPTION_TAG, mint.key().as_ref(), wallet.as_ref()],
+        bump,
+        payer = authority,
+        space = std::mem::size_of::<FeeExemption>() + 8,
     )]
-    pub treasury_mint: InterfaceAccount<'info, Mint>,
+    pub fee_exemption: Account<'info, FeeExemption>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveFeeExemption<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(
-        mut,
-        token::mint = treasury_mint,
-        token::authority = treasury,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
     )]
-    pub treasury_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub fee_config: Account<'info, FeeConfig>,
 
     #[account(
         mut,
-        token::mint = treasury_mint,
-        token::authority = user
+        seeds = [FEE_EXEMPTION_TAG, mint.key().as_ref(), fee_exemption.wallet.as_ref()],
+        bump,
+        close = authority,
     )]
-    pub user_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub fee_exemption: Account<'info, FeeExemption>,
+}
 
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct AddToBlocklist<'info> {
     #[account(mut)]
-    pub user_wrapper_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
 
+    #[account(
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub fee_config: Account<'info, FeeConfig>,
+
+    #[account(
+        init,
+        seeds = [BLOCKLIST_TAG, mint.key().as_ref(), wallet.as_ref()],
+        bump,
+        payer = authority,
+        space = std::mem::size_of::<BlocklistEntry>() + 8,
+    )]
+    pub blocklist_entry: Account<'info, BlocklistEntry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveFromBlocklist<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub fee_config: Account<'info, FeeConfig>,
+
+    #[account(
+        mut,
+        seeds = [BLOCKLIST_TAG, mint.key().as_ref(), blocklist_entry.wallet.as_ref()],
+        bump,
+        close = authority,
+    )]
+    pub blocklist_entry: Account<'info, BlocklistEntry>,
+}
+
+#[derive(Accounts)]
+#[instruction(vault: Pubkey)]
+pub struct AddAmmPoolVault<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub fee_config: Account<'info, FeeConfig>,
+
+    #[account(
+        init,
+        seeds = [AMM_POOL_VAULT_TAG, mint.key().as_ref(), vault.as_ref()],
+        bump,
+        payer = authority,
+        space = std::mem::size_of::<AmmPoolVault>() + 8,
+    )]
+    pub amm_pool_vault: Account<'info, AmmPoolVault>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAmmPoolVault<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub fee_config: Account<'info, FeeConfig>,
+
+    #[account(
+        mut,
+        seeds = [AMM_POOL_VAULT_TAG, mint.key().as_ref(), amm_pool_vault.vault.as_ref()],
+        bump,
+        close = authority,
+    )]
+    pub amm_pool_vault: Account<'info, AmmPoolVault>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeFeeConfigAuthority<'info> {
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub fee_config: Account<'info, FeeConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeConfigGuardian<'info> {
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub fee_config: Account<'info, FeeConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeConfigPause<'info> {
+    pub signer: Signer<'info>, // authority or guardian
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        constraint = signer.key() == fee_config.authority
+            || signer.key() == fee_config.guardian @ XError::NotAllowed,
+    )]
+    pub fee_config: Account<'info, FeeConfig>,
+}
+
+#[derive(Accounts)]
+pub struct RenounceFeeConfigAuthority<'info> {
+    pub authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub fee_config: Account<'info, FeeConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptFeeConfigAuthority<'info> {
+    pub pending_authority: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+        has_one = pending_authority @ XError::NotAllowed,
+    )]
+    pub fee_config: Account<'info, FeeConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeTreasuryAuthority<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub treasury: Account<'info, Treasury>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptTreasuryAuthority<'info> {
+    pub pending_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+        has_one = pending_authority @ XError::NotAllowed,
+    )]
+    pub treasury: Account<'info, Treasury>,
+}
+
+#[derive(Accounts)]
+pub struct ProcessAccruedFees<'info> {
+    pub swap: SwapFeeOnExchange<'info>,
+
+    #[account(
+        mut,
+        seeds = [FEE_ACCRUAL_TAG, swap.mint.key().as_ref()],
+        bump,
+    )]
+    pub fee_accrual: Box<Account<'info, FeeAccrual>>,
+
+    #[account(
+        seeds = [TREASURY_TAG, swap.mint.key().as_ref()],
+        bump,
+        constraint = treasury.wrapper_mint == swap.wrapper_mint.key() @ XError::InvalidWrapperMint,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+}
+
+
+#[derive(Accounts)]
+#[instruction()]
+pub struct CreateTreasury<'info> {
+    #[account(
+        init,
+        seeds = [TREASURY_TAG, treasury_mint.key().as_ref()],
+        bump,
+        payer = authority,
+        space = Treasury::SPACE
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(mut)]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        mint::decimals = treasury_mint.decimals,
+        mint::authority = treasury,
+        payer = authority,
+    )]
+    pub wrapper_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    // #[account(mut)]
+    // pub wrapper_mint_auth: Signer<'info>,
+    #[account(
+        mut,
+        token::mint = treasury_mint,
+        token::authority = treasury,
+    )]
+    pub treasury_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// upgrades a v1 treasury in place, the authority pays for the realloc
+#[derive(Accounts)]
+pub struct MigrateTreasury<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: v1 Treasury, parsed by migrate_treasury
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury_mint.key().as_ref()],
+        bump,
+    )]
+    pub treasury: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, lock_option: u8, min_shares: u64)]
+pub struct Stake<'info> {
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(
+        mut,
+        constraint = treasury.treasury_mint == treasury_mint.key() @ XError::InvalidTreasuryMint,
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = treasury.treasury_token_account @ XError::InvalidAddress,
+    )]
+    pub treasury_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = treasury_mint,
+        token::authority = user
+    )]
+    pub user_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        seeds = [STAKER_INFO_TAG, treasury.key().as_ref(), user.key().as_ref()],
+        bump,
+        payer = user,
+        space = std::mem::size_of::<StakerInfo>() + 8,
+    )]
+    pub staker_info: Box<Account<'info, StakerInfo>>,
+
+    #[account(
+        seeds = [REWARD_AUTHORITY_TAG, treasury.key().as_ref()],
+        bump
+    )]
+    pub reward_authority: SystemAccount<'info>,
+    #[account(
+        associated_token::mint = wsol_mint,
+        associated_token::authority = reward_authority,
+    )]
+    pub reward_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID @ XError::InvalidAddress)]
+    pub wsol_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program_treasury: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// CHECK: instructions sysvar, the treasury transfer of a mint hooked by this program is a
+    /// top level instruction next to this one, see utils::is_self_hooked
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID @ XError::InvalidAddress)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(shares: u64)]
+pub struct Redeem<'info> {
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(
+        mut,
+        constraint = treasury.treasury_mint == treasury_mint.key() @ XError::InvalidTreasuryMint,
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = treasury.treasury_token_account @ XError::InvalidAddress,
+    )]
+    pub treasury_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = treasury_mint,
+        token::authority = user
+    )]
+    pub user_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        seeds = [STAKER_INFO_TAG, treasury.key().as_ref(), user.key().as_ref()],
+        bump,
+        payer = user,
+        space = std::mem::size_of::<StakerInfo>() + 8,
+    )]
+    pub staker_info: Box<Account<'info, StakerInfo>>,
+
+    #[account(
+        seeds = [REWARD_AUTHORITY_TAG, treasury.key().as_ref()],
+        bump
+    )]
+    pub reward_authority: SystemAccount<'info>,
+    #[account(
+        associated_token::mint = wsol_mint,
+        associated_token::authority = reward_authority,
+    )]
+    pub reward_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID @ XError::InvalidAddress)]
+    pub wsol_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    pub token_program_treasury: Interface<'info, TokenInterface>,
+    /// CHECK: instructions sysvar, the treasury transfer of a mint hooked by this program is a
+    /// top level instruction next to this one, see utils::is_self_hooked
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID @ XError::InvalidAddress)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetEarlyRedeemPenalty<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub treasury: Account<'info, Treasury>,
+}
+
+// permissionless, drops the boosted weight of an expired lock
+#[derive(Accounts)]
+pub struct ExpireLock<'info> {
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(
+        mut,
+        seeds = [STAKER_INFO_TAG, treasury.key().as_ref(), staker_info.user.as_ref()],
+        bump,
+    )]
+    pub staker_info: Box<Account<'info, StakerInfo>>,
+
+    #[account(
+        seeds = [REWARD_AUTHORITY_TAG, treasury.key().as_ref()],
+        bump
+    )]
+    pub reward_authority: SystemAccount<'info>,
+    #[account(
+        associated_token::mint = wsol_mint,
+        associated_token::authority = reward_authority,
+    )]
+    pub reward_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID @ XError::InvalidAddress)]
+    pub wsol_mint: Box<InterfaceAccount<'info, Mint>>,
+}
+
+#[derive(Accounts)]
+pub struct SetTreasuryGuardian<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub treasury: Account<'info, Treasury>,
+}
+
+#[derive(Accounts)]
+pub struct SetTreasuryPause<'info> {
+    pub signer: Signer<'info>, // authority or guardian
+
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+        constraint = signer.key() == treasury.authority
+            || signer.key() == treasury.guardian @ XError::NotAllowed,
+    )]
+    pub treasury: Account<'info, Treasury>,
+}
+
+#[derive(Accounts)]
+pub struct SetRedeemCooldown<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+        has_one = authority @ XError::NotAllowed,
+    )]
+    pub treasury: Account<'info, Treasury>,
+}
+
+#[derive(Accounts)]
+#[instruction(shares: u64)]
+pub struct RequestRedeem<'info> {
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(
+        init_if_needed,
+        seeds = [STAKER_INFO_TAG, treasury.key().as_ref(), user.key().as_ref()],
+        bump,
+        payer = user,
+        space = std::mem::size_of::<StakerInfo>() + 8,
+    )]
+    pub staker_info: Box<Account<'info, StakerInfo>>,
+
+    #[account(
+        init_if_needed,
+        seeds = [WITHDRAWAL_TICKET_TAG, treasury.key().as_ref(), user.key().as_ref()],
+        bump,
+        payer = user,
+        space = std::mem::size_of::<WithdrawalTicket>() + 8,
+    )]
+    pub withdrawal_ticket: Box<Account<'info, WithdrawalTicket>>,
+
+    #[account(
+        seeds = [REWARD_AUTHORITY_TAG, treasury.key().as_ref()],
+        bump
+    )]
+    pub reward_authority: SystemAccount<'info>,
+    #[account(
+        associated_token::mint = wsol_mint,
+        associated_token::authority = reward_authority,
+    )]
+    pub reward_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID @ XError::InvalidAddress)]
+    pub wsol_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CompleteRedeem<'info> {
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(
+        mut,
+        constraint = treasury.treasury_mint == treasury_mint.key() @ XError::InvalidTreasuryMint,
+    )]
+    pub treasury_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = treasury.treasury_token_account @ XError::InvalidAddress,
+    )]
+    pub treasury_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = treasury_mint,
+        token::authority = user
+    )]
+    pub user_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [WITHDRAWAL_TICKET_TAG, treasury.key().as_ref(), user.key().as_ref()],
+        bump,
+        has_one = user @ XError::NotAllowed,
+        close = user,
+    )]
+    pub withdrawal_ticket: Box<Account<'info, WithdrawalTicket>>,
+
+    #[account(mut)]
     pub user: Signer<'info>,
-    pub token_program: Interface<'info, TokenInterface>, // should be TOKEN_PROGRAM_ID (not TOKEN_2022_PROGRAM_ID)
     pub token_program_treasury: Interface<'info, TokenInterface>,
+    /// CHECK: instructions sysvar, the treasury transfer of a mint hooked by this program is a
+    /// top level instruction next to this one, see utils::is_self_hooked
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID @ XError::InvalidAddress)]
+    pub instructions: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRedeem<'info> {
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(
+        mut,
+        seeds = [STAKER_INFO_TAG, treasury.key().as_ref(), user.key().as_ref()],
+        bump,
+        has_one = user @ XError::NotAllowed,
+    )]
+    pub staker_info: Box<Account<'info, StakerInfo>>,
+
+    #[account(
+        mut,
+        seeds = [WITHDRAWAL_TICKET_TAG, treasury.key().as_ref(), user.key().as_ref()],
+        bump,
+        has_one = user @ XError::NotAllowed,
+        close = user,
+    )]
+    pub withdrawal_ticket: Box<Account<'info, WithdrawalTicket>>,
+
+    #[account(
+        seeds = [REWARD_AUTHORITY_TAG, treasury.key().as_ref()],
+        bump
+    )]
+    pub reward_authority: SystemAccount<'info>,
+    #[account(
+        associated_token::mint = wsol_mint,
+        associated_token::authority = reward_authority,
+    )]
+    pub reward_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID @ XError::InvalidAddress)]
+    pub wsol_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Preview<'info> {
+    #[account(
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+    #[account(
+        address = treasury.treasury_token_account @ XError::InvalidAddress,
+    )]
+    pub treasury_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(
+        mut,
+        seeds = [TREASURY_TAG, treasury.treasury_mint.as_ref()],
+        bump,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(
+        mut,
+        seeds = [STAKER_INFO_TAG, treasury.key().as_ref(), user.key().as_ref()],
+        bump,
+        has_one = user @ XError::NotAllowed,
+    )]
+    pub staker_info: Box<Account<'info, StakerInfo>>,
+
+    #[account(
+        seeds = [REWARD_AUTHORITY_TAG, treasury.key().as_ref()],
+        bump
+    )]
+    pub reward_authority: SystemAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = wsol_mint,
+        associated_token::authority = reward_authority,
+    )]
+    pub reward_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(address = anchor_spl::token::spl_token::native_mint::ID @ XError::InvalidAddress)]
+    pub wsol_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = wsol_mint,
+        token::authority = user,
+    )]
+    pub user_wsol_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub user: Signer<'info>,
+    pub token_program: Interface<'info, TokenInterface>, // should be TOKEN_PROGRAM_ID (not TOKEN_2022_PROGRAM_ID)
+}
+
+// liquidity fee wsol is sent to the wsol account of the liquidity authority
+// (one of fee_config.fee_recipients), half of it is swapped to wrapper_mint and both are
+// added to the raydium clmm position of the liquidity authority
+#[derive(Accounts)]
+pub struct AddAutoLiquidity<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+    )]
+    pub fee_config: Box<Account<'info, FeeConfig>>,
+    #[account(address = fee_config.wsol_mint_address @ XError::InvalidAddress)]
+    pub wsol_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(address = fee_config.wrapper_mint_address @ XError::InvalidWrapperMint)]
+    pub wrapper_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [LIQUIDITY_AUTHORITY_TAG, mint.key().as_ref()],
+        bump,
+    )]
+    pub liquidity_authority: SystemAccount<'info>,
+    #[account(
+        mut,
+        token::mint = wsol_mint,
+        token::authority = liquidity_authority,
+    )]
+    pub liquidity_wsol_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = wrapper_mint,
+        token::authority = liquidity_authority,
+    )]
+    pub liquidity_wrapper_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        seeds = [LIQUIDITY_POSITION_TAG, mint.key().as_ref()],
+        bump,
+        payer = payer,
+        space = std::mem::size_of::<LiquidityPosition>() + 8,
+    )]
+    pub liquidity_position: Box<Account<'info, LiquidityPosition>>,
+
+    /// CHECK: created by raydium on open_position
+    #[account(
+        mut,
+        seeds = [POSITION_NFT_MINT_TAG, mint.key().as_ref()],
+        bump,
+    )]
+    pub position_nft_mint: UncheckedAccount<'info>,
+    /// CHECK: nft ATA of the liquidity authority, validated by raydium
+    #[account(mut)]
+    pub position_nft_account: UncheckedAccount<'info>,
+    /// CHECK: nft metadata, validated by raydium
+    #[account(mut)]
+    pub metadata_account: UncheckedAccount<'info>,
+
+    pub amm_program: Program<'info, AmmV3>,
+    /// CHECK: validated by the raydium program on swap
+    pub amm_config: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        address = fee_config.amm_pool_state @ XError::InvalidAddress,
+    )]
+    pub pool_state: AccountLoader<'info, PoolState>,
+    /// CHECK: validated by the raydium program
+    #[account(mut)]
+    pub protocol_position: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program
+    #[account(mut)]
+    pub personal_position: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program
+    #[account(mut)]
+    pub tick_array_lower: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program
+    #[account(mut)]
+    pub tick_array_upper: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program
+    #[account(mut)]
+    pub token_vault_0: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program
+    #[account(mut)]
+    pub token_vault_1: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program on swap
+    #[account(mut)]
+    pub observation_state: UncheckedAccount<'info>,
+    /// CHECK: tick array crossed by the wsol -> wrapper_mint swap
+    #[account(mut)]
+    pub swap_tick_array: UncheckedAccount<'info>,
+
+    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>, // should be TOKEN_PROGRAM_ID (not TOKEN_2022_PROGRAM_ID)
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// CHECK: metaplex token metadata program, validated by raydium
+    pub metadata_program: UncheckedAccount<'info>,
+    /// CHECK: pyth price account, required when fee_config.price_oracle is set
+    #[account(address = fee_config.price_oracle @ XError::InvalidAddress)]
+    pub price_oracle: Option<UncheckedAccount<'info>>,
+}
+
+// buyback fee wsol is sent to the wsol account of the buyback authority (one of
+// fee_config.fee_recipients, its bps are the buyback share of the fee),
+// buyback_and_burn swaps it to wrapper_mint and burns it
+#[derive(Accounts)]
+pub struct BuybackAndBurn<'info> {
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [FEE_CONFIG_TAG, mint.key().as_ref()],
+        bump,
+    )]
+    pub fee_config: Box<Account<'info, FeeConfig>>,
+    #[account(
+        mut,
+        seeds = [FEE_ACCRUAL_TAG, mint.key().as_ref()],
+        bump,
+    )]
+    pub fee_accrual: Box<Account<'info, FeeAccrual>>,
+
+    #[account(address = fee_config.wsol_mint_address @ XError::InvalidAddress)]
+    pub wsol_mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        address = fee_config.wrapper_mint_address @ XError::InvalidWrapperMint,
+    )]
+    pub wrapper_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        seeds = [BUYBACK_AUTHORITY_TAG, mint.key().as_ref()],
+        bump,
+    )]
+    pub buyback_authority: SystemAccount<'info>,
+    #[account(
+        mut,
+        token::mint = wsol_mint,
+        token::authority = buyback_authority,
+        constraint = fee_config.fee_share(&buyback_wsol_token_account.key()) > 0 @ XError::NotAFeeRecipient,
+    )]
+    pub buyback_wsol_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = wrapper_mint,
+        token::authority = buyback_authority,
+    )]
+    pub buyback_wrapper_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub amm_program: Program<'info, AmmV3>,
+    /// CHECK: validated by the raydium program on swap
+    pub amm_config: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program on swap
+    #[account(
+        mut,
+        address = fee_config.amm_pool_state @ XError::InvalidAddress,
+    )]
+    pub pool_state: UncheckedAccount<'info>,
+    /// CHECK: wsol vault of the pool, validated by the raydium program on swap
+    #[account(mut)]
+    pub input_vault: UncheckedAccount<'info>,
+    /// CHECK: wrapper_mint vault of the pool, validated by the raydium program on swap
+    #[account(mut)]
+    pub output_vault: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program on swap
+    #[account(mut)]
+    pub observation_state: UncheckedAccount<'info>,
+    /// CHECK: validated by the raydium program on swap
+    #[account(mut)]
+    pub tick_array: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>, // should be TOKEN_PROGRAM_ID (not TOKEN_2022_PROGRAM_ID)
+
+    /// CHECK: pyth price account, required when fee_config.price_oracle is set
+    #[account(address = fee_config.price_oracle @ XError::InvalidAddress)]
+    pub price_oracle: Option<UncheckedAccount<'info>>,
 }
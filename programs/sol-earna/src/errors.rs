@@ -13,5 +13,89 @@ pub enum XError {
     InvalidTreasuryMint,
 
     #[msg("Invalid Wrapper Mint")]
-    InvalidWrapperMint
+    InvalidWrapperMint,
+
+    #[msg("Invalid Swap Result")]
+    InvalidSwapResult,
+
+    #[msg("Accrued fee is below the threshold")]
+    ThresholdNotReached,
+
+    #[msg("Fee is too high")]
+    FeeTooHigh,
+
+    #[msg("Math overflow")]
+    MathOverflow,
+
+    #[msg("Amount is too small")]
+    AmountTooSmall,
+
+    #[msg("Invalid lock option")]
+    InvalidLockOption,
+
+    #[msg("Stake is locked")]
+    StakeLocked,
+
+    #[msg("Redeem must go through request_redeem")]
+    RedeemCooldownActive,
+
+    #[msg("Withdrawal ticket is not claimable yet")]
+    CooldownNotElapsed,
+
+    #[msg("Paused")]
+    Paused,
+
+    #[msg("Invalid fee recipients")]
+    InvalidFeeRecipients,
+
+    #[msg("Invalid slippage config")]
+    InvalidSlippageConfig,
+
+    #[msg("Reference price is unavailable")]
+    PriceUnavailable,
+
+    #[msg("Swap output is below the slippage bound")]
+    SlippageExceeded,
+
+    #[msg("Invalid transfer limit")]
+    InvalidTransferLimit,
+
+    #[msg("Transfer amount exceeds the max transaction amount")]
+    MaxTxAmountExceeded,
+
+    #[msg("Destination balance exceeds the max wallet balance")]
+    MaxWalletBalanceExceeded,
+
+    #[msg("Trading is not enabled yet")]
+    TradingNotEnabled,
+
+    #[msg("Trading is already enabled")]
+    TradingAlreadyEnabled,
+
+    #[msg("Sell cooldown account is not initialized")]
+    SellCooldownNotInitialized,
+
+    #[msg("Sell is within the cooldown")]
+    SellCooldownActive,
+
+    #[msg("Address is blocklisted")]
+    AddressBlocklisted,
+
+    #[msg("Account is not in the layout this migration upgrades")]
+    InvalidAccountVersion,
+
+    #[msg("The token is not currently transferring")]
+    IsNotCurrentlyTransferring,
+
+    #[msg("Shares exceed the staked amount")]
+    InsufficientStake,
+
+    #[msg("Minted shares are below the minimum")]
+    SharesBelowMinimum,
+
+    #[msg("Expected the treasury transfer_checked next to this instruction")]
+    InvalidTopLevelTransfer,
+
+    #[msg("Account has no share of the fee")]
+    NotAFeeRecipient,
 }
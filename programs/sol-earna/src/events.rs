@@ -2,6 +2,8 @@
 
 use anchor_lang::prelude::*;
 
+use crate::states::{FeeRecipient, TransferDirection, TransferLimit};
+
 #[event]
 pub struct TreasuryCreated {
     pub authority: Pubkey,
@@ -15,6 +17,7 @@ pub struct Deposited {
     pub treasury: Pubkey,
     pub user: Pubkey,
     pub amount: u64,
+    pub shares: u64,
 }
 
 #[event]
@@ -22,4 +25,249 @@ pub struct Claimed {
     pub treasury: Pubkey,
     pub user: Pubkey,
     pub amount: u64,
-}
\ No newline at end of file
+    pub shares: u64,
+    pub penalty: u64, // left in the treasury for redeeming a locked stake
+}
+
+#[event]
+pub struct FeeDistributed {
+    pub mint: Pubkey,
+    pub wrapper_amount: u64,
+    pub wsol_amounts: Vec<u64>, // in the order of fee_config.fee_recipients
+}
+
+#[event]
+pub struct AccruedFeesProcessed {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub total_processed: u64,
+}
+
+#[event]
+pub struct FeeConfigUpdated {
+    pub mint: Pubkey,
+    pub old_transfer_fee: u16,
+    pub old_buy_fee: u16,
+    pub old_sell_fee: u16,
+    pub old_fee_percent_cap: u16,
+    pub old_accrual_threshold: u64,
+    pub new_transfer_fee: u16,
+    pub new_buy_fee: u16,
+    pub new_sell_fee: u16,
+    pub new_fee_percent_cap: u16,
+    pub new_accrual_threshold: u64,
+}
+
+#[event]
+pub struct SlippageConfigUpdated {
+    pub mint: Pubkey,
+    pub old_max_slippage_bps: u16,
+    pub old_price_window: u32,
+    pub old_price_oracle: Pubkey,
+    pub new_max_slippage_bps: u16,
+    pub new_price_window: u32,
+    pub new_price_oracle: Pubkey,
+}
+
+#[event]
+pub struct TransferLimitsUpdated {
+    pub mint: Pubkey,
+    pub old_max_tx_amount: TransferLimit,
+    pub old_max_wallet_balance: TransferLimit,
+    pub new_max_tx_amount: TransferLimit,
+    pub new_max_wallet_balance: TransferLimit,
+}
+
+#[event]
+pub struct TradingEnabled {
+    pub mint: Pubkey,
+    pub trading_enabled_at: i64,
+    pub sniper_fee: u16,
+    pub sniper_fee_duration: u32,
+}
+
+#[event]
+pub struct SellCooldownUpdated {
+    pub mint: Pubkey,
+    pub old_sell_cooldown: i64,
+    pub new_sell_cooldown: i64,
+}
+
+#[event]
+pub struct FeeRecipientsUpdated {
+    pub mint: Pubkey,
+    pub old_fee_recipients: Vec<FeeRecipient>,
+    pub new_fee_recipients: Vec<FeeRecipient>,
+}
+
+#[event]
+pub struct Blocklisted {
+    pub mint: Pubkey,
+    pub wallet: Pubkey,
+}
+
+#[event]
+pub struct Unblocklisted {
+    pub mint: Pubkey,
+    pub wallet: Pubkey,
+}
+
+#[event]
+pub struct AuthorityProposed {
+    pub account: Pubkey, // treasury or fee config
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+#[event]
+pub struct AuthorityAccepted {
+    pub account: Pubkey,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct AuthorityRenounced {
+    pub account: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct FeeExemptionAdded {
+    pub mint: Pubkey,
+    pub wallet: Pubkey,
+}
+
+#[event]
+pub struct FeeExemptionRemoved {
+    pub mint: Pubkey,
+    pub wallet: Pubkey,
+}
+
+#[event]
+pub struct FeeAccrued {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub direction: TransferDirection,
+    pub amount: u64,
+    pub fee: u64,
+}
+
+#[event]
+pub struct AmmPoolVaultAdded {
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+}
+
+#[event]
+pub struct AmmPoolVaultRemoved {
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+}
+
+#[event]
+pub struct RewardsClaimed {
+    pub treasury: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct LockCreated {
+    pub treasury: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub lock_end: i64,
+    pub lock_multiplier_bps: u16,
+}
+
+#[event]
+pub struct LockExpired {
+    pub treasury: Pubkey,
+    pub user: Pubkey,
+}
+
+#[event]
+pub struct EarlyRedeemPenaltyUpdated {
+    pub treasury: Pubkey,
+    pub old_penalty_bps: u16,
+    pub new_penalty_bps: u16,
+}
+
+#[event]
+pub struct RedeemRequested {
+    pub treasury: Pubkey,
+    pub user: Pubkey,
+    pub shares: u64,
+    pub penalty_shares: u64, // burned for requesting on a locked stake
+    pub claimable_at: i64,
+}
+
+#[event]
+pub struct RedeemCancelled {
+    pub treasury: Pubkey,
+    pub user: Pubkey,
+    pub shares: u64,
+}
+
+#[event]
+pub struct RedeemCooldownUpdated {
+    pub treasury: Pubkey,
+    pub old_redeem_cooldown: i64,
+    pub new_redeem_cooldown: i64,
+}
+
+#[event]
+pub struct GuardianUpdated {
+    pub account: Pubkey, // treasury or fee config
+    pub old_guardian: Pubkey,
+    pub new_guardian: Pubkey,
+}
+
+#[event]
+pub struct TreasuryPauseUpdated {
+    pub treasury: Pubkey,
+    pub signer: Pubkey,
+    pub stake_paused: bool,
+    pub redeem_paused: bool,
+}
+
+#[event]
+pub struct FeeConfigPauseUpdated {
+    pub mint: Pubkey,
+    pub signer: Pubkey,
+    pub fee_collection_paused: bool,
+    pub swap_paused: bool,
+}
+
+#[event]
+pub struct AutoLiquidityAdded {
+    pub mint: Pubkey,
+    pub position_nft_mint: Pubkey,
+    pub liquidity: u128,
+    pub wrapper_amount: u64,
+    pub wsol_amount: u64,
+}
+
+#[event]
+pub struct BuybackBurned {
+    pub mint: Pubkey,
+    pub wsol_amount: u64,
+    pub burned_amount: u64, // wrapper_mint
+    pub total_buyback_wsol: u64,
+    pub total_buyback_burned: u64,
+}
+
+#[event]
+pub struct TreasuryMigrated {
+    pub treasury: Pubkey,
+    pub treasury_mint: Pubkey,
+    pub version: u8,
+}
+
+#[event]
+pub struct FeeConfigMigrated {
+    pub mint: Pubkey,
+    pub authority: Pubkey,
+    pub version: u8,
+}
@@ -2,28 +2,27 @@ use anchor_lang::{
     prelude::*,
     system_program::{create_account, CreateAccount},
 };
-use anchor_spl::{
-    associated_token::AssociatedToken,
-    token::{burn, mint_to, Burn, MintTo},
-    token_interface::{transfer_checked, TransferChecked},
-};
-use spl_tlv_account_resolution::{
-    account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList,
-};
+use spl_tlv_account_resolution::state::ExtraAccountMetaList;
 use spl_transfer_hook_interface::instruction::{ExecuteInstruction, TransferHookInstruction};
 
 declare_id!("2me2g1K7KVA7RBhg1rcbpxRvCknd4v1UCA8RMEjm3hmg");
 
-mod constants;
+pub mod constants;
 mod contexts;
-mod errors;
+pub mod errors;
 mod events;
 mod processors;
-mod states;
+pub mod states;
 mod utils;
 
 use constants::*;
 use contexts::*;
+use errors::*;
+use events::*;
+use states::*;
+
+// the client resolves the hook accounts of a transfer from the same list
+pub use processors::extra_account_metas;
 
 #[program]
 pub mod sol_earna {
@@ -33,56 +32,20 @@ pub mod sol_earna {
 
     pub fn initialize_extra_account_meta_list(
         ctx: Context<InitializeExtraAccountMetaList>,
-        fee_percent_holders: u16,
-        fee_percent_marketing: u16,
-        fee_percent_liquidity: u16,
-    ) -> Result<()> {
-        let _a = &ctx.accounts;
-
-        // The `addExtraAccountsToInstruction` JS helper function resolving incorrectly
-        let account_metas = vec![
-            // source: 0
-            // mint: 1
-            // destination: 2
-            // owner: 3
-            // ExtraAccountMetaList: 4
-            ExtraAccountMeta::new_with_pubkey(&_a.token_program.key(), false, false)?, // 5
-            ExtraAccountMeta::new_with_pubkey(&_a.token_program_org.key(), false, false)?, // 6
-            ExtraAccountMeta::new_with_pubkey(&_a.associated_token_program.key(), false, false)?, // 7
-            ExtraAccountMeta::new_with_seeds(
-                &[
-                    Seed::Literal {
-                        bytes: "delegate".as_bytes().to_vec(),
-                    },
-                    Seed::AccountKey { index: 1 }, // treasury_mint
-                ],
-                false,
-                true,
-            )?, // 8
-            ExtraAccountMeta::new_with_pubkey(&_a.fee_config.key(), false, true)?, // 9
-            ExtraAccountMeta::new_with_seeds(
-                &[
-                    Seed::Literal {
-                        bytes: "treasury".as_bytes().to_vec(),
-                    },
-                    Seed::AccountKey { index: 1 }, // treasury_mint
-                ],
-                false,
-                true,
-            )?, // 10
-            ExtraAccountMeta::new_with_pubkey(&_a.wsol_mint.key(), false, true)?,  // 11
-            ExtraAccountMeta::new_with_pubkey(&_a.wrapper_mint.key(), false, true)?, // 12
-            ExtraAccountMeta::new_external_pda_with_seeds( // 13. fee_wrapper_token_account
-                7, // associated token program index
-                &[
-                    Seed::AccountKey { index: 8 }, // owner index
-                    Seed::AccountKey { index: 6 }, // token program index
-                    Seed::AccountKey { index: 12 }, // wrapper mint index
-                ],
-                false, // is_signer
-                true,  // is_writable
-            )?,
-        ];
+        fee_percent: u16,
+        fee_recipients: Vec<FeeRecipient>,
+        accrual_threshold: u64,
+    ) -> Result<()> {
+        // wrapper mint is always set on init, the authority can be renounced later
+        require!(
+            ctx.accounts.fee_config.wrapper_mint_address == Pubkey::default(),
+            XError::NotAllowed
+        );
+        // same fee for every direction until the authority sets buy/sell fees
+        processors::validate_fee_percents(&[fee_percent], DEFAULT_FEE_PERCENT_CAP)?;
+        processors::validate_fee_recipients(&fee_recipients)?;
+
+        let account_metas = processors::extra_account_metas(&ctx.accounts.fee_config.key())?;
 
         // calculate account size
         let account_size = ExtraAccountMetaList::size_of(account_metas.len())? as u64;
@@ -92,7 +55,7 @@ pub mod sol_earna {
         let mint = ctx.accounts.mint.key();
         let signer_seeds: &[&[&[u8]]] = &[&[
             EXTRA_ACCOUNT_METAS_TAG,
-            &mint.as_ref(),
+            mint.as_ref(),
             &[ctx.bumps.extra_account_meta_list],
         ]];
 
@@ -117,65 +80,246 @@ pub mod sol_earna {
             &account_metas,
         )?;
 
+        ctx.accounts.fee_config.version = FEE_CONFIG_VERSION;
+        ctx.accounts.fee_config.authority = ctx.accounts.payer.key();
         ctx.accounts.fee_config.wsol_mint_address = ctx.accounts.wsol_mint.key();
         ctx.accounts.fee_config.wrapper_mint_address = ctx.accounts.wrapper_mint.key();
-        ctx.accounts.fee_config.fee_recipient_liquidity =
-            ctx.accounts.fee_recipient_liquidity.key();
-        ctx.accounts.fee_config.fee_recipient_marketing =
-            ctx.accounts.fee_recipient_marketing.key();
-        ctx.accounts.fee_config.fee_recipient_holders = ctx.accounts.fee_recipient_holders.key();
-        ctx.accounts.fee_config.fee_percent_liquidity = fee_percent_liquidity;
-        ctx.accounts.fee_config.fee_percent_marketing = fee_percent_marketing;
-        ctx.accounts.fee_config.fee_percent_holders = fee_percent_holders;
+        ctx.accounts.fee_config.transfer_fee = fee_percent;
+        ctx.accounts.fee_config.buy_fee = fee_percent;
+        ctx.accounts.fee_config.sell_fee = fee_percent;
+        ctx.accounts.fee_config.fee_recipients = fee_recipients;
+        ctx.accounts.fee_config.amm_pool_state = ctx.accounts.pool_state.key();
+        ctx.accounts.fee_config.accrual_threshold = accrual_threshold;
+        ctx.accounts.fee_config.fee_percent_cap = DEFAULT_FEE_PERCENT_CAP;
+        // the fee cranks are permissionless, they are bounded from the start. a new pool
+        // can't be swapped against until it has DEFAULT_PRICE_WINDOW of price history
+        ctx.accounts.fee_config.max_slippage_bps = DEFAULT_MAX_SLIPPAGE_BPS;
+        ctx.accounts.fee_config.price_window = DEFAULT_PRICE_WINDOW;
+
+        ctx.accounts.fee_accrual.mint = ctx.accounts.mint.key();
 
         Ok(())
     }
 
     pub fn transfer_hook(ctx: Context<TransferHook>, amount: u64) -> Result<()> {
-        let signer_seeds: &[&[&[u8]]] = &[
-            &[
-                TREASURY_TAG,
-                ctx.accounts.treasury.treasury_mint.as_ref(),
-                &[ctx.bumps.treasury],
-            ],
-        ];
-
-        let fee_config = &mut ctx.accounts.fee_config;
-        let total_fee_percent = fee_config.fee_percent_liquidity
-            + fee_config.fee_percent_marketing
-            + fee_config.fee_percent_holders;
-
-        let total_fee: u64 = amount * total_fee_percent as u64 / 10000;
-
-        // Step 1: mint total_fee of wrapper_mint to fee_wrapper_token_account
-        mint_to(
-            CpiContext::new(
-                ctx.accounts.token_program_org.to_account_info(),
-                MintTo {
-                    mint: ctx.accounts.wrapper_mint.to_account_info(),
-                    to: ctx.accounts.fee_wrapper_token_account.to_account_info(),
-                    authority: ctx.accounts.treasury.to_account_info(),
-                },
-            )
-            .with_signer(signer_seeds),
-            total_fee,
-        )?;
+        ctx.accounts.check_is_transferring()?;
+        ctx.accounts.check_blocklist()?;
+
+        // exempt wallets neither pay fees nor are limited, they also seed the pool before launch.
+        // keyed on the token account owners like the blocklist, not on the transfer authority
+        if utils::is_initialized_pda(&ctx.accounts.source_fee_exemption)
+            || utils::is_initialized_pda(&ctx.accounts.destination_fee_exemption)
+        {
+            return Ok(());
+        }
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            ctx.accounts.fee_config.is_trading_enabled(now),
+            XError::TradingNotEnabled
+        );
+        ctx.accounts.check_transfer_limits(amount)?;
 
-        // Step 2: through raydium, swap from wrapper_mint to wsol
+        // buying from a pool vault / selling into a pool vault
+        let direction = if utils::is_initialized_pda(&ctx.accounts.source_amm_pool_vault) {
+            TransferDirection::Buy
+        } else if utils::is_initialized_pda(&ctx.accounts.destination_amm_pool_vault) {
+            TransferDirection::Sell
+        } else {
+            TransferDirection::Transfer
+        };
+        if direction == TransferDirection::Sell {
+            ctx.accounts.check_sell_cooldown(now)?;
+        }
 
-        // Step 3: divide wsol to (wsol_amount_liquidity + wsol_amount_marketing + wsol_amount_holders)
+        // a pause never blocks the transfer itself
+        if ctx.accounts.fee_config.fee_collection_paused {
+            return Ok(());
+        }
+        let fee_percent = ctx.accounts.fee_config.current_fee_percent(direction, now);
 
-        // Step 4: transfer wsol_amount_liquidity to fee_liquidity_wsol_token_account
+        // split between the fee recipients when it is swapped
+        let total_fee = utils::compute_fee(amount, fee_percent as u32)?; // <= amount
+        if total_fee == 0 {
+            return Ok(());
+        }
 
-        // Step 5: transfer wsol_amount_marketing to fee_marketing_wsol_token_account
+        // only record the fee here, minting/swapping/distributing is deferred to
+        // `process_accrued_fees` so transfers never depend on the pool state
+        let fee_accrual = &mut ctx.accounts.fee_accrual;
+        fee_accrual.pending_fee = fee_accrual
+            .pending_fee
+            .checked_add(total_fee)
+            .ok_or(XError::MathOverflow)?;
+        fee_accrual.total_accrued = fee_accrual
+            .total_accrued
+            .checked_add(total_fee)
+            .ok_or(XError::MathOverflow)?;
 
-        // Step 6: transfer wsol_amount_holders to fee_holders_wsol_token_account
+        emit!(FeeAccrued {
+            mint: ctx.accounts.mint.key(),
+            owner: ctx.accounts.owner.key(),
+            direction,
+            amount,
+            fee: total_fee,
+        });
 
         Ok(())
     }
 
-    pub fn swap_fee_on_exchange(ctx: Context<SwapFeeOnExchange>, amount: u64) -> Result<()> {
-        ctx.accounts.swap_fee_on_exchange(amount)
+    pub fn swap_fee_on_exchange<'info>(
+        ctx: Context<'_, '_, '_, 'info, SwapFeeOnExchange<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .swap_fee_on_exchange(ctx.bumps.delegate, amount, ctx.remaining_accounts)
+    }
+
+    pub fn update_fee_config(
+        ctx: Context<UpdateFeeConfig>,
+        transfer_fee: u16,
+        buy_fee: u16,
+        sell_fee: u16,
+        fee_percent_cap: u16,
+        accrual_threshold: u64,
+    ) -> Result<()> {
+        ctx.accounts.update_fee_config(
+            transfer_fee,
+            buy_fee,
+            sell_fee,
+            fee_percent_cap,
+            accrual_threshold,
+        )
+    }
+
+    // opens transfers to non-exempt wallets at `trading_enabled_at` (now if earlier), with
+    // a fee decaying from `sniper_fee` over `sniper_fee_duration` seconds. callable once
+    pub fn enable_trading(
+        ctx: Context<EnableTrading>,
+        trading_enabled_at: i64,
+        sniper_fee: u16,
+        sniper_fee_duration: u32,
+    ) -> Result<()> {
+        ctx.accounts
+            .enable_trading(trading_enabled_at, sniper_fee, sniper_fee_duration)
+    }
+
+    // seconds between two sells of the same transfer authority, 0 disables it
+    pub fn set_sell_cooldown(ctx: Context<SetSellCooldown>, sell_cooldown: i64) -> Result<()> {
+        ctx.accounts.set_sell_cooldown(sell_cooldown)
+    }
+
+    pub fn init_sell_cooldown(ctx: Context<InitSellCooldown>) -> Result<()> {
+        ctx.accounts.init_sell_cooldown()
+    }
+
+    // relaxes, tightens or removes (TransferLimit::Unlimited) the anti-whale limits
+    pub fn set_transfer_limits(
+        ctx: Context<SetTransferLimits>,
+        max_tx_amount: TransferLimit,
+        max_wallet_balance: TransferLimit,
+    ) -> Result<()> {
+        ctx.accounts
+            .set_transfer_limits(max_tx_amount, max_wallet_balance)
+    }
+
+    // upgrades a fee config from before versioning, see states::FeeConfigV1
+    pub fn migrate_fee_config(ctx: Context<MigrateFeeConfig>, accrual_threshold: u64) -> Result<()> {
+        ctx.accounts.migrate_fee_config(accrual_threshold)
+    }
+
+    pub fn set_fee_recipients(
+        ctx: Context<SetFeeRecipients>,
+        fee_recipients: Vec<FeeRecipient>,
+    ) -> Result<()> {
+        ctx.accounts.set_fee_recipients(fee_recipients)
+    }
+
+    // min-out of the fee swaps, 0 bps disables it. `price_oracle` is a pyth price account
+    // of wrapper_mint in wsol, Pubkey::default() uses the twap of the pool
+    pub fn set_slippage_config(
+        ctx: Context<SetSlippageConfig>,
+        max_slippage_bps: u16,
+        price_window: u32,
+        price_oracle: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts
+            .set_slippage_config(max_slippage_bps, price_window, price_oracle)
+    }
+
+    pub fn add_amm_pool_vault(ctx: Context<AddAmmPoolVault>, vault: Pubkey) -> Result<()> {
+        ctx.accounts.add_amm_pool_vault(vault)
+    }
+
+    pub fn remove_amm_pool_vault(ctx: Context<RemoveAmmPoolVault>) -> Result<()> {
+        ctx.accounts.remove_amm_pool_vault()
+    }
+
+    pub fn add_fee_exemption(ctx: Context<AddFeeExemption>, wallet: Pubkey) -> Result<()> {
+        ctx.accounts.add_fee_exemption(wallet)
+    }
+
+    pub fn remove_fee_exemption(ctx: Context<RemoveFeeExemption>) -> Result<()> {
+        ctx.accounts.remove_fee_exemption()
+    }
+
+    // blocks every transfer from or to token accounts owned by `wallet`
+    pub fn add_to_blocklist(ctx: Context<AddToBlocklist>, wallet: Pubkey) -> Result<()> {
+        ctx.accounts.add_to_blocklist(wallet)
+    }
+
+    pub fn remove_from_blocklist(ctx: Context<RemoveFromBlocklist>) -> Result<()> {
+        ctx.accounts.remove_from_blocklist()
+    }
+
+    pub fn set_fee_config_guardian(ctx: Context<SetFeeConfigGuardian>, guardian: Pubkey) -> Result<()> {
+        ctx.accounts.set_fee_config_guardian(guardian)
+    }
+
+    // callable by the authority or the guardian
+    pub fn set_fee_config_pause(
+        ctx: Context<SetFeeConfigPause>,
+        fee_collection_paused: bool,
+        swap_paused: bool,
+    ) -> Result<()> {
+        ctx.accounts
+            .set_fee_config_pause(fee_collection_paused, swap_paused)
+    }
+
+    pub fn propose_fee_config_authority(
+        ctx: Context<ProposeFeeConfigAuthority>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.propose_fee_config_authority(new_authority)
+    }
+
+    pub fn accept_fee_config_authority(ctx: Context<AcceptFeeConfigAuthority>) -> Result<()> {
+        ctx.accounts.accept_fee_config_authority()
+    }
+
+    // permanently freezes the fee config and its pause flags, the guardian is cleared too
+    pub fn renounce_fee_config_authority(ctx: Context<RenounceFeeConfigAuthority>) -> Result<()> {
+        ctx.accounts.renounce_fee_config_authority()
+    }
+
+    pub fn propose_treasury_authority(
+        ctx: Context<ProposeTreasuryAuthority>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.propose_treasury_authority(new_authority)
+    }
+
+    pub fn accept_treasury_authority(ctx: Context<AcceptTreasuryAuthority>) -> Result<()> {
+        ctx.accounts.accept_treasury_authority()
+    }
+
+    pub fn process_accrued_fees<'info>(
+        ctx: Context<'_, '_, '_, 'info, ProcessAccruedFees<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.process_accrued_fees(
+            ctx.bumps.treasury,
+            ctx.bumps.swap.delegate,
+            ctx.remaining_accounts,
+        )
     }
 
     // fallback instruction handler as workaround to anchor instruction discriminator check
@@ -195,7 +339,7 @@ pub mod sol_earna {
                 // invoke custom transfer hook instruction on our program
                 __private::__global::transfer_hook(program_id, accounts, &amount_bytes)
             }
-            _ => return Err(ProgramError::InvalidInstructionData.into()),
+            _ => Err(ProgramError::InvalidInstructionData.into()),
         }
     }
 
@@ -203,11 +347,101 @@ pub mod sol_earna {
         ctx.accounts.create_treasury()
     }
 
-    pub fn stake(ctx: Context<Stake>, amount: u64) -> Result<()> {
-        ctx.accounts.stake(ctx.bumps.treasury, amount)
+    // upgrades a treasury from before versioning, see states::TreasuryV1
+    pub fn migrate_treasury(ctx: Context<MigrateTreasury>) -> Result<()> {
+        ctx.accounts.migrate_treasury()
     }
-    pub fn redeem(ctx: Context<Redeem>, amount: u64) -> Result<()> {
-        ctx.accounts.redeem(ctx.bumps.treasury, amount)
+
+    // fails with SharesBelowMinimum if less than `min_shares` are issued.
+    // the remaining accounts of stake, redeem and complete_redeem are the extra accounts
+    // of the treasury mint transfer hook, if it has one
+    pub fn stake<'info>(
+        ctx: Context<'_, '_, '_, 'info, Stake<'info>>,
+        amount: u64,
+        lock_option: u8,
+        min_shares: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .stake(amount, lock_option, min_shares, ctx.remaining_accounts)
+    }
+    pub fn redeem<'info>(ctx: Context<'_, '_, '_, 'info, Redeem<'info>>, shares: u64) -> Result<()> {
+        ctx.accounts
+            .redeem(ctx.bumps.treasury, shares, ctx.remaining_accounts)
+    }
+
+    pub fn preview_stake(ctx: Context<Preview>, amount: u64) -> Result<u64> {
+        ctx.accounts.preview_stake(amount)
+    }
+
+    pub fn preview_redeem(ctx: Context<Preview>, shares: u64) -> Result<u64> {
+        ctx.accounts.preview_redeem(shares)
+    }
+
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        ctx.accounts.claim_rewards(ctx.bumps.reward_authority)
+    }
+
+    pub fn set_treasury_guardian(ctx: Context<SetTreasuryGuardian>, guardian: Pubkey) -> Result<()> {
+        ctx.accounts.set_treasury_guardian(guardian)
+    }
+
+    // callable by the authority or the guardian
+    pub fn set_treasury_pause(
+        ctx: Context<SetTreasuryPause>,
+        stake_paused: bool,
+        redeem_paused: bool,
+    ) -> Result<()> {
+        ctx.accounts.set_treasury_pause(stake_paused, redeem_paused)
+    }
+
+    pub fn set_redeem_cooldown(ctx: Context<SetRedeemCooldown>, redeem_cooldown: i64) -> Result<()> {
+        ctx.accounts.set_redeem_cooldown(redeem_cooldown)
+    }
+
+    pub fn request_redeem(ctx: Context<RequestRedeem>, shares: u64) -> Result<()> {
+        ctx.accounts.request_redeem(shares)
+    }
+
+    pub fn complete_redeem<'info>(ctx: Context<'_, '_, '_, 'info, CompleteRedeem<'info>>) -> Result<()> {
+        ctx.accounts
+            .complete_redeem(ctx.bumps.treasury, ctx.remaining_accounts)
+    }
+
+    pub fn cancel_redeem(ctx: Context<CancelRedeem>) -> Result<()> {
+        ctx.accounts.cancel_redeem()
+    }
+
+    pub fn expire_lock(ctx: Context<ExpireLock>) -> Result<()> {
+        ctx.accounts.expire_lock()
+    }
+
+    pub fn set_early_redeem_penalty(
+        ctx: Context<SetEarlyRedeemPenalty>,
+        penalty_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts.set_early_redeem_penalty(penalty_bps)
+    }
+
+    pub fn buyback_and_burn(ctx: Context<BuybackAndBurn>, amount: u64) -> Result<()> {
+        ctx.accounts
+            .buyback_and_burn(ctx.bumps.buyback_authority, amount)
+    }
+
+    pub fn add_auto_liquidity(
+        ctx: Context<AddAutoLiquidity>,
+        tick_lower_index: i32,
+        tick_upper_index: i32,
+        tick_array_lower_start_index: i32,
+        tick_array_upper_start_index: i32,
+    ) -> Result<()> {
+        ctx.accounts.add_auto_liquidity(
+            ctx.bumps.liquidity_authority,
+            ctx.bumps.position_nft_mint,
+            tick_lower_index,
+            tick_upper_index,
+            tick_array_lower_start_index,
+            tick_array_upper_start_index,
+        )
     }
 }
 
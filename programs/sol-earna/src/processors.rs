@@ -1,18 +1,967 @@
 use crate::*;
-use anchor_lang::prelude::*;
 use anchor_spl::{
-    associated_token::AssociatedToken,
     token::{burn, mint_to, Burn, MintTo},
-    token_interface::{transfer_checked, TransferChecked},
+    token_2022::spl_token_2022::{
+        extension::{transfer_hook::TransferHookAccount, BaseStateWithExtensions, StateWithExtensions},
+        state::Account as Token2022Account,
+    },
+    token_interface::{approve, revoke, transfer_checked, Approve, Mint, Revoke, TransferChecked},
 };
 use constants::*;
 use errors::*;
 use events::*;
-use raydium_amm_v3::amm_anchor;
+use raydium_amm_v3::states::{ObservationState, PoolState};
+use spl_tlv_account_resolution::{
+    account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList,
+};
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+use states::*;
+
+
+// pool price (Q64.64 of token_1 per token_0) the swaps of the fee wsol are checked
+// against, and whether wrapper_mint is token_0 of the pool. the pyth price if
+// fee_config.price_oracle is set, otherwise the twap of the pool observations
+fn reference_price_x64(
+    fee_config: &FeeConfig,
+    pool_state: &AccountInfo,
+    observation_state: &AccountInfo,
+    price_oracle: Option<&AccountInfo>,
+    wrapper_mint: &InterfaceAccount<Mint>,
+    wsol_mint: &InterfaceAccount<Mint>,
+) -> Result<(u128, bool)> {
+    let now = Clock::get()?.unix_timestamp;
+    let pool_state = utils::load_amm_account::<PoolState>(pool_state)?;
+    let wrapper_is_token_0 = pool_state.token_mint_0 == wrapper_mint.key();
+
+    if fee_config.price_oracle != Pubkey::default() {
+        let price_oracle = price_oracle.ok_or(XError::PriceUnavailable)?;
+        let price = pyth_sdk_solana::state::SolanaPriceAccount::account_info_to_feed(price_oracle)
+            .map_err(|_| error!(XError::PriceUnavailable))?
+            .get_price_no_older_than(now, fee_config.price_window as u64)
+            .ok_or(XError::PriceUnavailable)?;
+        // wsol per wrapper_mint
+        let price_x64 = utils::oracle_price_x64(
+            price.price,
+            price.expo,
+            wrapper_mint.decimals,
+            wsol_mint.decimals,
+        )?;
+        let price_x64 = if wrapper_is_token_0 {
+            price_x64
+        } else {
+            utils::invert_price_x64(price_x64)?
+        };
+        return Ok((price_x64, wrapper_is_token_0));
+    }
 
+    require_keys_eq!(
+        pool_state.observation_key,
+        observation_state.key(),
+        XError::InvalidAddress
+    );
+    let observation_state = utils::load_amm_account::<ObservationState>(observation_state)?;
+    let observations = &observation_state.observations;
+    let price_x64 = utils::twap_price_x64(
+        |i| {
+            let observation = observations[i];
+            (
+                observation.block_timestamp,
+                observation.sqrt_price_x64,
+                observation.cumulative_time_price_x64,
+            )
+        },
+        observations.len(),
+        pool_state.observation_index as usize,
+        now as u32,
+        fee_config.price_window,
+    )?
+    .ok_or(XError::PriceUnavailable)?;
+    Ok((price_x64, wrapper_is_token_0))
+}
 
 impl<'info> SwapFeeOnExchange<'info> {
-    pub fn swap_fee_on_exchange(&mut self, amount: u64) -> Result<()> {
+    pub fn swap_fee_on_exchange(
+        &mut self,
+        delegate_bump: u8,
+        amount: u64,
+        fee_recipient_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(!self.fee_config.swap_paused, XError::Paused);
+        self.swap_and_distribute(delegate_bump, amount, fee_recipient_accounts)
+    }
+
+    // min wsol out of swapping `amount` of wrapper_mint, 0 while the check is disabled
+    pub fn minimum_swap_output(&self, amount: u64) -> Result<u64> {
+        let max_slippage_bps = self.fee_config.max_slippage_bps;
+        if max_slippage_bps == 0 {
+            return Ok(0);
+        }
+        let (price_x64, wrapper_is_token_0) = reference_price_x64(
+            &self.fee_config,
+            &self.pool_state,
+            &self.observation_state,
+            self.price_oracle.as_deref(),
+            &self.wrapper_mint,
+            &self.wsol_mint,
+        )?;
+        let quote = utils::quote_by_price_x64(amount, price_x64, wrapper_is_token_0)?;
+        utils::apply_slippage(quote, max_slippage_bps)
+    }
+
+    // swaps up to `amount` of fee_wrapper_token_account to wsol and splits it by the bps
+    // of fee_config.fee_recipients, `fee_recipient_accounts` are their wsol token accounts
+    // in the same order (remaining accounts)
+    pub fn swap_and_distribute(
+        &mut self,
+        delegate_bump: u8,
+        amount: u64,
+        fee_recipient_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let fee_recipients = &self.fee_config.fee_recipients;
+        require!(
+            fee_recipient_accounts.len() == fee_recipients.len(),
+            XError::InvalidFeeRecipients
+        );
+        for (account, fee_recipient) in fee_recipient_accounts.iter().zip(fee_recipients.iter()) {
+            require_keys_eq!(account.key(), fee_recipient.recipient, XError::InvalidFeeRecipients);
+        }
+
+        let amount = amount.min(self.fee_wrapper_token_account.amount);
+        if amount == 0 {
+            return Ok(());
+        }
+
+        let mint = self.mint.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[DELEGATE_TAG, mint.as_ref(), &[delegate_bump]]];
+
+        // a worse execution aborts the swap, accrued fees stay pending for a later crank
+        let minimum_wsol_amount = self.minimum_swap_output(amount)?;
+
+        // swap wrapper_mint to wsol through raydium
+        let wsol_balance_before = self.fee_wsol_token_account.amount;
+        raydium_amm_v3::cpi::swap(
+            CpiContext::new(
+                self.amm_program.to_account_info(),
+                raydium_amm_v3::cpi::accounts::SwapSingle {
+                    payer: self.delegate.to_account_info(),
+                    amm_config: self.amm_config.to_account_info(),
+                    pool_state: self.pool_state.to_account_info(),
+                    input_token_account: self.fee_wrapper_token_account.to_account_info(),
+                    output_token_account: self.fee_wsol_token_account.to_account_info(),
+                    input_vault: self.input_vault.to_account_info(),
+                    output_vault: self.output_vault.to_account_info(),
+                    observation_state: self.observation_state.to_account_info(),
+                    token_program: self.token_program.to_account_info(),
+                    tick_array: self.tick_array.to_account_info(),
+                },
+            )
+            .with_signer(signer_seeds),
+            amount,
+            minimum_wsol_amount, // other_amount_threshold
+            0,    // sqrt_price_limit_x64, raydium uses the min/max price for the direction
+            true, // is_base_input
+        )?;
+        self.fee_wsol_token_account.reload()?;
+        let wsol_amount = self
+            .fee_wsol_token_account
+            .amount
+            .checked_sub(wsol_balance_before)
+            .ok_or(XError::InvalidSwapResult)?;
+        require!(wsol_amount >= minimum_wsol_amount, XError::SlippageExceeded);
+
+        // divide wsol by the bps, see utils for the rounding policy
+        let wsol_amounts = utils::split_fee(wsol_amount, &self.fee_config.fee_recipients)?;
+
+        // the token program rejects a recipient that is not a wsol token account
+        for (to, wsol_amount_recipient) in fee_recipient_accounts.iter().zip(wsol_amounts.iter()) {
+            if *wsol_amount_recipient == 0 {
+                continue;
+            }
+            transfer_checked(
+                CpiContext::new(
+                    self.token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.fee_wsol_token_account.to_account_info(),
+                        to: to.clone(),
+                        mint: self.wsol_mint.to_account_info(),
+                        authority: self.delegate.to_account_info(),
+                    },
+                )
+                .with_signer(signer_seeds),
+                *wsol_amount_recipient,
+                self.wsol_mint.decimals,
+            )?;
+        }
+
+        emit!(FeeDistributed {
+            mint,
+            wrapper_amount: amount,
+            wsol_amounts,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> UpdateFeeConfig<'info> {
+    pub fn update_fee_config(
+        &mut self,
+        transfer_fee: u16,
+        buy_fee: u16,
+        sell_fee: u16,
+        fee_percent_cap: u16,
+        accrual_threshold: u64,
+    ) -> Result<()> {
+        validate_fee_percents(&[transfer_fee, buy_fee, sell_fee], fee_percent_cap)?;
+
+        let fee_config = &mut self.fee_config;
+        let old = (**fee_config).clone();
+
+        fee_config.transfer_fee = transfer_fee;
+        fee_config.buy_fee = buy_fee;
+        fee_config.sell_fee = sell_fee;
+        fee_config.fee_percent_cap = fee_percent_cap;
+        fee_config.accrual_threshold = accrual_threshold;
+
+        emit!(FeeConfigUpdated {
+            mint: self.mint.key(),
+            old_transfer_fee: old.transfer_fee,
+            old_buy_fee: old.buy_fee,
+            old_sell_fee: old.sell_fee,
+            old_fee_percent_cap: old.fee_percent_cap,
+            old_accrual_threshold: old.accrual_threshold,
+            new_transfer_fee: fee_config.transfer_fee,
+            new_buy_fee: fee_config.buy_fee,
+            new_sell_fee: fee_config.sell_fee,
+            new_fee_percent_cap: fee_config.fee_percent_cap,
+            new_accrual_threshold: fee_config.accrual_threshold,
+        });
+
+        Ok(())
+    }
+}
+
+pub fn validate_fee_percents(fee_percents: &[u16], fee_percent_cap: u16) -> Result<()> {
+    require!(
+        fee_percent_cap <= FEE_PERCENT_DENOMINATOR,
+        XError::FeeTooHigh
+    );
+    for fee_percent in fee_percents {
+        require!(*fee_percent <= fee_percent_cap, XError::FeeTooHigh);
+    }
+    Ok(())
+}
+
+// extra accounts of the transfer hook, indices follow the TransferHook accounts.
+// only what the hook reads, token-2022 runs out of heap resolving much longer lists
+pub fn extra_account_metas(fee_config: &Pubkey) -> Result<Vec<ExtraAccountMeta>> {
+    // The `addExtraAccountsToInstruction` JS helper function resolving incorrectly
+    Ok(vec![
+        // source: 0
+        // mint: 1
+        // destination: 2
+        // owner: 3
+        // ExtraAccountMetaList: 4
+        ExtraAccountMeta::new_with_pubkey(fee_config, false, false)?, // 5
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: "treasury".as_bytes().to_vec(),
+                },
+                Seed::AccountKey { index: 1 }, // treasury_mint
+            ],
+            false,
+            false,
+        )?, // 6
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: FEE_ACCRUAL_TAG.to_vec(),
+                },
+                Seed::AccountKey { index: 1 }, // mint
+            ],
+            false,
+            true,
+        )?, // 7
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: FEE_EXEMPTION_TAG.to_vec(),
+                },
+                Seed::AccountKey { index: 1 }, // mint
+                Seed::AccountData {
+                    account_index: 0, // source token account
+                    data_index: 32,   // owner offset
+                    length: 32,
+                },
+            ],
+            false,
+            false,
+        )?, // 8
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: FEE_EXEMPTION_TAG.to_vec(),
+                },
+                Seed::AccountKey { index: 1 }, // mint
+                Seed::AccountData {
+                    account_index: 2, // destination token account
+                    data_index: 32,   // owner offset
+                    length: 32,
+                },
+            ],
+            false,
+            false,
+        )?, // 9
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: AMM_POOL_VAULT_TAG.to_vec(),
+                },
+                Seed::AccountKey { index: 1 }, // mint
+                Seed::AccountKey { index: 0 }, // source token account
+            ],
+            false,
+            false,
+        )?, // 10
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: AMM_POOL_VAULT_TAG.to_vec(),
+                },
+                Seed::AccountKey { index: 1 }, // mint
+                Seed::AccountKey { index: 2 }, // destination token account
+            ],
+            false,
+            false,
+        )?, // 11
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: SELL_COOLDOWN_TAG.to_vec(),
+                },
+                Seed::AccountKey { index: 1 }, // mint
+                Seed::AccountKey { index: 3 }, // owner
+            ],
+            false,
+            true,
+        )?, // 12
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: BLOCKLIST_TAG.to_vec(),
+                },
+                Seed::AccountKey { index: 1 }, // mint
+                Seed::AccountData {
+                    account_index: 0, // source token account
+                    data_index: 32,   // owner offset
+                    length: 32,
+                },
+            ],
+            false,
+            false,
+        )?, // 13
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: BLOCKLIST_TAG.to_vec(),
+                },
+                Seed::AccountKey { index: 1 }, // mint
+                Seed::AccountData {
+                    account_index: 2, // destination token account
+                    data_index: 32,   // owner offset
+                    length: 32,
+                },
+            ],
+            false,
+            false,
+        )?, // 14
+    ])
+}
+
+// 1..=MAX_FEE_RECIPIENTS distinct recipients whose bps sum up to 100%
+pub fn validate_fee_recipients(fee_recipients: &[FeeRecipient]) -> Result<()> {
+    require!(
+        !fee_recipients.is_empty() && fee_recipients.len() <= MAX_FEE_RECIPIENTS,
+        XError::InvalidFeeRecipients
+    );
+    let mut total_bps: u32 = 0;
+    for (i, fee_recipient) in fee_recipients.iter().enumerate() {
+        require!(
+            fee_recipient.label.len() <= MAX_FEE_RECIPIENT_LABEL_LEN,
+            XError::InvalidFeeRecipients
+        );
+        require!(
+            fee_recipients[..i]
+                .iter()
+                .all(|other| other.recipient != fee_recipient.recipient),
+            XError::InvalidFeeRecipients
+        );
+        total_bps += fee_recipient.bps as u32;
+    }
+    require!(
+        total_bps == FEE_PERCENT_DENOMINATOR as u32,
+        XError::InvalidFeeRecipients
+    );
+    Ok(())
+}
+
+impl<'info> SetSlippageConfig<'info> {
+    pub fn set_slippage_config(
+        &mut self,
+        max_slippage_bps: u16,
+        price_window: u32,
+        price_oracle: Pubkey,
+    ) -> Result<()> {
+        require!(
+            max_slippage_bps <= FEE_PERCENT_DENOMINATOR,
+            XError::InvalidSlippageConfig
+        );
+        // the window is only used once the check is enabled
+        require!(
+            max_slippage_bps == 0 || (price_window > 0 && price_window <= MAX_PRICE_WINDOW),
+            XError::InvalidSlippageConfig
+        );
+
+        let fee_config = &mut self.fee_config;
+        let old = (**fee_config).clone();
+
+        fee_config.max_slippage_bps = max_slippage_bps;
+        fee_config.price_window = price_window;
+        fee_config.price_oracle = price_oracle;
+
+        emit!(SlippageConfigUpdated {
+            mint: self.mint.key(),
+            old_max_slippage_bps: old.max_slippage_bps,
+            old_price_window: old.price_window,
+            old_price_oracle: old.price_oracle,
+            new_max_slippage_bps: fee_config.max_slippage_bps,
+            new_price_window: fee_config.price_window,
+            new_price_oracle: fee_config.price_oracle,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> SetTransferLimits<'info> {
+    // Unlimited removes a limit
+    pub fn set_transfer_limits(
+        &mut self,
+        max_tx_amount: TransferLimit,
+        max_wallet_balance: TransferLimit,
+    ) -> Result<()> {
+        validate_transfer_limit(max_tx_amount)?;
+        validate_transfer_limit(max_wallet_balance)?;
+
+        let fee_config = &mut self.fee_config;
+        let old = (**fee_config).clone();
+
+        fee_config.max_tx_amount = max_tx_amount;
+        fee_config.max_wallet_balance = max_wallet_balance;
+
+        emit!(TransferLimitsUpdated {
+            mint: self.mint.key(),
+            old_max_tx_amount: old.max_tx_amount,
+            old_max_wallet_balance: old.max_wallet_balance,
+            new_max_tx_amount: fee_config.max_tx_amount,
+            new_max_wallet_balance: fee_config.max_wallet_balance,
+        });
+
+        Ok(())
+    }
+}
+
+// a zero limit would block every transfer
+pub fn validate_transfer_limit(limit: TransferLimit) -> Result<()> {
+    match limit {
+        TransferLimit::Unlimited => {}
+        TransferLimit::Amount(amount) => require!(amount > 0, XError::InvalidTransferLimit),
+        TransferLimit::SupplyBps(bps) => require!(
+            bps > 0 && bps <= FEE_PERCENT_DENOMINATOR,
+            XError::InvalidTransferLimit
+        ),
+    }
+    Ok(())
+}
+
+impl<'info> EnableTrading<'info> {
+    // once only, `trading_enabled_at` in the past (or 0) enables trading now
+    pub fn enable_trading(
+        &mut self,
+        trading_enabled_at: i64,
+        sniper_fee: u16,
+        sniper_fee_duration: u32,
+    ) -> Result<()> {
+        let fee_config = &mut self.fee_config;
+        require!(
+            fee_config.trading_enabled_at == 0,
+            XError::TradingAlreadyEnabled
+        );
+        require!(sniper_fee <= FEE_PERCENT_DENOMINATOR, XError::FeeTooHigh);
+        require!(
+            sniper_fee_duration <= MAX_SNIPER_FEE_DURATION,
+            XError::NotAllowed
+        );
+
+        fee_config.trading_enabled_at = trading_enabled_at.max(Clock::get()?.unix_timestamp);
+        fee_config.sniper_fee = sniper_fee;
+        fee_config.sniper_fee_duration = sniper_fee_duration;
+
+        emit!(TradingEnabled {
+            mint: self.mint.key(),
+            trading_enabled_at: fee_config.trading_enabled_at,
+            sniper_fee,
+            sniper_fee_duration,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> SetSellCooldown<'info> {
+    pub fn set_sell_cooldown(&mut self, sell_cooldown: i64) -> Result<()> {
+        require!(
+            (0..=MAX_SELL_COOLDOWN).contains(&sell_cooldown),
+            XError::NotAllowed
+        );
+
+        let fee_config = &mut self.fee_config;
+        let old_sell_cooldown = fee_config.sell_cooldown;
+        fee_config.sell_cooldown = sell_cooldown;
+
+        emit!(SellCooldownUpdated {
+            mint: self.mint.key(),
+            old_sell_cooldown,
+            new_sell_cooldown: sell_cooldown,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> InitSellCooldown<'info> {
+    // no-op for an existing account
+    pub fn init_sell_cooldown(&mut self) -> Result<()> {
+        let sell_cooldown = &mut self.sell_cooldown;
+        sell_cooldown.mint = self.mint.key();
+        sell_cooldown.owner = self.owner.key();
+        Ok(())
+    }
+}
+
+impl<'info> TransferHook<'info> {
+    // owners of the source and destination token accounts, the transfer authority may be
+    // a delegate of a blocklisted owner. no event, the failed transfer would drop it
+    pub fn check_blocklist(&self) -> Result<()> {
+        for blocklist_entry in [&self.source_blocklist_entry, &self.destination_blocklist_entry] {
+            require!(
+                !utils::is_initialized_pda(blocklist_entry),
+                XError::AddressBlocklisted
+            );
+        }
+        Ok(())
+    }
+
+    // token-2022 flags both token accounts for the duration of the hook cpi, so a direct
+    // call can't accrue fees or record sells
+    pub fn check_is_transferring(&self) -> Result<()> {
+        for token_account in [&self.source_token, &self.destination_token] {
+            let account_info = token_account.to_account_info();
+            let data = account_info.try_borrow_data()?;
+            let account = StateWithExtensions::<Token2022Account>::unpack(&data)?;
+            let extension = account.get_extension::<TransferHookAccount>()?;
+            require!(
+                bool::from(extension.transferring),
+                XError::IsNotCurrentlyTransferring
+            );
+        }
+        Ok(())
+    }
+
+    // records the sell of the owner, rejects it within fee_config.sell_cooldown of the last
+    // one. the hook can't create the SellCooldown account, sellers without one are rejected
+    // or they could skip the cooldown by never creating it
+    pub fn check_sell_cooldown(&self, now: i64) -> Result<()> {
+        let cooldown = self.fee_config.sell_cooldown;
+        if cooldown == 0 {
+            return Ok(());
+        }
+        require!(
+            utils::is_initialized_pda(&self.sell_cooldown),
+            XError::SellCooldownNotInitialized
+        );
+
+        let mut data = self.sell_cooldown.try_borrow_mut_data()?;
+        let mut sell_cooldown = SellCooldown::try_deserialize(&mut &data[..])?;
+        require!(
+            now >= sell_cooldown.last_sell_at.saturating_add(cooldown),
+            XError::SellCooldownActive
+        );
+        sell_cooldown.last_sell_at = now;
+        sell_cooldown.try_serialize(&mut &mut data[..])
+    }
+
+    // anti-whale limits, the caller skips them for fee exempt wallets
+    pub fn check_transfer_limits(&self, amount: u64) -> Result<()> {
+        let fee_config = &self.fee_config;
+        let supply = self.mint.supply;
+
+        if let Some(max_tx_amount) = fee_config.max_tx_amount.resolve(supply) {
+            require!(amount <= max_tx_amount, XError::MaxTxAmountExceeded);
+        }
+
+        // pool vaults and the treasury (staked tokens) are not wallets
+        let destination = &self.destination_token;
+        if utils::is_initialized_pda(&self.destination_amm_pool_vault)
+            || (destination.owner == self.treasury.key()
+                && self.treasury.treasury_mint == self.mint.key())
+        {
+            return Ok(());
+        }
+        if let Some(max_wallet_balance) = fee_config.max_wallet_balance.resolve(supply) {
+            // the hook runs after the transfer, the balance includes `amount`
+            require!(
+                destination.amount <= max_wallet_balance,
+                XError::MaxWalletBalanceExceeded
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl<'info> MigrateFeeConfig<'info> {
+    pub fn migrate_fee_config(&mut self, accrual_threshold: u64) -> Result<()> {
+        let fee_config_info = self.fee_config.to_account_info();
+        require_keys_eq!(*fee_config_info.owner, crate::ID, XError::InvalidAccountVersion);
+        let fee_config_v1 = FeeConfigV1::try_from_account_data(&fee_config_info.try_borrow_data()?)?;
+        require_keys_eq!(
+            fee_config_v1.wrapper_mint_address,
+            self.treasury.wrapper_mint,
+            XError::InvalidWrapperMint
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let fee_config = fee_config_v1.upgrade(
+            self.authority.key(),
+            self.pool_state.key(),
+            accrual_threshold,
+            now,
+        )?;
+        validate_fee_recipients(&fee_config.fee_recipients)?;
+
+        let payer = self.authority.to_account_info();
+        let system_program = self.system_program.to_account_info();
+        utils::realloc_with_rent(
+            &fee_config_info,
+            FeeConfig::space(fee_config.fee_recipients.len()),
+            &payer,
+            &system_program,
+        )?;
+        fee_config.try_serialize(&mut &mut fee_config_info.try_borrow_mut_data()?[..])?;
+
+        self.fee_accrual.mint = self.mint.key();
+
+        // v1 resolved other extra accounts, the list is rebuilt from scratch
+        let account_metas = extra_account_metas(&fee_config_info.key())?;
+        let extra_account_meta_list = self.extra_account_meta_list.to_account_info();
+        require_keys_eq!(
+            *extra_account_meta_list.owner,
+            crate::ID,
+            XError::InvalidAccountVersion
+        );
+        utils::realloc_with_rent(
+            &extra_account_meta_list,
+            ExtraAccountMetaList::size_of(account_metas.len())?,
+            &payer,
+            &system_program,
+        )?;
+        let mut data = extra_account_meta_list.try_borrow_mut_data()?;
+        data.fill(0);
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &account_metas)?;
+
+        emit!(FeeConfigMigrated {
+            mint: self.mint.key(),
+            authority: fee_config.authority,
+            version: fee_config.version,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> SetFeeRecipients<'info> {
+    pub fn set_fee_recipients(&mut self, fee_recipients: Vec<FeeRecipient>) -> Result<()> {
+        validate_fee_recipients(&fee_recipients)?;
+
+        let fee_config = &mut self.fee_config;
+        let old_fee_recipients = std::mem::replace(&mut fee_config.fee_recipients, fee_recipients);
+
+        emit!(FeeRecipientsUpdated {
+            mint: self.mint.key(),
+            old_fee_recipients,
+            new_fee_recipients: fee_config.fee_recipients.clone(),
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> AddAmmPoolVault<'info> {
+    pub fn add_amm_pool_vault(&mut self, vault: Pubkey) -> Result<()> {
+        let amm_pool_vault = &mut self.amm_pool_vault;
+        amm_pool_vault.mint = self.mint.key();
+        amm_pool_vault.vault = vault;
+
+        emit!(AmmPoolVaultAdded {
+            mint: amm_pool_vault.mint,
+            vault,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> RemoveAmmPoolVault<'info> {
+    pub fn remove_amm_pool_vault(&mut self) -> Result<()> {
+        emit!(AmmPoolVaultRemoved {
+            mint: self.amm_pool_vault.mint,
+            vault: self.amm_pool_vault.vault,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> AddFeeExemption<'info> {
+    pub fn add_fee_exemption(&mut self, wallet: Pubkey) -> Result<()> {
+        let fee_exemption = &mut self.fee_exemption;
+        fee_exemption.mint = self.mint.key();
+        fee_exemption.wallet = wallet;
+
+        emit!(FeeExemptionAdded {
+            mint: fee_exemption.mint,
+            wallet,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> RemoveFeeExemption<'info> {
+    pub fn remove_fee_exemption(&mut self) -> Result<()> {
+        emit!(FeeExemptionRemoved {
+            mint: self.fee_exemption.mint,
+            wallet: self.fee_exemption.wallet,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> AddToBlocklist<'info> {
+    pub fn add_to_blocklist(&mut self, wallet: Pubkey) -> Result<()> {
+        let blocklist_entry = &mut self.blocklist_entry;
+        blocklist_entry.mint = self.mint.key();
+        blocklist_entry.wallet = wallet;
+
+        emit!(Blocklisted {
+            mint: blocklist_entry.mint,
+            wallet,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> RemoveFromBlocklist<'info> {
+    pub fn remove_from_blocklist(&mut self) -> Result<()> {
+        emit!(Unblocklisted {
+            mint: self.blocklist_entry.mint,
+            wallet: self.blocklist_entry.wallet,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> ProposeFeeConfigAuthority<'info> {
+    pub fn propose_fee_config_authority(&mut self, new_authority: Pubkey) -> Result<()> {
+        let fee_config = &mut self.fee_config;
+        fee_config.pending_authority = new_authority;
+
+        emit!(AuthorityProposed {
+            account: fee_config.key(),
+            authority: fee_config.authority,
+            pending_authority: new_authority,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> SetFeeConfigGuardian<'info> {
+    pub fn set_fee_config_guardian(&mut self, guardian: Pubkey) -> Result<()> {
+        let fee_config = &mut self.fee_config;
+        let old_guardian = fee_config.guardian;
+        fee_config.guardian = guardian;
+
+        emit!(GuardianUpdated {
+            account: fee_config.key(),
+            old_guardian,
+            new_guardian: guardian,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> SetFeeConfigPause<'info> {
+    pub fn set_fee_config_pause(&mut self, fee_collection_paused: bool, swap_paused: bool) -> Result<()> {
+        let fee_config = &mut self.fee_config;
+        fee_config.fee_collection_paused = fee_collection_paused;
+        fee_config.swap_paused = swap_paused;
+
+        emit!(FeeConfigPauseUpdated {
+            mint: self.mint.key(),
+            signer: self.signer.key(),
+            fee_collection_paused,
+            swap_paused,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> RenounceFeeConfigAuthority<'info> {
+    pub fn renounce_fee_config_authority(&mut self) -> Result<()> {
+        let fee_config = &mut self.fee_config;
+        let authority = fee_config.authority;
+        fee_config.authority = Pubkey::default();
+        fee_config.pending_authority = Pubkey::default();
+        // nobody is left to replace the guardian, so it goes with the authority
+        let old_guardian = std::mem::take(&mut fee_config.guardian);
+
+        emit!(AuthorityRenounced {
+            account: fee_config.key(),
+            authority,
+        });
+        if old_guardian != Pubkey::default() {
+            emit!(GuardianUpdated {
+                account: fee_config.key(),
+                old_guardian,
+                new_guardian: Pubkey::default(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<'info> AcceptFeeConfigAuthority<'info> {
+    pub fn accept_fee_config_authority(&mut self) -> Result<()> {
+        let fee_config = &mut self.fee_config;
+        let old_authority = fee_config.authority;
+        fee_config.authority = fee_config.pending_authority;
+        fee_config.pending_authority = Pubkey::default();
+
+        emit!(AuthorityAccepted {
+            account: fee_config.key(),
+            old_authority,
+            new_authority: fee_config.authority,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> ProposeTreasuryAuthority<'info> {
+    pub fn propose_treasury_authority(&mut self, new_authority: Pubkey) -> Result<()> {
+        let treasury = &mut self.treasury;
+        treasury.pending_authority = new_authority;
+
+        emit!(AuthorityProposed {
+            account: treasury.key(),
+            authority: treasury.authority,
+            pending_authority: new_authority,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> AcceptTreasuryAuthority<'info> {
+    pub fn accept_treasury_authority(&mut self) -> Result<()> {
+        let treasury = &mut self.treasury;
+        let old_authority = treasury.authority;
+        treasury.authority = treasury.pending_authority;
+        treasury.pending_authority = Pubkey::default();
+
+        emit!(AuthorityAccepted {
+            account: treasury.key(),
+            old_authority,
+            new_authority: treasury.authority,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> ProcessAccruedFees<'info> {
+    pub fn process_accrued_fees(
+        &mut self,
+        treasury_bump: u8,
+        delegate_bump: u8,
+        fee_recipient_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(!self.swap.fee_config.swap_paused, XError::Paused);
+        let amount = self.fee_accrual.pending_fee;
+        require!(amount > 0, XError::ThresholdNotReached);
+        require!(
+            amount >= self.swap.fee_config.accrual_threshold,
+            XError::ThresholdNotReached
+        );
+
+        let treasury = &self.treasury;
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            TREASURY_TAG,
+            treasury.treasury_mint.as_ref(),
+            &[treasury_bump],
+        ]];
+
+        // mint the pending fee of wrapper_mint to fee_wrapper_token_account
+        mint_to(
+            CpiContext::new(
+                self.swap.token_program.to_account_info(),
+                MintTo {
+                    mint: self.swap.wrapper_mint.to_account_info(),
+                    to: self.swap.fee_wrapper_token_account.to_account_info(),
+                    authority: treasury.to_account_info(),
+                },
+            )
+            .with_signer(signer_seeds),
+            amount,
+        )?;
+        self.swap.fee_wrapper_token_account.reload()?;
+
+        self.swap
+            .swap_and_distribute(delegate_bump, amount, fee_recipient_accounts)?;
+
+        let fee_accrual = &mut self.fee_accrual;
+        fee_accrual.pending_fee = 0;
+        fee_accrual.total_processed = fee_accrual
+            .total_processed
+            .checked_add(amount)
+            .ok_or(XError::MathOverflow)?;
+        fee_accrual.last_processed_at = Clock::get()?.unix_timestamp;
+
+        emit!(AccruedFeesProcessed {
+            mint: fee_accrual.mint,
+            amount,
+            total_processed: fee_accrual.total_processed,
+        });
+
         Ok(())
     }
 }
@@ -20,6 +969,7 @@ impl<'info> SwapFeeOnExchange<'info> {
 impl<'info> CreateTreasury<'info> {
     pub fn create_treasury(&mut self) -> Result<()> {
         let treasury = &mut self.treasury;
+        treasury.version = TREASURY_VERSION;
         treasury.authority = self.authority.key();
         treasury.treasury_mint = self.treasury_mint.key();
         treasury.treasury_token_account = self.treasury_token_account.key();
@@ -36,13 +986,243 @@ impl<'info> CreateTreasury<'info> {
     }
 }
 
+impl<'info> MigrateTreasury<'info> {
+    pub fn migrate_treasury(&mut self) -> Result<()> {
+        let treasury_info = self.treasury.to_account_info();
+        require_keys_eq!(*treasury_info.owner, crate::ID, XError::InvalidAccountVersion);
+        let treasury_v1 = TreasuryV1::try_from_account_data(&treasury_info.try_borrow_data()?)?;
+        require_keys_eq!(treasury_v1.authority, self.authority.key(), XError::NotAllowed);
+
+        let treasury = treasury_v1.upgrade();
+        utils::realloc_with_rent(
+            &treasury_info,
+            Treasury::SPACE,
+            &self.authority.to_account_info(),
+            &self.system_program.to_account_info(),
+        )?;
+        treasury.try_serialize(&mut &mut treasury_info.try_borrow_mut_data()?[..])?;
+
+        emit!(TreasuryMigrated {
+            treasury: treasury_info.key(),
+            treasury_mint: treasury.treasury_mint,
+            version: treasury.version,
+        });
+
+        Ok(())
+    }
+}
+
+// pays `amount` of the treasury mint to the user. a mint hooked by this program is moved by
+// the user as delegate of the treasury in the instruction right after this one, see
+// utils::is_self_hooked
+#[allow(clippy::too_many_arguments)]
+fn pay_out<'info>(
+    accounts: TransferChecked<'info>,
+    user: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    instructions: &AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+    decimals: u8,
+) -> Result<()> {
+    if !utils::is_self_hooked(&accounts.mint)? {
+        return utils::transfer_checked_with_hook(
+            CpiContext::new(token_program, accounts)
+                .with_signer(signer_seeds)
+                .with_remaining_accounts(hook_accounts.to_vec()),
+            amount,
+            decimals,
+        );
+    }
+    utils::check_top_level_transfer(
+        instructions,
+        1,
+        [accounts.from.key(), accounts.mint.key(), accounts.to.key(), user.key()],
+        amount,
+    )?;
+    // the transfer uses up the whole approval, which clears the delegate again
+    approve(
+        CpiContext::new(
+            token_program,
+            Approve {
+                to: accounts.from,
+                delegate: user,
+                authority: accounts.authority,
+            },
+        )
+        .with_signer(signer_seeds),
+        amount,
+    )
+}
+
 impl<'info> Stake<'info> {
-    pub fn stake(&mut self, treasury_bump: u8, amount: u64) -> Result<()> {
+    // a lock covers the whole position, including later stakes
+    pub fn stake(
+        &mut self,
+        amount: u64,
+        lock_option: u8,
+        min_shares: u64,
+        hook_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let (lock_duration, lock_multiplier_bps) = *LOCK_OPTIONS
+            .get(lock_option as usize)
+            .ok_or(XError::InvalidLockOption)?;
+        require!(!self.treasury.stake_paused, XError::Paused);
         if amount == 0 {
             return Ok(())
         }
 
         let treasury = &mut self.treasury;
+        let total_shares = treasury.total_shares()?;
+
+        // token-2022 transfer fee may be withheld, only received amount is deposited
+        let treasury_mint = self.treasury_mint.to_account_info();
+        let (total_assets, received) = if utils::is_self_hooked(&treasury_mint)? {
+            // the user's own transfer_checked right before this instruction
+            utils::check_top_level_transfer(
+                &self.instructions,
+                -1,
+                [
+                    self.user_token_account.key(),
+                    treasury_mint.key(),
+                    self.treasury_token_account.key(),
+                    self.user.key(),
+                ],
+                amount,
+            )?;
+            let received = utils::received_amount(&treasury_mint, amount)?;
+            let total_assets = self
+                .treasury_token_account
+                .amount
+                .checked_sub(received)
+                .ok_or(XError::MathOverflow)?;
+            (total_assets, received)
+        } else {
+            let total_assets = self.treasury_token_account.amount;
+            utils::transfer_checked_with_hook(
+                CpiContext::new(
+                    self.token_program_treasury.to_account_info(),
+                    TransferChecked {
+                        from: self.user_token_account.to_account_info(),
+                        to: self.treasury_token_account.to_account_info(),
+                        mint: treasury_mint,
+                        authority: self.user.to_account_info(),
+                    },
+                )
+                .with_remaining_accounts(hook_accounts.to_vec()),
+                amount,
+                self.treasury_mint.decimals,
+            )?;
+            self.treasury_token_account.reload()?;
+            let received = self
+                .treasury_token_account
+                .amount
+                .checked_sub(total_assets)
+                .ok_or(XError::MathOverflow)?;
+            (total_assets, received)
+        };
+        let shares = utils::convert_to_shares(received, total_assets, total_shares)?;
+        require!(shares > 0, XError::AmountTooSmall);
+        require!(shares >= min_shares, XError::SharesBelowMinimum);
+
+        // holders rewards checkpoint for the new stake
+        let now = Clock::get()?.unix_timestamp;
+        treasury.update_rewards(self.reward_vault.amount)?;
+        let staker_info = &mut self.staker_info;
+        staker_info.treasury = treasury.key();
+        staker_info.user = self.user.key();
+        staker_info.settle(treasury.acc_reward_per_share)?;
+        if staker_info.expire_lock(now) {
+            emit!(LockExpired {
+                treasury: treasury.key(),
+                user: staker_info.user,
+            });
+        }
+        staker_info.amount = staker_info
+            .amount
+            .checked_add(shares)
+            .ok_or(XError::MathOverflow)?;
+        if lock_duration > 0 {
+            staker_info.lock(now, lock_duration, lock_multiplier_bps)?;
+            emit!(LockCreated {
+                treasury: treasury.key(),
+                user: staker_info.user,
+                amount: staker_info.amount,
+                lock_end: staker_info.lock_end,
+                lock_multiplier_bps: staker_info.lock_multiplier_bps,
+            });
+        }
+        staker_info.sync_weight(treasury)?;
+        staker_info.checkpoint(treasury.acc_reward_per_share)?;
+        treasury.total_staked = treasury
+            .total_staked
+            .checked_add(shares)
+            .ok_or(XError::MathOverflow)?;
+
+        emit!(Deposited {
+            treasury: treasury.key(),
+            user: self.user.key(),
+            amount: received,
+            shares,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> Redeem<'info> {
+    // `shares` of the stake are redeemed for their value in treasury_mint
+    pub fn redeem(
+        &mut self,
+        treasury_bump: u8,
+        shares: u64,
+        hook_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(shares > 0, XError::NotAllowed);
+
+        let treasury = &mut self.treasury;
+        require!(!treasury.redeem_paused, XError::Paused);
+        require!(treasury.redeem_cooldown == 0, XError::RedeemCooldownActive);
+
+        let amount = utils::convert_to_assets(
+            shares,
+            self.treasury_token_account.amount,
+            treasury.total_shares()?,
+        )?;
+
+        // holders rewards checkpoint for the unstaked amount
+        let now = Clock::get()?.unix_timestamp;
+        treasury.update_rewards(self.reward_vault.amount)?;
+        let staker_info = &mut self.staker_info;
+        staker_info.treasury = treasury.key();
+        staker_info.user = self.user.key();
+        staker_info.settle(treasury.acc_reward_per_share)?;
+        if staker_info.expire_lock(now) {
+            emit!(LockExpired {
+                treasury: treasury.key(),
+                user: staker_info.user,
+            });
+        }
+        // only staked shares are redeemable, a lock can not be moved to another wallet
+        require!(shares <= staker_info.amount, XError::InsufficientStake);
+
+        // the penalty of a locked stake stays in the treasury
+        let mut penalty = 0;
+        if staker_info.is_locked(now) {
+            require!(treasury.early_redeem_penalty_bps > 0, XError::StakeLocked);
+            penalty = utils::compute_fee(amount, treasury.early_redeem_penalty_bps as u32)?;
+        }
+        let amount = amount - penalty; // penalty <= amount
+        require!(amount > 0, XError::AmountTooSmall);
+
+        staker_info.amount -= shares; // shares <= staker_info.amount
+        staker_info.sync_weight(treasury)?;
+        staker_info.checkpoint(treasury.acc_reward_per_share)?;
+        treasury.total_staked = treasury
+            .total_staked
+            .checked_sub(shares)
+            .ok_or(XError::MathOverflow)?;
 
         let signer_seeds: &[&[&[u8]]] = &[&[
             TREASURY_TAG,
@@ -50,48 +1230,202 @@ impl<'info> Stake<'info> {
             &[treasury_bump],
         ]];
 
-        transfer_checked(
-            CpiContext::new(
-                self.token_program_treasury.to_account_info(),
-                TransferChecked {
-                    from: self.user_token_account.to_account_info(),
-                    to: self.treasury_token_account.to_account_info(),
-                    mint: self.treasury_mint.to_account_info(),
-                    authority: self.user.to_account_info(),
-                },
-            ),
+        pay_out(
+            TransferChecked {
+                from: self.treasury_token_account.to_account_info(),
+                to: self.user_token_account.to_account_info(),
+                mint: self.treasury_mint.to_account_info(),
+                authority: treasury.to_account_info(),
+            },
+            self.user.to_account_info(),
+            self.token_program_treasury.to_account_info(),
+            &self.instructions,
+            hook_accounts,
+            signer_seeds,
             amount,
             self.treasury_mint.decimals,
         )?;
 
-        mint_to(
-            CpiContext::new(
-                self.token_program.to_account_info(),
-                MintTo {
-                    mint: self.wrapper_mint.to_account_info(),
-                    to: self.user_wrapper_token_account.to_account_info(),
-                    authority: treasury.to_account_info(),
-                },
-            )
-            .with_signer(signer_seeds),
+        emit!(Claimed {
+            treasury: treasury.key(),
+            user: self.user.key(),
             amount,
-        )?;
+            shares,
+            penalty,
+        });
 
-        emit!(Deposited {
+        Ok(())
+    }
+}
+
+impl<'info> SetEarlyRedeemPenalty<'info> {
+    pub fn set_early_redeem_penalty(&mut self, penalty_bps: u16) -> Result<()> {
+        require!(
+            penalty_bps <= FEE_PERCENT_DENOMINATOR,
+            XError::FeeTooHigh
+        );
+
+        let treasury = &mut self.treasury;
+        let old_penalty_bps = treasury.early_redeem_penalty_bps;
+        treasury.early_redeem_penalty_bps = penalty_bps;
+
+        emit!(EarlyRedeemPenaltyUpdated {
+            treasury: treasury.key(),
+            old_penalty_bps,
+            new_penalty_bps: penalty_bps,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> SetTreasuryGuardian<'info> {
+    pub fn set_treasury_guardian(&mut self, guardian: Pubkey) -> Result<()> {
+        let treasury = &mut self.treasury;
+        let old_guardian = treasury.guardian;
+        treasury.guardian = guardian;
+
+        emit!(GuardianUpdated {
+            account: treasury.key(),
+            old_guardian,
+            new_guardian: guardian,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> SetTreasuryPause<'info> {
+    pub fn set_treasury_pause(&mut self, stake_paused: bool, redeem_paused: bool) -> Result<()> {
+        let treasury = &mut self.treasury;
+        treasury.stake_paused = stake_paused;
+        treasury.redeem_paused = redeem_paused;
+
+        emit!(TreasuryPauseUpdated {
+            treasury: treasury.key(),
+            signer: self.signer.key(),
+            stake_paused,
+            redeem_paused,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> SetRedeemCooldown<'info> {
+    pub fn set_redeem_cooldown(&mut self, redeem_cooldown: i64) -> Result<()> {
+        require!(redeem_cooldown >= 0, XError::NotAllowed);
+
+        let treasury = &mut self.treasury;
+        let old_redeem_cooldown = treasury.redeem_cooldown;
+        treasury.redeem_cooldown = redeem_cooldown;
+
+        emit!(RedeemCooldownUpdated {
+            treasury: treasury.key(),
+            old_redeem_cooldown,
+            new_redeem_cooldown: redeem_cooldown,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> RequestRedeem<'info> {
+    // moves `shares` out of the stake into the withdrawal ticket, they stop earning rewards
+    pub fn request_redeem(&mut self, shares: u64) -> Result<()> {
+        require!(shares > 0, XError::NotAllowed);
+
+        let treasury = &mut self.treasury;
+        require!(!treasury.redeem_paused, XError::Paused);
+
+        let now = Clock::get()?.unix_timestamp;
+        treasury.update_rewards(self.reward_vault.amount)?;
+        let staker_info = &mut self.staker_info;
+        staker_info.treasury = treasury.key();
+        staker_info.user = self.user.key();
+        staker_info.settle(treasury.acc_reward_per_share)?;
+        if staker_info.expire_lock(now) {
+            emit!(LockExpired {
+                treasury: treasury.key(),
+                user: staker_info.user,
+            });
+        }
+        require!(shares <= staker_info.amount, XError::InsufficientStake);
+
+        // the penalty shares of a locked stake are dropped, their value stays in the treasury
+        let mut penalty_shares = 0;
+        if staker_info.is_locked(now) {
+            require!(treasury.early_redeem_penalty_bps > 0, XError::StakeLocked);
+            penalty_shares =
+                utils::compute_fee(shares, treasury.early_redeem_penalty_bps as u32)?;
+        }
+        let queued = shares - penalty_shares; // penalty_shares <= shares
+        require!(queued > 0, XError::AmountTooSmall);
+
+        staker_info.amount -= shares; // shares <= staker_info.amount
+        staker_info.sync_weight(treasury)?;
+        staker_info.checkpoint(treasury.acc_reward_per_share)?;
+        treasury.total_staked = treasury
+            .total_staked
+            .checked_sub(shares)
+            .ok_or(XError::MathOverflow)?;
+        treasury.total_pending_redeem = treasury
+            .total_pending_redeem
+            .checked_add(queued)
+            .ok_or(XError::MathOverflow)?;
+
+        // a new request restarts the cooldown of the whole ticket
+        let withdrawal_ticket = &mut self.withdrawal_ticket;
+        withdrawal_ticket.treasury = treasury.key();
+        withdrawal_ticket.user = self.user.key();
+        withdrawal_ticket.shares = withdrawal_ticket
+            .shares
+            .checked_add(queued)
+            .ok_or(XError::MathOverflow)?;
+        withdrawal_ticket.requested_at = now;
+        withdrawal_ticket.claimable_at = now
+            .checked_add(treasury.redeem_cooldown)
+            .ok_or(XError::MathOverflow)?;
+
+        emit!(RedeemRequested {
             treasury: treasury.key(),
             user: self.user.key(),
-            amount: amount,
+            shares: queued,
+            penalty_shares,
+            claimable_at: withdrawal_ticket.claimable_at,
         });
 
         Ok(())
     }
 }
 
-impl<'info> Redeem<'info> {
-    pub fn redeem(&mut self, treasury_bump: u8, amount: u64) -> Result<()> {
-        require!(amount > 0, XError::NotAllowed);
+impl<'info> CompleteRedeem<'info> {
+    // redeems the ticket shares for their value in treasury_mint at completion time
+    pub fn complete_redeem(
+        &mut self,
+        treasury_bump: u8,
+        hook_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(!self.treasury.redeem_paused, XError::Paused);
+        let withdrawal_ticket = &self.withdrawal_ticket;
+        require!(
+            Clock::get()?.unix_timestamp >= withdrawal_ticket.claimable_at,
+            XError::CooldownNotElapsed
+        );
+        let shares = withdrawal_ticket.shares;
+
+        let amount = utils::convert_to_assets(
+            shares,
+            self.treasury_token_account.amount,
+            self.treasury.total_shares()?,
+        )?;
+        require!(amount > 0, XError::AmountTooSmall);
 
         let treasury = &mut self.treasury;
+        treasury.total_pending_redeem = treasury
+            .total_pending_redeem
+            .checked_sub(shares)
+            .ok_or(XError::MathOverflow)?;
 
         let signer_seeds: &[&[&[u8]]] = &[&[
             TREASURY_TAG,
@@ -99,38 +1433,546 @@ impl<'info> Redeem<'info> {
             &[treasury_bump],
         ]];
 
-        burn(
+        pay_out(
+            TransferChecked {
+                from: self.treasury_token_account.to_account_info(),
+                to: self.user_token_account.to_account_info(),
+                mint: self.treasury_mint.to_account_info(),
+                authority: treasury.to_account_info(),
+            },
+            self.user.to_account_info(),
+            self.token_program_treasury.to_account_info(),
+            &self.instructions,
+            hook_accounts,
+            signer_seeds,
+            amount,
+            self.treasury_mint.decimals,
+        )?;
+
+        emit!(Claimed {
+            treasury: treasury.key(),
+            user: self.user.key(),
+            amount,
+            shares,
+            penalty: 0,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> CancelRedeem<'info> {
+    // stakes the ticket shares again
+    pub fn cancel_redeem(&mut self) -> Result<()> {
+        require!(!self.treasury.stake_paused, XError::Paused);
+        let shares = self.withdrawal_ticket.shares;
+        let treasury_key = self.treasury.key();
+        let user_key = self.user.key();
+
+        let now = Clock::get()?.unix_timestamp;
+        let treasury = &mut self.treasury;
+        treasury.update_rewards(self.reward_vault.amount)?;
+        let staker_info = &mut self.staker_info;
+        staker_info.settle(treasury.acc_reward_per_share)?;
+        if staker_info.expire_lock(now) {
+            emit!(LockExpired {
+                treasury: treasury_key,
+                user: user_key,
+            });
+        }
+        staker_info.amount = staker_info
+            .amount
+            .checked_add(shares)
+            .ok_or(XError::MathOverflow)?;
+        staker_info.sync_weight(treasury)?;
+        staker_info.checkpoint(treasury.acc_reward_per_share)?;
+        treasury.total_staked = treasury
+            .total_staked
+            .checked_add(shares)
+            .ok_or(XError::MathOverflow)?;
+        treasury.total_pending_redeem = treasury
+            .total_pending_redeem
+            .checked_sub(shares)
+            .ok_or(XError::MathOverflow)?;
+
+        emit!(RedeemCancelled {
+            treasury: treasury_key,
+            user: user_key,
+            shares,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> ExpireLock<'info> {
+    pub fn expire_lock(&mut self) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let treasury = &mut self.treasury;
+        treasury.update_rewards(self.reward_vault.amount)?;
+
+        let staker_info = &mut self.staker_info;
+        staker_info.settle(treasury.acc_reward_per_share)?;
+        require!(staker_info.expire_lock(now), XError::StakeLocked);
+        staker_info.sync_weight(treasury)?;
+        staker_info.checkpoint(treasury.acc_reward_per_share)?;
+
+        emit!(LockExpired {
+            treasury: treasury.key(),
+            user: staker_info.user,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> Preview<'info> {
+    pub fn preview_stake(&self, amount: u64) -> Result<u64> {
+        utils::convert_to_shares(
+            amount,
+            self.treasury_token_account.amount,
+            self.treasury.total_shares()?,
+        )
+    }
+
+    pub fn preview_redeem(&self, shares: u64) -> Result<u64> {
+        utils::convert_to_assets(
+            shares,
+            self.treasury_token_account.amount,
+            self.treasury.total_shares()?,
+        )
+    }
+}
+
+impl<'info> ClaimRewards<'info> {
+    pub fn claim_rewards(&mut self, reward_authority_bump: u8) -> Result<()> {
+        let treasury = &mut self.treasury;
+        treasury.update_rewards(self.reward_vault.amount)?;
+
+        let staker_info = &mut self.staker_info;
+        staker_info.settle(treasury.acc_reward_per_share)?;
+        if staker_info.expire_lock(Clock::get()?.unix_timestamp) {
+            staker_info.sync_weight(treasury)?;
+            staker_info.checkpoint(treasury.acc_reward_per_share)?;
+            emit!(LockExpired {
+                treasury: treasury.key(),
+                user: staker_info.user,
+            });
+        }
+        let amount = staker_info.pending_rewards;
+        if amount == 0 {
+            return Ok(());
+        }
+        staker_info.pending_rewards = 0;
+        treasury.reward_vault_balance = treasury
+            .reward_vault_balance
+            .checked_sub(amount)
+            .ok_or(XError::MathOverflow)?;
+
+        let treasury_key = treasury.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            REWARD_AUTHORITY_TAG,
+            treasury_key.as_ref(),
+            &[reward_authority_bump],
+        ]];
+
+        transfer_checked(
             CpiContext::new(
                 self.token_program.to_account_info(),
-                Burn {
-                    mint: self.wrapper_mint.to_account_info(),
-                    from: self.user_wrapper_token_account.to_account_info(),
-                    authority: self.user.to_account_info(),
+                TransferChecked {
+                    from: self.reward_vault.to_account_info(),
+                    to: self.user_wsol_token_account.to_account_info(),
+                    mint: self.wsol_mint.to_account_info(),
+                    authority: self.reward_authority.to_account_info(),
                 },
             )
             .with_signer(signer_seeds),
             amount,
+            self.wsol_mint.decimals,
         )?;
 
-        transfer_checked(
+        emit!(RewardsClaimed {
+            treasury: treasury_key,
+            user: self.user.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> AddAutoLiquidity<'info> {
+    // the first call opens the position (authority only) with the given tick range,
+    // later calls increase it and ignore the tick arguments
+    pub fn add_auto_liquidity(
+        &mut self,
+        liquidity_authority_bump: u8,
+        position_nft_mint_bump: u8,
+        tick_lower_index: i32,
+        tick_upper_index: i32,
+        tick_array_lower_start_index: i32,
+        tick_array_upper_start_index: i32,
+    ) -> Result<()> {
+        require!(!self.fee_config.swap_paused, XError::Paused);
+
+        let mint = self.mint.key();
+        let authority_seeds: &[&[u8]] = &[
+            LIQUIDITY_AUTHORITY_TAG,
+            mint.as_ref(),
+            &[liquidity_authority_bump],
+        ];
+
+        let (token_mint_0, token_vault_0, token_vault_1) = {
+            let pool_state = self.pool_state.load()?;
+            (
+                pool_state.token_mint_0,
+                pool_state.token_vault_0,
+                pool_state.token_vault_1,
+            )
+        };
+        require_keys_eq!(token_vault_0, self.token_vault_0.key(), XError::InvalidAddress);
+        require_keys_eq!(token_vault_1, self.token_vault_1.key(), XError::InvalidAddress);
+        let wsol_is_token_0 = token_mint_0 == self.wsol_mint.key();
+        let (wsol_vault, wrapper_vault) = if wsol_is_token_0 {
+            (self.token_vault_0.to_account_info(), self.token_vault_1.to_account_info())
+        } else {
+            (self.token_vault_1.to_account_info(), self.token_vault_0.to_account_info())
+        };
+
+        // reference price of the swap and the deposit, None while the check is disabled
+        let max_slippage_bps = self.fee_config.max_slippage_bps;
+        let reference = if max_slippage_bps == 0 {
+            None
+        } else {
+            Some(reference_price_x64(
+                &self.fee_config,
+                &self.pool_state.to_account_info(),
+                &self.observation_state,
+                self.price_oracle.as_deref(),
+                &self.wrapper_mint,
+                &self.wsol_mint,
+            )?)
+        };
+
+        // half of the liquidity fee is paired as wrapper_mint
+        let swap_amount = self.liquidity_wsol_token_account.amount / 2;
+        require!(swap_amount > 0, XError::AmountTooSmall);
+        let minimum_wrapper_amount = match reference {
+            Some((price_x64, wrapper_is_token_0)) => utils::apply_slippage(
+                utils::quote_by_price_x64(swap_amount, price_x64, !wrapper_is_token_0)?,
+                max_slippage_bps,
+            )?,
+            None => 0,
+        };
+        let wrapper_balance_before_swap = self.liquidity_wrapper_token_account.amount;
+        raydium_amm_v3::cpi::swap(
             CpiContext::new(
-                self.token_program_treasury.to_account_info(),
-                TransferChecked {
-                    from: self.treasury_token_account.to_account_info(),
-                    to: self.user_token_account.to_account_info(),
-                    mint: self.treasury_mint.to_account_info(),
-                    authority: treasury.to_account_info(),
+                self.amm_program.to_account_info(),
+                raydium_amm_v3::cpi::accounts::SwapSingle {
+                    payer: self.liquidity_authority.to_account_info(),
+                    amm_config: self.amm_config.to_account_info(),
+                    pool_state: self.pool_state.to_account_info(),
+                    input_token_account: self.liquidity_wsol_token_account.to_account_info(),
+                    output_token_account: self.liquidity_wrapper_token_account.to_account_info(),
+                    input_vault: wsol_vault,
+                    output_vault: wrapper_vault,
+                    observation_state: self.observation_state.to_account_info(),
+                    token_program: self.token_program.to_account_info(),
+                    tick_array: self.swap_tick_array.to_account_info(),
+                },
+            )
+            .with_signer(&[authority_seeds]),
+            swap_amount,
+            minimum_wrapper_amount, // other_amount_threshold
+            0,    // sqrt_price_limit_x64, raydium uses the min/max price for the direction
+            true, // is_base_input
+        )?;
+        self.liquidity_wsol_token_account.reload()?;
+        self.liquidity_wrapper_token_account.reload()?;
+        let wsol_before = self.liquidity_wsol_token_account.amount;
+        let wrapper_before = self.liquidity_wrapper_token_account.amount;
+        let wrapper_swapped = wrapper_before
+            .checked_sub(wrapper_balance_before_swap)
+            .ok_or(XError::InvalidSwapResult)?;
+        require!(wrapper_swapped >= minimum_wrapper_amount, XError::SlippageExceeded);
+
+        let position = &mut self.liquidity_position;
+        let opening = position.position_nft_mint == Pubkey::default();
+        if opening {
+            require_keys_eq!(self.payer.key(), self.fee_config.authority, XError::NotAllowed);
+            require!(tick_lower_index < tick_upper_index, XError::NotAllowed);
+            position.mint = mint;
+            position.pool_state = self.pool_state.key();
+            position.tick_lower_index = tick_lower_index;
+            position.tick_upper_index = tick_upper_index;
+        }
+
+        // liquidity for the balances at the reference price, raydium rejects the deposit
+        // if the pool price has moved so far that it takes more than the maxes. without
+        // the check the pool price is used and the maxes are what it takes at it. what
+        // does not fit stays in the liquidity authority accounts for the next call
+        let (token_account_0, token_account_1, balance_0, balance_1) = if wsol_is_token_0 {
+            (
+                self.liquidity_wsol_token_account.to_account_info(),
+                self.liquidity_wrapper_token_account.to_account_info(),
+                wsol_before,
+                wrapper_before,
+            )
+        } else {
+            (
+                self.liquidity_wrapper_token_account.to_account_info(),
+                self.liquidity_wsol_token_account.to_account_info(),
+                wrapper_before,
+                wsol_before,
+            )
+        };
+        let sqrt_price_x64 = match reference {
+            Some((price_x64, _)) => utils::sqrt_price_x64(price_x64),
+            None => self.pool_state.load()?.sqrt_price_x64,
+        };
+        let (liquidity, amount_0_max, amount_1_max) = utils::bounded_deposit(
+            sqrt_price_x64,
+            position.tick_lower_index,
+            position.tick_upper_index,
+            balance_0,
+            balance_1,
+            max_slippage_bps,
+        )?;
+        require!(liquidity > 0, XError::AmountTooSmall);
+
+        if opening {
+            let nft_mint_seeds: &[&[u8]] = &[
+                POSITION_NFT_MINT_TAG,
+                mint.as_ref(),
+                &[position_nft_mint_bump],
+            ];
+            // raydium pulls the deposit of a new position from the payer, who may spend
+            // up to the maxes of the liquidity authority accounts
+            for (token_account, amount_max) in [
+                (&token_account_0, amount_0_max),
+                (&token_account_1, amount_1_max),
+            ] {
+                approve(
+                    CpiContext::new(
+                        self.token_program.to_account_info(),
+                        Approve {
+                            to: token_account.clone(),
+                            delegate: self.payer.to_account_info(),
+                            authority: self.liquidity_authority.to_account_info(),
+                        },
+                    )
+                    .with_signer(&[authority_seeds]),
+                    amount_max,
+                )?;
+            }
+            raydium_amm_v3::cpi::open_position(
+                CpiContext::new(
+                    self.amm_program.to_account_info(),
+                    raydium_amm_v3::cpi::accounts::OpenPosition {
+                        payer: self.payer.to_account_info(),
+                        position_nft_owner: self.liquidity_authority.to_account_info(),
+                        position_nft_mint: self.position_nft_mint.to_account_info(),
+                        position_nft_account: self.position_nft_account.to_account_info(),
+                        metadata_account: self.metadata_account.to_account_info(),
+                        pool_state: self.pool_state.to_account_info(),
+                        protocol_position: self.protocol_position.to_account_info(),
+                        tick_array_lower: self.tick_array_lower.to_account_info(),
+                        tick_array_upper: self.tick_array_upper.to_account_info(),
+                        personal_position: self.personal_position.to_account_info(),
+                        token_account_0: token_account_0.clone(),
+                        token_account_1: token_account_1.clone(),
+                        token_vault_0: self.token_vault_0.to_account_info(),
+                        token_vault_1: self.token_vault_1.to_account_info(),
+                        rent: self.rent.to_account_info(),
+                        system_program: self.system_program.to_account_info(),
+                        token_program: self.token_program.to_account_info(),
+                        associated_token_program: self.associated_token_program.to_account_info(),
+                        metadata_program: self.metadata_program.to_account_info(),
+                    },
+                )
+                .with_signer(&[authority_seeds, nft_mint_seeds]),
+                tick_lower_index,
+                tick_upper_index,
+                tick_array_lower_start_index,
+                tick_array_upper_start_index,
+                liquidity,
+                amount_0_max,
+                amount_1_max,
+            )?;
+            for token_account in [&token_account_0, &token_account_1] {
+                revoke(
+                    CpiContext::new(
+                        self.token_program.to_account_info(),
+                        Revoke {
+                            source: token_account.clone(),
+                            authority: self.liquidity_authority.to_account_info(),
+                        },
+                    )
+                    .with_signer(&[authority_seeds]),
+                )?;
+            }
+            position.position_nft_mint = self.position_nft_mint.key();
+        } else {
+            raydium_amm_v3::cpi::increase_liquidity(
+                CpiContext::new(
+                    self.amm_program.to_account_info(),
+                    raydium_amm_v3::cpi::accounts::IncreaseLiquidity {
+                        nft_owner: self.liquidity_authority.to_account_info(),
+                        nft_account: self.position_nft_account.to_account_info(),
+                        pool_state: self.pool_state.to_account_info(),
+                        protocol_position: self.protocol_position.to_account_info(),
+                        personal_position: self.personal_position.to_account_info(),
+                        tick_array_lower: self.tick_array_lower.to_account_info(),
+                        tick_array_upper: self.tick_array_upper.to_account_info(),
+                        token_account_0,
+                        token_account_1,
+                        token_vault_0: self.token_vault_0.to_account_info(),
+                        token_vault_1: self.token_vault_1.to_account_info(),
+                        token_program: self.token_program.to_account_info(),
+                    },
+                )
+                .with_signer(&[authority_seeds]),
+                liquidity,
+                amount_0_max,
+                amount_1_max,
+            )?;
+        }
+
+        self.liquidity_wsol_token_account.reload()?;
+        self.liquidity_wrapper_token_account.reload()?;
+        let wsol_amount = wsol_before
+            .checked_sub(self.liquidity_wsol_token_account.amount)
+            .ok_or(XError::MathOverflow)?;
+        let wrapper_amount = wrapper_before
+            .checked_sub(self.liquidity_wrapper_token_account.amount)
+            .ok_or(XError::MathOverflow)?;
+
+        let position = &mut self.liquidity_position;
+        position.liquidity = position
+            .liquidity
+            .checked_add(liquidity)
+            .ok_or(XError::MathOverflow)?;
+        position.total_wsol_added = position
+            .total_wsol_added
+            .checked_add(wsol_amount)
+            .ok_or(XError::MathOverflow)?;
+        position.total_wrapper_added = position
+            .total_wrapper_added
+            .checked_add(wrapper_amount)
+            .ok_or(XError::MathOverflow)?;
+
+        emit!(AutoLiquidityAdded {
+            mint,
+            position_nft_mint: position.position_nft_mint,
+            liquidity,
+            wrapper_amount,
+            wsol_amount,
+        });
+
+        Ok(())
+    }
+}
+
+impl<'info> BuybackAndBurn<'info> {
+    // min wrapper_mint out of swapping `amount` of wsol, 0 while the check is disabled
+    pub fn minimum_swap_output(&self, amount: u64) -> Result<u64> {
+        let max_slippage_bps = self.fee_config.max_slippage_bps;
+        if max_slippage_bps == 0 {
+            return Ok(0);
+        }
+        let (price_x64, wrapper_is_token_0) = reference_price_x64(
+            &self.fee_config,
+            &self.pool_state,
+            &self.observation_state,
+            self.price_oracle.as_deref(),
+            &self.wrapper_mint,
+            &self.wsol_mint,
+        )?;
+        let quote = utils::quote_by_price_x64(amount, price_x64, !wrapper_is_token_0)?;
+        utils::apply_slippage(quote, max_slippage_bps)
+    }
+
+    // permissionless crank, swaps up to `amount` of the buyback wsol to wrapper_mint
+    // (the pool side of the hooked token) and burns what it got
+    pub fn buyback_and_burn(&mut self, buyback_authority_bump: u8, amount: u64) -> Result<()> {
+        require!(!self.fee_config.swap_paused, XError::Paused);
+        let amount = amount.min(self.buyback_wsol_token_account.amount);
+        require!(amount > 0, XError::AmountTooSmall);
+
+        let mint = self.mint.key();
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            BUYBACK_AUTHORITY_TAG,
+            mint.as_ref(),
+            &[buyback_authority_bump],
+        ]];
+
+        // a worse execution aborts the swap, the wsol stays for a later crank
+        let minimum_wrapper_amount = self.minimum_swap_output(amount)?;
+
+        let wrapper_balance_before = self.buyback_wrapper_token_account.amount;
+        raydium_amm_v3::cpi::swap(
+            CpiContext::new(
+                self.amm_program.to_account_info(),
+                raydium_amm_v3::cpi::accounts::SwapSingle {
+                    payer: self.buyback_authority.to_account_info(),
+                    amm_config: self.amm_config.to_account_info(),
+                    pool_state: self.pool_state.to_account_info(),
+                    input_token_account: self.buyback_wsol_token_account.to_account_info(),
+                    output_token_account: self.buyback_wrapper_token_account.to_account_info(),
+                    input_vault: self.input_vault.to_account_info(),
+                    output_vault: self.output_vault.to_account_info(),
+                    observation_state: self.observation_state.to_account_info(),
+                    token_program: self.token_program.to_account_info(),
+                    tick_array: self.tick_array.to_account_info(),
                 },
             )
             .with_signer(signer_seeds),
             amount,
-            self.treasury_mint.decimals,
+            minimum_wrapper_amount, // other_amount_threshold
+            0,    // sqrt_price_limit_x64, raydium uses the min/max price for the direction
+            true, // is_base_input
         )?;
+        self.buyback_wrapper_token_account.reload()?;
+        let burned_amount = self
+            .buyback_wrapper_token_account
+            .amount
+            .checked_sub(wrapper_balance_before)
+            .ok_or(XError::InvalidSwapResult)?;
+        require!(burned_amount >= minimum_wrapper_amount, XError::SlippageExceeded);
 
-        emit!(Claimed {
-            treasury: treasury.key(),
-            user: self.user.key(),
-            amount: amount,
+        // the hooked token cannot be bought back, raydium clmm pools do not take mints with
+        // the transfer hook extension, so wrapper_mint stands for it in the pool; it is the
+        // fee crank that mints wrapper_mint into the pool, burning the bought back amount
+        // retires that supply
+        burn(
+            CpiContext::new(
+                self.token_program.to_account_info(),
+                Burn {
+                    mint: self.wrapper_mint.to_account_info(),
+                    from: self.buyback_wrapper_token_account.to_account_info(),
+                    authority: self.buyback_authority.to_account_info(),
+                },
+            )
+            .with_signer(signer_seeds),
+            burned_amount,
+        )?;
+
+        let fee_accrual = &mut self.fee_accrual;
+        fee_accrual.total_buyback_wsol = fee_accrual
+            .total_buyback_wsol
+            .checked_add(amount)
+            .ok_or(XError::MathOverflow)?;
+        fee_accrual.total_buyback_burned = fee_accrual
+            .total_buyback_burned
+            .checked_add(burned_amount)
+            .ok_or(XError::MathOverflow)?;
+
+        emit!(BuybackBurned {
+            mint,
+            wsol_amount: amount,
+            burned_amount,
+            total_buyback_wsol: fee_accrual.total_buyback_wsol,
+            total_buyback_burned: fee_accrual.total_buyback_burned,
         });
 
         Ok(())
@@ -1,22 +1,729 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, Discriminator};
+use anchor_spl::associated_token::get_associated_token_address;
+
+use crate::constants::{
+    BASE_LOCK_MULTIPLIER_BPS, DEFAULT_FEE_PERCENT_CAP, DEFAULT_MAX_SLIPPAGE_BPS,
+    DEFAULT_PRICE_WINDOW, FEE_CONFIG_VERSION, FEE_PERCENT_DENOMINATOR,
+    MAX_FEE_RECIPIENT_LABEL_LEN, RESERVED_SPACE, REWARD_PRECISION, TREASURY_VERSION,
+};
+use crate::errors::XError;
+use crate::utils::split_by_weights;
 
 #[account]
 pub struct FeeConfig {
+    pub version: u8,               // FEE_CONFIG_VERSION, upgraded by migrate_fee_config
+    pub authority: Pubkey, // Pubkey::default() once renounced
+    pub pending_authority: Pubkey,
     pub wsol_mint_address: Pubkey,
     pub wrapper_mint_address: Pubkey,
-    pub fee_recipient_liquidity: Pubkey,
-    pub fee_recipient_marketing: Pubkey,
-    pub fee_recipient_holders: Pubkey,
-    pub fee_percent_liquidity: u16, // 100 means 1%
-    pub fee_percent_marketing: u16, // 400 means 4%
-    pub fee_percent_holders: u16,   // 500 means 5%
+    pub transfer_fee: u16,         // wallet to wallet, 100 means 1%
+    pub buy_fee: u16,              // source is a registered amm pool vault
+    pub sell_fee: u16,             // destination is a registered amm pool vault
+    pub amm_pool_state: Pubkey,    // raydium clmm pool (wrapper_mint <-> wsol)
+    pub accrual_threshold: u64,    // min pending fee (in wrapper_mint) for process_accrued_fees
+    pub fee_percent_cap: u16,      // max fee of each direction
+    pub guardian: Pubkey,          // may pause besides the authority
+    pub fee_collection_paused: bool, // transfers still go through, without fees
+    pub swap_paused: bool,
+    pub max_slippage_bps: u16,    // fee swap min-out below the reference price, 0 disables the check
+    pub price_window: u32,        // seconds of the pool twap, or max age of the oracle price
+    pub price_oracle: Pubkey,     // pyth price account (wsol per wrapper_mint), Pubkey::default() uses the pool twap
+    pub max_tx_amount: TransferLimit,      // per transfer, fee exempt wallets skip the limits
+    pub max_wallet_balance: TransferLimit, // destination balance after the transfer
+    pub trading_enabled_at: i64,   // 0 until enable_trading, only exempt wallets transfer before it
+    pub sniper_fee: u16,           // fee at trading_enabled_at, decays to the direction fee
+    pub sniper_fee_duration: u32,  // seconds of the decay
+    pub sell_cooldown: i64,        // seconds between sells of an owner, 0 disables it
+    pub reserved: [u8; RESERVED_SPACE], // zeroed, new fields take their space
+    pub fee_recipients: Vec<FeeRecipient>, // split of the swapped fee, bps sum up to 100%
+}
+
+impl FeeConfig {
+    // account size (with the discriminator) for `recipients` fee recipients
+    pub fn space(recipients: usize) -> usize {
+        8 + 1 + 32 * 4 + 2 * 3 + 32 + 8 + 2 + 32 + 1 + 1 + 2 + 4 + 32
+            + TransferLimit::SPACE * 2
+            + 8 + 2 + 4
+            + 8
+            + RESERVED_SPACE
+            + 4
+            + recipients * FeeRecipient::SPACE
+    }
+
+    pub fn fee_percent(&self, direction: TransferDirection) -> u16 {
+        match direction {
+            TransferDirection::Transfer => self.transfer_fee,
+            TransferDirection::Buy => self.buy_fee,
+            TransferDirection::Sell => self.sell_fee,
+        }
+    }
+
+    pub fn is_trading_enabled(&self, now: i64) -> bool {
+        self.trading_enabled_at != 0 && now >= self.trading_enabled_at
+    }
+
+    // bps of the swapped fee sent to the wsol token account `recipient`, 0 if it is not a recipient
+    pub fn fee_share(&self, recipient: &Pubkey) -> u16 {
+        self.fee_recipients
+            .iter()
+            .find(|fee_recipient| fee_recipient.recipient == *recipient)
+            .map_or(0, |fee_recipient| fee_recipient.bps)
+    }
+
+    // direction fee raised by the sniper fee while it decays, trading has to be enabled
+    pub fn current_fee_percent(&self, direction: TransferDirection, now: i64) -> u16 {
+        crate::utils::decay_fee(
+            self.fee_percent(direction),
+            self.sniper_fee,
+            now.saturating_sub(self.trading_enabled_at),
+            self.sniper_fee_duration,
+        )
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct FeeRecipient {
+    pub recipient: Pubkey, // wsol token account
+    pub bps: u16,          // share of the fee, 5000 means half of it
+    pub label: String,     // e.g. "marketing", at most MAX_FEE_RECIPIENT_LABEL_LEN bytes
+}
+
+impl FeeRecipient {
+    pub const SPACE: usize = 32 + 2 + 4 + MAX_FEE_RECIPIENT_LABEL_LEN;
+}
+
+// anti-whale limit of the transfer hook
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum TransferLimit {
+    #[default]
+    Unlimited,
+    Amount(u64),    // raw amount of the hooked mint
+    SupplyBps(u16), // share of the current supply, 100 means 1%
+}
+
+impl TransferLimit {
+    pub const SPACE: usize = 1 + 8;
+
+    // the limit in raw amount at `supply`, None if unlimited
+    pub fn resolve(&self, supply: u64) -> Option<u64> {
+        match *self {
+            TransferLimit::Unlimited => None,
+            TransferLimit::Amount(amount) => Some(amount),
+            TransferLimit::SupplyBps(bps) => Some(
+                (supply as u128 * bps as u128 / FEE_PERCENT_DENOMINATOR as u128) as u64,
+            ),
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransferDirection {
+    Transfer,
+    Buy,
+    Sell,
 }
 
 #[account]
 #[derive(Default)]
+pub struct FeeAccrual {
+    pub mint: Pubkey,
+    pub pending_fee: u64, // recorded by transfer_hook, not minted/swapped yet
+    pub total_accrued: u64,
+    pub total_processed: u64,
+    pub last_processed_at: i64,
+    pub total_buyback_wsol: u64,   // wsol spent by buyback_and_burn
+    pub total_buyback_burned: u64, // wrapper_mint burned by buyback_and_burn
+}
+
+#[account]
 pub struct Treasury {
+    pub version: u8,                 // TREASURY_VERSION, upgraded by migrate_treasury
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub treasury_mint: Pubkey,
+    pub wrapper_mint: Pubkey,
+    pub treasury_token_account: Pubkey,
+    pub total_staked: u64,           // shares of all StakerInfo
+    pub total_weight: u64,           // reward weight of all StakerInfo
+    pub acc_reward_per_share: u128,  // wsol per unit of weight, scaled by REWARD_PRECISION
+    pub reward_vault_balance: u64,   // reward vault balance already accounted for
+    pub early_redeem_penalty_bps: u16, // 0 rejects redeeming a locked stake
+    pub redeem_cooldown: i64,        // seconds between request_redeem and complete_redeem, 0 allows redeem
+    pub total_pending_redeem: u64,   // shares held by WithdrawalTicket
+    pub guardian: Pubkey,            // may pause besides the authority
+    pub stake_paused: bool,
+    pub redeem_paused: bool,
+    pub reserved: [u8; RESERVED_SPACE], // zeroed, new fields take their space
+}
+
+impl Treasury {
+    pub const SPACE: usize =
+        8 + 1 + 32 * 5 + 8 + 8 + 16 + 8 + 2 + 8 + 8 + 32 + 1 + 1 + RESERVED_SPACE;
+
+    // accounts wsol that arrived in the reward vault since the last update
+    pub fn update_rewards(&mut self, reward_vault_balance: u64) -> Result<()> {
+        if self.total_weight == 0 {
+            // keep it for the first stakers
+            return Ok(());
+        }
+        let new_rewards = reward_vault_balance.saturating_sub(self.reward_vault_balance);
+        let reward_per_share = (new_rewards as u128)
+            .checked_mul(REWARD_PRECISION)
+            .ok_or(XError::MathOverflow)?
+            / self.total_weight as u128;
+        self.acc_reward_per_share = self
+            .acc_reward_per_share
+            .checked_add(reward_per_share)
+            .ok_or(XError::MathOverflow)?;
+        self.reward_vault_balance = reward_vault_balance;
+        Ok(())
+    }
+
+    // shares issued against the treasury backing, staked or queued for redeem. shares are
+    // only this ledger, wrapper_mint is the fee token of the pool and backs nothing
+    pub fn total_shares(&self) -> Result<u64> {
+        self.total_staked
+            .checked_add(self.total_pending_redeem)
+            .ok_or(error!(XError::MathOverflow))
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct StakerInfo {
+    pub treasury: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,            // staked shares
+    pub weight: u64,            // amount boosted by the lock multiplier
+    pub reward_debt: u128,      // weight * acc_reward_per_share at the last checkpoint
+    pub pending_rewards: u64,   // settled but not claimed wsol
+    pub lock_end: i64,          // 0 when not locked
+    pub lock_multiplier_bps: u16,
+}
+
+impl StakerInfo {
+    // moves rewards earned since the last checkpoint into pending_rewards
+    pub fn settle(&mut self, acc_reward_per_share: u128) -> Result<()> {
+        let accumulated = self.accumulated_rewards(acc_reward_per_share)?;
+        let earned = accumulated
+            .checked_sub(self.reward_debt)
+            .and_then(|earned| u64::try_from(earned).ok())
+            .ok_or(XError::MathOverflow)?;
+        self.pending_rewards = self
+            .pending_rewards
+            .checked_add(earned)
+            .ok_or(XError::MathOverflow)?;
+        self.reward_debt = accumulated;
+        Ok(())
+    }
+
+    pub fn checkpoint(&mut self, acc_reward_per_share: u128) -> Result<()> {
+        self.reward_debt = self.accumulated_rewards(acc_reward_per_share)?;
+        Ok(())
+    }
+
+    fn accumulated_rewards(&self, acc_reward_per_share: u128) -> Result<u128> {
+        Ok((self.weight as u128)
+            .checked_mul(acc_reward_per_share)
+            .ok_or(XError::MathOverflow)?
+            / REWARD_PRECISION)
+    }
+
+    pub fn is_locked(&self, now: i64) -> bool {
+        now < self.lock_end
+    }
+
+    // extends the lock, a shorter lock never reduces an existing one
+    pub fn lock(&mut self, now: i64, lock_duration: i64, lock_multiplier_bps: u16) -> Result<()> {
+        let lock_end = now.checked_add(lock_duration).ok_or(XError::MathOverflow)?;
+        self.lock_end = self.lock_end.max(lock_end);
+        self.lock_multiplier_bps = self.lock_multiplier_bps.max(lock_multiplier_bps);
+        Ok(())
+    }
+
+    // drops the boost once the lock is over, true if it expired just now
+    pub fn expire_lock(&mut self, now: i64) -> bool {
+        if self.lock_end == 0 || self.is_locked(now) {
+            return false;
+        }
+        self.lock_end = 0;
+        self.lock_multiplier_bps = BASE_LOCK_MULTIPLIER_BPS;
+        true
+    }
+
+    // recomputes the weight from the staked shares, call between settle and checkpoint
+    pub fn sync_weight(&mut self, treasury: &mut Treasury) -> Result<()> {
+        let lock_multiplier_bps = self.lock_multiplier_bps.max(BASE_LOCK_MULTIPLIER_BPS);
+        let weight = u64::try_from(
+            self.amount as u128 * lock_multiplier_bps as u128 / BASE_LOCK_MULTIPLIER_BPS as u128,
+        )
+        .map_err(|_| XError::MathOverflow)?;
+        treasury.total_weight = treasury
+            .total_weight
+            .checked_sub(self.weight)
+            .and_then(|total_weight| total_weight.checked_add(weight))
+            .ok_or(XError::MathOverflow)?;
+        self.weight = weight;
+        Ok(())
+    }
+}
+
+// existence of this account means `wallet` neither pays nor triggers the transfer fee
+#[account]
+#[derive(Default)]
+pub struct FeeExemption {
+    pub mint: Pubkey,
+    pub wallet: Pubkey,
+}
+
+// last sell of a transfer authority, required by transfer_hook for sells while
+// fee_config.sell_cooldown is set (created by init_sell_cooldown)
+#[account]
+#[derive(Default)]
+pub struct SellCooldown {
+    pub mint: Pubkey,
+    pub owner: Pubkey,
+    pub last_sell_at: i64,
+}
+
+// existence of this account means token accounts owned by `wallet` can neither send nor
+// receive the hooked mint, exemptions included
+#[account]
+#[derive(Default)]
+pub struct BlocklistEntry {
+    pub mint: Pubkey,
+    pub wallet: Pubkey,
+}
+
+// registered amm pool vault (token account of the hooked mint), decides buy/sell direction
+#[account]
+#[derive(Default)]
+pub struct AmmPoolVault {
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+}
+
+#[account]
+pub struct WithdrawalTicket {
+    pub treasury: Pubkey,
+    pub user: Pubkey,
+    pub shares: u64,            // shares held until claimable_at
+    pub requested_at: i64,
+    pub claimable_at: i64,
+}
+
+// raydium clmm position of the auto-liquidity, the nft is held by the liquidity authority pda
+#[account]
+pub struct LiquidityPosition {
+    pub mint: Pubkey,
+    pub pool_state: Pubkey,
+    pub position_nft_mint: Pubkey, // Pubkey::default() until opened
+    pub tick_lower_index: i32,
+    pub tick_upper_index: i32,
+    pub liquidity: u128,
+    pub total_wrapper_added: u64,
+    pub total_wsol_added: u64,
+}
+
+// unversioned (v1) layouts, still on chain until migrate_treasury / migrate_fee_config
+// upgrade them in place. their space was size_of::<T>() + 8, which no later layout matches
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct TreasuryV1 {
     pub authority: Pubkey,
     pub treasury_mint: Pubkey,
     pub wrapper_mint: Pubkey,
     pub treasury_token_account: Pubkey,
 }
+
+impl TreasuryV1 {
+    pub const SPACE: usize = 8 + 32 * 4;
+
+    // rejects anything but a v1 treasury, e.g. an already upgraded one
+    pub fn try_from_account_data(data: &[u8]) -> Result<Self> {
+        require!(
+            data.len() == Self::SPACE && data[..8] == Treasury::DISCRIMINATOR,
+            XError::InvalidAccountVersion
+        );
+        Self::try_from_slice(&data[8..]).map_err(|_| error!(XError::InvalidAccountVersion))
+    }
+
+    // v1 had no staking, every counter starts from zero
+    pub fn upgrade(self) -> Treasury {
+        Treasury {
+            version: TREASURY_VERSION,
+            authority: self.authority,
+            pending_authority: Pubkey::default(),
+            treasury_mint: self.treasury_mint,
+            wrapper_mint: self.wrapper_mint,
+            treasury_token_account: self.treasury_token_account,
+            total_staked: 0,
+            total_weight: 0,
+            acc_reward_per_share: 0,
+            reward_vault_balance: 0,
+            early_redeem_penalty_bps: 0,
+            redeem_cooldown: 0,
+            total_pending_redeem: 0,
+            guardian: Pubkey::default(),
+            stake_paused: false,
+            redeem_paused: false,
+            reserved: [0; RESERVED_SPACE],
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct FeeConfigV1 {
+    pub wsol_mint_address: Pubkey,
+    pub wrapper_mint_address: Pubkey,
+    pub fee_recipient_liquidity: Pubkey, // wallets, paid through their wsol token accounts
+    pub fee_recipient_marketing: Pubkey,
+    pub fee_recipient_holders: Pubkey,
+    pub fee_percent_liquidity: u16, // 100 means 1%
+    pub fee_percent_marketing: u16,
+    pub fee_percent_holders: u16,
+}
+
+impl FeeConfigV1 {
+    pub const SPACE: usize = 8 + 32 * 5 + 2 * 3;
+
+    // rejects anything but a v1 fee config, e.g. an already upgraded one
+    pub fn try_from_account_data(data: &[u8]) -> Result<Self> {
+        require!(
+            data.len() == Self::SPACE && data[..8] == FeeConfig::DISCRIMINATOR,
+            XError::InvalidAccountVersion
+        );
+        Self::try_from_slice(&data[8..]).map_err(|_| error!(XError::InvalidAccountVersion))
+    }
+
+    // v1 had no authority and charged the sum of its percents on every transfer.
+    // the split becomes fee recipients at the wsol ata of each wallet, and trading stays
+    // enabled since the mint already traded without the gate
+    pub fn upgrade(
+        self,
+        authority: Pubkey,
+        amm_pool_state: Pubkey,
+        accrual_threshold: u64,
+        now: i64,
+    ) -> Result<FeeConfig> {
+        let fee_percents = [
+            self.fee_percent_liquidity,
+            self.fee_percent_marketing,
+            self.fee_percent_holders,
+        ];
+        let fee_percent = fee_percents
+            .iter()
+            .fold(0u16, |total, fee_percent| total.saturating_add(*fee_percent))
+            .min(FEE_PERCENT_DENOMINATOR);
+        // without any fee the split does not matter, keep every recipient
+        let weights = match fee_percent {
+            0 => [1; 3],
+            _ => fee_percents.map(|fee_percent| fee_percent as u64),
+        };
+        let shares = split_by_weights(FEE_PERCENT_DENOMINATOR as u64, &weights)?;
+
+        let wallets = [
+            (self.fee_recipient_liquidity, "liquidity"),
+            (self.fee_recipient_marketing, "marketing"),
+            (self.fee_recipient_holders, "holders"),
+        ];
+        let mut fee_recipients: Vec<FeeRecipient> = Vec::with_capacity(wallets.len());
+        for ((wallet, label), bps) in wallets.into_iter().zip(shares) {
+            let bps = bps as u16;
+            let recipient = get_associated_token_address(&wallet, &self.wsol_mint_address);
+            // recipients have to be distinct, a wallet used twice gets both shares
+            match fee_recipients.iter_mut().find(|other| other.recipient == recipient) {
+                Some(other) => other.bps += bps,
+                None => fee_recipients.push(FeeRecipient {
+                    recipient,
+                    bps,
+                    label: label.to_string(),
+                }),
+            }
+        }
+
+        Ok(FeeConfig {
+            version: FEE_CONFIG_VERSION,
+            authority,
+            pending_authority: Pubkey::default(),
+            wsol_mint_address: self.wsol_mint_address,
+            wrapper_mint_address: self.wrapper_mint_address,
+            transfer_fee: fee_percent,
+            buy_fee: fee_percent,
+            sell_fee: fee_percent,
+            amm_pool_state,
+            accrual_threshold,
+            fee_percent_cap: DEFAULT_FEE_PERCENT_CAP.max(fee_percent),
+            guardian: Pubkey::default(),
+            fee_collection_paused: false,
+            swap_paused: false,
+            max_slippage_bps: DEFAULT_MAX_SLIPPAGE_BPS,
+            price_window: DEFAULT_PRICE_WINDOW,
+            price_oracle: Pubkey::default(),
+            max_tx_amount: TransferLimit::Unlimited,
+            max_wallet_balance: TransferLimit::Unlimited,
+            trading_enabled_at: now,
+            sniper_fee: 0,
+            sniper_fee_duration: 0,
+            sell_cooldown: 0,
+            reserved: [0; RESERVED_SPACE],
+            fee_recipients,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_code(err: Error) -> u32 {
+        match err {
+            Error::AnchorError(err) => err.error_code_number,
+            Error::ProgramError(err) => panic!("unexpected program error {:?}", err),
+        }
+    }
+
+    // account data as the unversioned program wrote it
+    fn v1_account_data<T: AnchorSerialize>(discriminator: [u8; 8], account: &T) -> Vec<u8> {
+        let mut data = discriminator.to_vec();
+        account.serialize(&mut data).unwrap();
+        data
+    }
+
+    fn fee_config_v1(fee_percents: [u16; 3]) -> FeeConfigV1 {
+        FeeConfigV1 {
+            wsol_mint_address: Pubkey::new_unique(),
+            wrapper_mint_address: Pubkey::new_unique(),
+            fee_recipient_liquidity: Pubkey::new_unique(),
+            fee_recipient_marketing: Pubkey::new_unique(),
+            fee_recipient_holders: Pubkey::new_unique(),
+            fee_percent_liquidity: fee_percents[0],
+            fee_percent_marketing: fee_percents[1],
+            fee_percent_holders: fee_percents[2],
+        }
+    }
+
+    #[test]
+    fn v1_space_matches_the_unversioned_size() {
+        assert_eq!(TreasuryV1::SPACE, std::mem::size_of::<TreasuryV1>() + 8);
+        assert_eq!(FeeConfigV1::SPACE, std::mem::size_of::<FeeConfigV1>() + 8);
+    }
+
+    #[test]
+    fn treasury_v1_bytes_upgrade_in_place() {
+        let treasury_v1 = TreasuryV1 {
+            authority: Pubkey::new_unique(),
+            treasury_mint: Pubkey::new_unique(),
+            wrapper_mint: Pubkey::new_unique(),
+            treasury_token_account: Pubkey::new_unique(),
+        };
+        let data = v1_account_data(Treasury::DISCRIMINATOR, &treasury_v1);
+        assert_eq!(data.len(), TreasuryV1::SPACE);
+
+        let parsed = TreasuryV1::try_from_account_data(&data).unwrap();
+        assert_eq!(parsed, treasury_v1);
+
+        let mut upgraded = Vec::new();
+        parsed.upgrade().try_serialize(&mut upgraded).unwrap();
+        assert_eq!(upgraded.len(), Treasury::SPACE);
+
+        let treasury = Treasury::try_deserialize(&mut upgraded.as_slice()).unwrap();
+        assert_eq!(treasury.version, TREASURY_VERSION);
+        assert_eq!(treasury.authority, treasury_v1.authority);
+        assert_eq!(treasury.treasury_mint, treasury_v1.treasury_mint);
+        assert_eq!(treasury.wrapper_mint, treasury_v1.wrapper_mint);
+        assert_eq!(treasury.treasury_token_account, treasury_v1.treasury_token_account);
+        assert_eq!(treasury.pending_authority, Pubkey::default());
+        assert_eq!(treasury.total_staked, 0);
+        assert_eq!(treasury.acc_reward_per_share, 0);
+        assert!(!treasury.stake_paused && !treasury.redeem_paused);
+        assert_eq!(treasury.reserved, [0; RESERVED_SPACE]);
+    }
+
+    #[test]
+    fn treasury_migration_rejects_other_layouts() {
+        let treasury_v1 = TreasuryV1::default();
+        let mut upgraded = Vec::new();
+        treasury_v1.clone().upgrade().try_serialize(&mut upgraded).unwrap();
+        let err = TreasuryV1::try_from_account_data(&upgraded).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::InvalidAccountVersion));
+
+        let data = v1_account_data(FeeConfig::DISCRIMINATOR, &treasury_v1);
+        let err = TreasuryV1::try_from_account_data(&data).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::InvalidAccountVersion));
+    }
+
+    #[test]
+    fn fee_config_v1_bytes_upgrade_in_place() {
+        let fee_config_v1 = fee_config_v1([100, 400, 500]);
+        let data = v1_account_data(FeeConfig::DISCRIMINATOR, &fee_config_v1);
+        assert_eq!(data.len(), FeeConfigV1::SPACE);
+
+        let authority = Pubkey::new_unique();
+        let amm_pool_state = Pubkey::new_unique();
+        let parsed = FeeConfigV1::try_from_account_data(&data).unwrap();
+        assert_eq!(parsed, fee_config_v1);
+
+        let mut upgraded = Vec::new();
+        parsed
+            .upgrade(authority, amm_pool_state, 1_000, 1_700_000_000)
+            .unwrap()
+            .try_serialize(&mut upgraded)
+            .unwrap();
+        // labels shorter than MAX_FEE_RECIPIENT_LABEL_LEN leave zeroed space
+        assert!(upgraded.len() <= FeeConfig::space(3));
+
+        let fee_config = FeeConfig::try_deserialize(&mut upgraded.as_slice()).unwrap();
+        assert_eq!(fee_config.version, FEE_CONFIG_VERSION);
+        assert_eq!(fee_config.authority, authority);
+        assert_eq!(fee_config.wsol_mint_address, fee_config_v1.wsol_mint_address);
+        assert_eq!(fee_config.wrapper_mint_address, fee_config_v1.wrapper_mint_address);
+        assert_eq!(fee_config.amm_pool_state, amm_pool_state);
+        assert_eq!(fee_config.accrual_threshold, 1_000);
+        // the v1 fee was the sum of its percents, in every direction
+        assert_eq!(fee_config.transfer_fee, 1000);
+        assert_eq!(fee_config.buy_fee, 1000);
+        assert_eq!(fee_config.sell_fee, 1000);
+        assert_eq!(fee_config.fee_percent_cap, DEFAULT_FEE_PERCENT_CAP);
+        assert!(fee_config.is_trading_enabled(1_700_000_000));
+        assert_eq!(fee_config.max_tx_amount, TransferLimit::Unlimited);
+        assert_eq!(fee_config.reserved, [0; RESERVED_SPACE]);
+
+        let wsol_ata = |wallet| {
+            get_associated_token_address(&wallet, &fee_config_v1.wsol_mint_address)
+        };
+        assert_eq!(
+            fee_config.fee_recipients,
+            vec![
+                FeeRecipient {
+                    recipient: wsol_ata(fee_config_v1.fee_recipient_liquidity),
+                    bps: 1000,
+                    label: "liquidity".to_string(),
+                },
+                FeeRecipient {
+                    recipient: wsol_ata(fee_config_v1.fee_recipient_marketing),
+                    bps: 4000,
+                    label: "marketing".to_string(),
+                },
+                FeeRecipient {
+                    recipient: wsol_ata(fee_config_v1.fee_recipient_holders),
+                    bps: 5000,
+                    label: "holders".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fee_config_v1_upgrade_keeps_the_recipients_valid() {
+        // no fee, the split is even
+        let fee_config = fee_config_v1([0, 0, 0])
+            .upgrade(Pubkey::new_unique(), Pubkey::new_unique(), 0, 1)
+            .unwrap();
+        let bps = fee_config.fee_recipients.iter().map(|r| r.bps).collect::<Vec<_>>();
+        assert_eq!(bps, [3333, 3333, 3334]);
+
+        // a wallet used twice gets both shares
+        let mut same_wallet = fee_config_v1([300, 300, 400]);
+        same_wallet.fee_recipient_marketing = same_wallet.fee_recipient_liquidity;
+        let fee_config = same_wallet
+            .upgrade(Pubkey::new_unique(), Pubkey::new_unique(), 0, 1)
+            .unwrap();
+        let bps = fee_config.fee_recipients.iter().map(|r| r.bps).collect::<Vec<_>>();
+        assert_eq!(bps, [6000, 4000]);
+        assert_eq!(fee_config.fee_recipients[0].label, "liquidity");
+
+        // a v1 fee above the default cap raises the cap
+        let fee_config = fee_config_v1([1000, 1000, 1000])
+            .upgrade(Pubkey::new_unique(), Pubkey::new_unique(), 0, 1)
+            .unwrap();
+        assert_eq!(fee_config.transfer_fee, 3000);
+        assert_eq!(fee_config.fee_percent_cap, 3000);
+
+        // v1 swapped without a min-out, the upgraded config is bounded like a new one
+        assert_eq!(fee_config.max_slippage_bps, DEFAULT_MAX_SLIPPAGE_BPS);
+        assert_eq!(fee_config.price_window, DEFAULT_PRICE_WINDOW);
+    }
+
+    #[test]
+    fn fee_share_of_a_recipient() {
+        let fee_config_v1 = fee_config_v1([100, 400, 500]);
+        let holders = fee_config_v1.fee_recipient_holders;
+        let holders_wsol_ata =
+            get_associated_token_address(&holders, &fee_config_v1.wsol_mint_address);
+        let fee_config = fee_config_v1
+            .upgrade(Pubkey::new_unique(), Pubkey::new_unique(), 0, 1)
+            .unwrap();
+        assert_eq!(fee_config.fee_share(&holders_wsol_ata), 5000);
+        // the wallet itself is not the recipient, its wsol token account is
+        assert_eq!(fee_config.fee_share(&holders), 0);
+        assert_eq!(fee_config.fee_share(&Pubkey::new_unique()), 0);
+    }
+
+    #[test]
+    fn fee_config_migration_rejects_other_layouts() {
+        let mut upgraded = Vec::new();
+        fee_config_v1([100, 400, 500])
+            .upgrade(Pubkey::new_unique(), Pubkey::new_unique(), 0, 1)
+            .unwrap()
+            .try_serialize(&mut upgraded)
+            .unwrap();
+        let err = FeeConfigV1::try_from_account_data(&upgraded).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::InvalidAccountVersion));
+
+        let data = v1_account_data(Treasury::DISCRIMINATOR, &fee_config_v1([0, 0, 0]));
+        let err = FeeConfigV1::try_from_account_data(&data).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::InvalidAccountVersion));
+    }
+
+    fn empty_treasury() -> Treasury {
+        TreasuryV1::default().upgrade()
+    }
+
+    #[test]
+    fn rewards_follow_the_weight() {
+        let mut treasury = empty_treasury();
+        let mut staker_info = StakerInfo {
+            amount: 1_000,
+            lock_multiplier_bps: 20000,
+            ..Default::default()
+        };
+        staker_info.sync_weight(&mut treasury).unwrap();
+        assert_eq!(staker_info.weight, 2_000);
+        assert_eq!(treasury.total_weight, 2_000);
+
+        treasury.update_rewards(500).unwrap();
+        staker_info.settle(treasury.acc_reward_per_share).unwrap();
+        assert_eq!(staker_info.pending_rewards, 500);
+
+        staker_info.amount = 0;
+        staker_info.sync_weight(&mut treasury).unwrap();
+        assert_eq!(treasury.total_weight, 0);
+    }
+
+    #[test]
+    fn reward_math_overflow_is_an_error() {
+        let mut treasury = Treasury {
+            total_weight: 1,
+            acc_reward_per_share: u128::MAX,
+            ..empty_treasury()
+        };
+        let err = treasury.update_rewards(1).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::MathOverflow));
+
+        let mut staker_info = StakerInfo {
+            weight: u64::MAX,
+            ..Default::default()
+        };
+        let err = staker_info.settle(u128::MAX).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::MathOverflow));
+
+        // a weight that is not part of the total
+        staker_info.amount = 1;
+        let err = staker_info.sync_weight(&mut empty_treasury()).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::MathOverflow));
+
+        let err = staker_info.lock(i64::MAX, 1, 12500).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::MathOverflow));
+    }
+}
@@ -0,0 +1,672 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+    ZeroCopy,
+};
+use anchor_lang::solana_program::sysvar::instructions::get_instruction_relative;
+use anchor_spl::{
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            transfer_fee::TransferFeeConfig, transfer_hook, BaseStateWithExtensions,
+            StateWithExtensions,
+        },
+        instruction::TokenInstruction,
+        state::Mint,
+    },
+    token_interface::TransferChecked,
+};
+use std::cell::Ref;
+
+use crate::constants::*;
+use crate::errors::XError;
+use crate::states::FeeRecipient;
+use raydium_amm_v3::libraries::{big_num::U256, liquidity_math, tick_math};
+
+// optional pda resolved through the ExtraAccountMetaList, may not be created yet
+pub fn is_initialized_pda(account: &AccountInfo) -> bool {
+    account.owner == &crate::ID && !account.data_is_empty()
+}
+
+// transfer_checked of a mint that may have a transfer hook, the remaining accounts of the
+// context are its extra accounts followed by the hook program and the meta list, in the
+// order the client resolved them. anchor's transfer_checked only passes the four transfer
+// accounts, and the token-2022 onchain helper resolves the hook pdas with the wrong program id
+pub fn transfer_checked_with_hook<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+) -> Result<()> {
+    let mut instruction = spl_token_2022::instruction::transfer_checked(
+        ctx.program.key,
+        ctx.accounts.from.key,
+        ctx.accounts.mint.key,
+        ctx.accounts.to.key,
+        ctx.accounts.authority.key,
+        &[],
+        amount,
+        decimals,
+    )?;
+    let mut account_infos = vec![
+        ctx.accounts.from,
+        ctx.accounts.mint,
+        ctx.accounts.to,
+        ctx.accounts.authority,
+    ];
+    for account in ctx.remaining_accounts {
+        instruction.accounts.push(match account.is_writable {
+            true => AccountMeta::new(*account.key, false),
+            false => AccountMeta::new_readonly(*account.key, false),
+        });
+        account_infos.push(account);
+    }
+    anchor_lang::solana_program::program::invoke_signed(&instruction, &account_infos, ctx.signer_seeds)
+        .map_err(Into::into)
+}
+
+// a mint hooked by this program. the runtime rejects the hook call of a transfer cpi of
+// this program as reentrant, so the treasury transfers of such a mint are top level
+// instructions next to stake and redeem
+pub fn is_self_hooked(mint: &AccountInfo) -> Result<bool> {
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&data)?;
+    Ok(transfer_hook::get_program_id(&mint) == Some(crate::ID))
+}
+
+// the top level token-2022 transfer_checked of `amount` between the given source, mint,
+// destination and authority, `offset` instructions away from the current one
+pub fn check_top_level_transfer(
+    instructions: &AccountInfo,
+    offset: i64,
+    accounts: [Pubkey; 4],
+    amount: u64,
+) -> Result<()> {
+    let instruction = get_instruction_relative(offset, instructions)
+        .map_err(|_| XError::InvalidTopLevelTransfer)?;
+    require_keys_eq!(
+        instruction.program_id,
+        spl_token_2022::ID,
+        XError::InvalidTopLevelTransfer
+    );
+    let transfer_amount = match TokenInstruction::unpack(&instruction.data) {
+        Ok(TokenInstruction::TransferChecked { amount, .. }) => amount,
+        _ => return err!(XError::InvalidTopLevelTransfer),
+    };
+    let keys = instruction.accounts.iter().take(4).map(|meta| meta.pubkey);
+    require!(
+        keys.eq(accounts) && transfer_amount == amount,
+        XError::InvalidTopLevelTransfer
+    );
+    Ok(())
+}
+
+// amount arriving of a transfer, less the token-2022 transfer fee withheld at the destination
+pub fn received_amount(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<Mint>::unpack(&data)?;
+    let fee = match mint.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or(XError::MathOverflow)?,
+        Err(_) => 0,
+    };
+    Ok(amount - fee) // fee <= amount
+}
+
+// zero copy view of an amm account the contexts keep unchecked, borrowed in place
+pub fn load_amm_account<'a, T: ZeroCopy + Owner>(account: &'a AccountInfo) -> Result<Ref<'a, T>> {
+    require_keys_eq!(*account.owner, T::owner(), ErrorCode::AccountOwnedByWrongProgram);
+    let data = account.try_borrow_data()?;
+    let len = 8 + std::mem::size_of::<T>();
+    require!(data.len() >= len, ErrorCode::AccountDidNotDeserialize);
+    require!(data[..8] == T::discriminator(), ErrorCode::AccountDiscriminatorMismatch);
+    Ok(Ref::map(data, |data| bytemuck::from_bytes(&data[8..len])))
+}
+
+// resizes a program owned account in place, `payer` tops up the rent of the new size
+pub fn realloc_with_rent<'info>(
+    account: &AccountInfo<'info>,
+    new_len: usize,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let lamports = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(account.lamports());
+    if lamports > 0 {
+        transfer(
+            CpiContext::new(
+                system_program.clone(),
+                Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            lamports,
+        )?;
+    }
+    account.realloc(new_len, true)?;
+    Ok(())
+}
+
+// share <-> asset conversion of the treasury, rounds down (in favor of the treasury).
+// total_assets is the treasury backing and total_shares the shares issued against it
+// (Treasury::total_shares).
+// the virtual shares/asset make donating to an empty treasury unprofitable
+// (first depositor inflation attack), the attacker loses most of the donation
+pub fn convert_to_shares(assets: u64, total_assets: u64, total_shares: u64) -> Result<u64> {
+    let shares = assets as u128 * (total_shares as u128 + VIRTUAL_SHARES as u128)
+        / (total_assets as u128 + VIRTUAL_ASSETS as u128);
+    u64::try_from(shares).map_err(|_| error!(XError::MathOverflow))
+}
+
+pub fn convert_to_assets(shares: u64, total_assets: u64, total_shares: u64) -> Result<u64> {
+    let assets = shares as u128 * (total_assets as u128 + VIRTUAL_ASSETS as u128)
+        / (total_shares as u128 + VIRTUAL_SHARES as u128);
+    u64::try_from(assets).map_err(|_| error!(XError::MathOverflow))
+}
+
+// fee math
+//
+// all intermediates are u128, results that do not fit return MathOverflow.
+// rounding policy: the fee rounds down (in favor of the payer), the split of a fee
+// into buckets rounds each bucket down and the remainder goes to the last bucket
+// with a non-zero weight, so the buckets always sum up to the fee.
+
+// fee of `amount` at `fee_percent` (10000 = 100%)
+pub fn compute_fee(amount: u64, fee_percent: u32) -> Result<u64> {
+    require!(
+        fee_percent <= FEE_PERCENT_DENOMINATOR as u32,
+        XError::FeeTooHigh
+    );
+    let fee = amount as u128 * fee_percent as u128 / FEE_PERCENT_DENOMINATOR as u128;
+    u64::try_from(fee).map_err(|_| error!(XError::MathOverflow))
+}
+
+// splits `total` by `weights`, all zero if the weights are all zero
+pub fn split_by_weights(total: u64, weights: &[u64]) -> Result<Vec<u64>> {
+    let mut buckets = vec![0u64; weights.len()];
+    let total_weight = weights.iter().map(|w| *w as u128).sum::<u128>();
+    if total_weight == 0 {
+        return Ok(buckets);
+    }
+
+    let mut assigned: u64 = 0;
+    for (bucket, weight) in buckets.iter_mut().zip(weights.iter()) {
+        *bucket = u64::try_from(total as u128 * *weight as u128 / total_weight)
+            .map_err(|_| error!(XError::MathOverflow))?;
+        assigned = assigned
+            .checked_add(*bucket)
+            .ok_or(XError::MathOverflow)?;
+    }
+
+    let remainder = total.checked_sub(assigned).ok_or(XError::MathOverflow)?;
+    if let Some(last) = weights.iter().rposition(|w| *w > 0) {
+        buckets[last] = buckets[last]
+            .checked_add(remainder)
+            .ok_or(XError::MathOverflow)?;
+    }
+    Ok(buckets)
+}
+
+// share of `fee` of each fee recipient, in the order of `fee_recipients`
+pub fn split_fee(fee: u64, fee_recipients: &[FeeRecipient]) -> Result<Vec<u64>> {
+    let weights = fee_recipients
+        .iter()
+        .map(|fee_recipient| fee_recipient.bps as u64)
+        .collect::<Vec<_>>();
+    split_by_weights(fee, &weights)
+}
+
+// fee of a transfer `elapsed` seconds after trading was enabled, decays linearly from
+// `sniper_fee` to `fee_percent` over `duration`, never below `fee_percent`
+pub fn decay_fee(fee_percent: u16, sniper_fee: u16, elapsed: i64, duration: u32) -> u16 {
+    if sniper_fee <= fee_percent || elapsed < 0 || elapsed >= duration as i64 {
+        return fee_percent;
+    }
+    let remaining = duration as u64 - elapsed as u64;
+    let extra = (sniper_fee - fee_percent) as u64 * remaining / duration as u64;
+    fee_percent + extra as u16
+}
+
+// price math of the fee swap slippage check
+//
+// quotes round down, the min-out of a swap is the quote less `max_slippage_bps`.
+
+// time weighted average price (Q64.64 of token_1 per token_0) over the last `window`
+// seconds of a clmm observation ring buffer of `len` slots, read in place.
+// `observation(i)` is the (block_timestamp, sqrt_price_x64, cumulative_time_price_x64) of
+// slot `i` and `newest` the last written slot. walks back to the newest observation at or
+// before `now - window` and averages the cumulative delta since, the newest price holding
+// until `now`. None if the history doesn't reach back that far
+pub fn twap_price_x64(
+    observation: impl Fn(usize) -> (u32, u128, u128),
+    len: usize,
+    newest: usize,
+    now: u32,
+    window: u32,
+) -> Result<Option<u128>> {
+    if window == 0 || newest >= len || now < window {
+        return Ok(None);
+    }
+    let window_start = now - window;
+    let (newest_timestamp, newest_sqrt_price_x64, newest_cumulative) = observation(newest);
+    if newest_timestamp == 0 || newest_timestamp > now {
+        return Ok(None);
+    }
+    let newest_price_x64 = (U256::from(newest_sqrt_price_x64) * U256::from(newest_sqrt_price_x64)) >> 64;
+    require!(newest_price_x64 <= U256::from(u128::MAX), XError::MathOverflow);
+
+    let mut index = newest;
+    let mut later_timestamp = newest_timestamp;
+    for _ in 0..len {
+        let (block_timestamp, _, cumulative) = observation(index);
+        // an empty slot or the wrap to the oldest observation
+        if block_timestamp == 0 || block_timestamp > later_timestamp {
+            return Ok(None);
+        }
+        if block_timestamp <= window_start {
+            // the cumulative wraps on overflow, so does the delta
+            let cumulative = U256::from(newest_cumulative.wrapping_sub(cumulative))
+                + newest_price_x64 * U256::from(now - newest_timestamp);
+            let twap = cumulative / U256::from(now - block_timestamp);
+            require!(twap <= U256::from(u128::MAX), XError::MathOverflow);
+            return Ok(Some(twap.as_u128()));
+        }
+        later_timestamp = block_timestamp;
+        index = index.checked_sub(1).unwrap_or(len - 1);
+    }
+    Ok(None)
+}
+
+// output of `amount` at a clmm price (Q64.64 of token_1 per token_0)
+pub fn quote_by_price_x64(amount: u64, price_x64: u128, zero_for_one: bool) -> Result<u64> {
+    require!(price_x64 > 0, XError::PriceUnavailable);
+    let quote = if zero_for_one {
+        (U256::from(amount) * U256::from(price_x64)) >> 64
+    } else {
+        (U256::from(amount) << 64) / U256::from(price_x64)
+    };
+    require!(quote <= U256::from(u64::MAX), XError::MathOverflow);
+    Ok(quote.as_u64())
+}
+
+// clmm price (Q64.64 of output per input base unit) of an oracle price of one whole
+// input token (`price` * 10^`expo` whole output tokens)
+pub fn oracle_price_x64(price: i64, expo: i32, input_decimals: u8, output_decimals: u8) -> Result<u128> {
+    require!(price > 0, XError::PriceUnavailable);
+    let price_x64 = U256::from(price as u64) << 64;
+    let exponent = expo + output_decimals as i32 - input_decimals as i32;
+    let price_x64 = if exponent >= 0 {
+        10u128
+            .checked_pow(exponent as u32)
+            .and_then(|scale| price_x64.checked_mul(U256::from(scale)))
+            .ok_or(XError::MathOverflow)?
+    } else {
+        10u128
+            .checked_pow(exponent.unsigned_abs())
+            .map_or(U256::zero(), |scale| price_x64 / U256::from(scale))
+    };
+    require!(price_x64 <= U256::from(u128::MAX), XError::MathOverflow);
+    Ok(price_x64.as_u128())
+}
+
+// the same clmm price in the other direction
+pub fn invert_price_x64(price_x64: u128) -> Result<u128> {
+    require!(price_x64 > 0, XError::PriceUnavailable);
+    let inverted = (U256::one() << 128) / U256::from(price_x64);
+    require!(inverted <= U256::from(u128::MAX), XError::MathOverflow);
+    Ok(inverted.as_u128())
+}
+
+// min-out of a swap expected to return `quote`
+pub fn apply_slippage(quote: u64, max_slippage_bps: u16) -> Result<u64> {
+    require!(
+        max_slippage_bps <= FEE_PERCENT_DENOMINATOR,
+        XError::InvalidSlippageConfig
+    );
+    let minimum = quote as u128 * (FEE_PERCENT_DENOMINATOR - max_slippage_bps) as u128
+        / FEE_PERCENT_DENOMINATOR as u128;
+    Ok(minimum as u64)
+}
+
+// clmm sqrt price (Q64.64) of a price (Q64.64), rounded down
+pub fn sqrt_price_x64(price_x64: u128) -> u128 {
+    (U256::from(price_x64) << 64).integer_sqrt().as_u128()
+}
+
+// liquidity of a clmm deposit of up to `amount_0` and `amount_1` between the ticks at
+// `sqrt_price_x64`, with the max amounts of it. `max_slippage_bps` of the amounts is held
+// back for a worse price at execution, the maxes are the amounts at `sqrt_price_x64` plus
+// the slippage and never more than `amount_0` and `amount_1`
+pub fn bounded_deposit(
+    sqrt_price_x64: u128,
+    tick_lower_index: i32,
+    tick_upper_index: i32,
+    amount_0: u64,
+    amount_1: u64,
+    max_slippage_bps: u16,
+) -> Result<(u128, u64, u64)> {
+    require!(
+        max_slippage_bps <= FEE_PERCENT_DENOMINATOR,
+        XError::InvalidSlippageConfig
+    );
+    let denominator = FEE_PERCENT_DENOMINATOR as u128;
+    let with_slippage = denominator + max_slippage_bps as u128;
+    let held_back = |amount: u64| (amount as u128 * denominator / with_slippage) as u64;
+
+    let liquidity = liquidity_math::get_liquidity_from_amounts(
+        sqrt_price_x64,
+        tick_math::get_sqrt_price_at_tick(tick_lower_index)?,
+        tick_math::get_sqrt_price_at_tick(tick_upper_index)?,
+        held_back(amount_0),
+        held_back(amount_1),
+    );
+    let (expected_0, expected_1) = liquidity_math::get_delta_amounts_signed(
+        tick_math::get_tick_at_sqrt_price(sqrt_price_x64)?,
+        sqrt_price_x64,
+        tick_lower_index,
+        tick_upper_index,
+        i128::try_from(liquidity).map_err(|_| XError::MathOverflow)?,
+    )?;
+
+    // raydium doesn't check a zero max, 1 is the tightest it takes
+    let amount_max = |expected: u64, amount: u64| {
+        (expected as u128 * with_slippage / denominator).min(amount as u128).max(1) as u64
+    };
+    Ok((
+        liquidity,
+        amount_max(expected_0, amount_0),
+        amount_max(expected_1, amount_1),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn error_code(err: Error) -> u32 {
+        match err {
+            Error::AnchorError(err) => err.error_code_number,
+            Error::ProgramError(err) => panic!("unexpected program error {:?}", err),
+        }
+    }
+
+    fn fee_recipients(bps: &[u16]) -> Vec<FeeRecipient> {
+        bps.iter()
+            .map(|bps| FeeRecipient {
+                recipient: Pubkey::new_unique(),
+                bps: *bps,
+                label: String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compute_fee_rounds_down() {
+        assert_eq!(compute_fee(10_000, 100).unwrap(), 100);
+        assert_eq!(compute_fee(199, 100).unwrap(), 1);
+        assert_eq!(compute_fee(99, 100).unwrap(), 0);
+        assert_eq!(compute_fee(0, 10_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn compute_fee_does_not_overflow_on_large_amounts() {
+        assert_eq!(compute_fee(u64::MAX, 10_000).unwrap(), u64::MAX);
+        assert_eq!(compute_fee(u64::MAX, 5_000).unwrap(), u64::MAX / 2);
+    }
+
+    #[test]
+    fn compute_fee_rejects_more_than_100_percent() {
+        let err = compute_fee(1, 10_001).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::FeeTooHigh));
+    }
+
+    #[test]
+    fn split_assigns_remainder_to_last_weighted_bucket() {
+        assert_eq!(split_by_weights(10, &[1, 1, 1]).unwrap(), [3, 3, 4]);
+        assert_eq!(split_by_weights(10, &[1, 1, 0]).unwrap(), [5, 5, 0]);
+        assert_eq!(split_by_weights(11, &[1, 1, 0]).unwrap(), [5, 6, 0]);
+        assert_eq!(split_by_weights(10, &[0, 0, 0]).unwrap(), [0, 0, 0]);
+        assert!(split_by_weights(10, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn split_fee_follows_recipient_bps() {
+        // 10% of 1000 tokens with 9 decimals, split 10/40/50
+        let fee = compute_fee(1_000_000_000_000, 1000).unwrap();
+        let fees = split_fee(fee, &fee_recipients(&[1000, 4000, 5000])).unwrap();
+        assert_eq!(fees, [10_000_000_000, 40_000_000_000, 50_000_000_000]);
+
+        let fees = split_fee(100, &fee_recipients(&[2500, 2500, 2500, 2500, 0])).unwrap();
+        assert_eq!(fees, [25, 25, 25, 25, 0]);
+    }
+
+    #[test]
+    fn sniper_fee_decays_to_the_fee() {
+        assert_eq!(decay_fee(500, 5000, 0, 600), 5000);
+        assert_eq!(decay_fee(500, 5000, 300, 600), 2750);
+        assert_eq!(decay_fee(500, 5000, 599, 600), 507);
+        assert_eq!(decay_fee(500, 5000, 600, 600), 500);
+        // no sniper fee, or lower than the fee
+        assert_eq!(decay_fee(500, 0, 0, 600), 500);
+        assert_eq!(decay_fee(500, 100, 0, 600), 500);
+        assert_eq!(decay_fee(500, 5000, 0, 0), 500);
+    }
+
+    // (block_timestamp, sqrt_price_x64, cumulative_time_price_x64) slots, the cumulative
+    // adds each new price for the time since the previous observation like the clmm does
+    fn observations(history: &[(u32, u128)], len: usize) -> Vec<(u32, u128, u128)> {
+        let mut slots = vec![(0, 0, 0); len];
+        let mut cumulative = 0u128;
+        let mut previous = None;
+        for (i, (block_timestamp, sqrt_price_x64)) in history.iter().enumerate() {
+            if let Some(previous) = previous {
+                let price_x64 = (U256::from(*sqrt_price_x64) * U256::from(*sqrt_price_x64)) >> 64;
+                cumulative = cumulative.wrapping_add(price_x64.as_u128() * (block_timestamp - previous) as u128);
+            }
+            slots[i % len] = (*block_timestamp, *sqrt_price_x64, cumulative);
+            previous = Some(*block_timestamp);
+        }
+        slots
+    }
+
+    fn twap(slots: &[(u32, u128, u128)], newest: usize, now: u32, window: u32) -> Option<u128> {
+        twap_price_x64(|i| slots[i], slots.len(), newest, now, window).unwrap()
+    }
+
+    #[test]
+    fn twap_averages_the_cumulative_delta() {
+        let one = 1u128 << 64;
+        // price 1.0 from 940, 4.0 credited over 940..1030, then 4.0 until now
+        let slots = observations(&[(940, one), (1030, 2 * one)], 3);
+        assert_eq!(twap(&slots, 1, 1060, 90), Some(4 * one));
+        assert_eq!(twap(&slots, 1, 1060, 120), Some(4 * one));
+        // the window starts after the newest observation
+        assert_eq!(twap(&slots, 1, 1060, 20), Some(4 * one));
+        // 1.0 over 900..1000, 4.0 credited over 1000..1100 and held until now
+        let slots = observations(&[(900, one), (1000, one), (1100, 2 * one)], 4);
+        assert_eq!(twap(&slots, 2, 1100, 100), Some(4 * one));
+        assert_eq!(twap(&slots, 2, 1200, 300), Some(3 * one));
+        // unchanged price since the only observation
+        assert_eq!(twap(&observations(&[(100, one)], 2), 0, 1000, 300), Some(one));
+    }
+
+    #[test]
+    fn twap_walks_the_ring_buffer_back() {
+        let one = 1u128 << 64;
+        // 5 observations in 3 slots, the newest in slot 1 and the oldest kept in slot 2
+        let history = [(100, one), (200, one), (300, one), (400, 2 * one), (500, 2 * one)];
+        let slots = observations(&history, 3);
+        assert_eq!(twap(&slots, 1, 500, 100), Some(4 * one));
+        assert_eq!(twap(&slots, 1, 500, 200), Some(4 * one));
+        // the overwritten 200 is out of reach
+        assert_eq!(twap(&slots, 1, 500, 250), None);
+    }
+
+    #[test]
+    fn twap_needs_observations_covering_the_window() {
+        let one = 1u128 << 64;
+        assert_eq!(twap(&observations(&[(950, one)], 2), 0, 1000, 60), None);
+        assert_eq!(twap(&[(0, 0, 0)], 0, 1000, 60), None);
+        assert_eq!(twap(&observations(&[(900, one)], 2), 0, 1000, 0), None);
+        assert_eq!(twap(&observations(&[(900, one)], 2), 2, 1000, 60), None);
+    }
+
+    #[test]
+    fn quote_by_price_follows_direction() {
+        let four = 4u128 << 64;
+        assert_eq!(quote_by_price_x64(1_000, 1 << 64, true).unwrap(), 1_000);
+        assert_eq!(quote_by_price_x64(1_000, 1 << 64, false).unwrap(), 1_000);
+        assert_eq!(quote_by_price_x64(1_000, four, true).unwrap(), 4_000);
+        assert_eq!(quote_by_price_x64(1_000, four, false).unwrap(), 250);
+        let err = quote_by_price_x64(1_000, 0, true).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::PriceUnavailable));
+    }
+
+    #[test]
+    fn empty_treasury_mints_virtual_shares_per_asset() {
+        assert_eq!(convert_to_shares(1_000, 0, 0).unwrap(), 1_000 * VIRTUAL_SHARES);
+        let shares = 1_000 * VIRTUAL_SHARES;
+        assert_eq!(convert_to_assets(shares, 1_000, shares).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn donation_to_an_empty_treasury_is_unprofitable() {
+        // the attacker stakes 1 and donates before the victim stakes
+        let donation = 1_000_000_000_000;
+        let attacker_shares = convert_to_shares(1, 0, 0).unwrap();
+        let total_assets = 1 + donation;
+        let victim_shares = convert_to_shares(donation, total_assets, attacker_shares).unwrap();
+        assert!(victim_shares > 0);
+
+        let total_assets = total_assets + donation;
+        let total_shares = attacker_shares + victim_shares;
+        let attacker_assets =
+            convert_to_assets(attacker_shares, total_assets, total_shares).unwrap();
+        assert!(attacker_assets < 1 + donation);
+    }
+
+    #[test]
+    fn oracle_price_scales_decimals() {
+        // 0.0025 wsol (9 decimals) per token (6 decimals)
+        let price_x64 = oracle_price_x64(250_000, -8, 6, 9).unwrap();
+        assert_eq!(quote_by_price_x64(1_000_000, price_x64, true).unwrap(), 2_500_000);
+        // 2.5 wsol per token (9 decimals)
+        let price_x64 = oracle_price_x64(25, -1, 9, 9).unwrap();
+        assert_eq!(quote_by_price_x64(3, price_x64, true).unwrap(), 7);
+        assert_eq!(quote_by_price_x64(5, price_x64, false).unwrap(), 2);
+        let err = oracle_price_x64(-1, 0, 9, 9).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::PriceUnavailable));
+    }
+
+    #[test]
+    fn invert_price_swaps_direction() {
+        let four = 4u128 << 64;
+        assert_eq!(invert_price_x64(four).unwrap(), 1 << 62);
+        assert_eq!(invert_price_x64(1 << 64).unwrap(), 1 << 64);
+        let err = invert_price_x64(1).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::MathOverflow));
+        let err = invert_price_x64(0).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::PriceUnavailable));
+    }
+
+    #[test]
+    fn sqrt_price_rounds_down() {
+        assert_eq!(sqrt_price_x64(4 << 64), 2 << 64);
+        assert_eq!(sqrt_price_x64(1 << 64), 1 << 64);
+        assert_eq!(sqrt_price_x64(1 << 62), 1 << 63);
+        assert_eq!(sqrt_price_x64(3), 7_439_101_573);
+    }
+
+    #[test]
+    fn bounded_deposit_leaves_room_for_the_slippage() {
+        let one = 1u128 << 64;
+        // in range, the maxes are the expected amounts
+        let (liquidity, amount_0_max, amount_1_max) =
+            bounded_deposit(one, -600, 600, 1_000_000, 1_000_000, 0).unwrap();
+        assert!(liquidity > 0);
+        assert!(amount_0_max > 990_000 && amount_0_max <= 1_000_000);
+        assert!(amount_1_max > 990_000 && amount_1_max <= 1_000_000);
+
+        // less liquidity for the same balances, the maxes are 1% above what it takes
+        let (bounded_liquidity, bounded_0_max, bounded_1_max) =
+            bounded_deposit(one, -600, 600, 1_000_000, 1_000_000, 100).unwrap();
+        assert!(bounded_liquidity < liquidity);
+        let (expected_0, expected_1) = liquidity_math::get_delta_amounts_signed(
+            0,
+            one,
+            -600,
+            600,
+            bounded_liquidity as i128,
+        )
+        .unwrap();
+        assert_eq!(bounded_0_max, expected_0 * 10_100 / 10_000);
+        assert_eq!(bounded_1_max, expected_1 * 10_100 / 10_000);
+        assert!(bounded_0_max <= 1_000_000 && bounded_1_max <= 1_000_000);
+
+        // below the range only token_0 is deposited, token_1 is capped at the minimum
+        let (_, amount_0_max, amount_1_max) =
+            bounded_deposit(one, 600, 1200, 1_000_000, 1_000_000, 100).unwrap();
+        assert!(amount_0_max > 0);
+        assert_eq!(amount_1_max, 1);
+
+        let err = bounded_deposit(one, -600, 600, 1, 1, 10_001).unwrap_err();
+        assert_eq!(error_code(err), u32::from(XError::InvalidSlippageConfig));
+    }
+
+    #[test]
+    fn apply_slippage_rounds_down() {
+        assert_eq!(apply_slippage(10_000, 100).unwrap(), 9_900);
+        assert_eq!(apply_slippage(999, 100).unwrap(), 989);
+        assert_eq!(apply_slippage(u64::MAX, 0).unwrap(), u64::MAX);
+        assert_eq!(apply_slippage(u64::MAX, 10_000).unwrap(), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn fee_never_exceeds_amount(amount in any::<u64>(), fee_percent in 0u32..=10_000) {
+            let fee = compute_fee(amount, fee_percent).unwrap();
+            prop_assert!(fee <= amount);
+            prop_assert_eq!(
+                fee as u128,
+                amount as u128 * fee_percent as u128 / FEE_PERCENT_DENOMINATOR as u128
+            );
+        }
+
+        #[test]
+        fn split_sums_to_total(
+            total in any::<u64>(),
+            weights in prop::collection::vec(any::<u16>(), 0..=MAX_FEE_RECIPIENTS),
+        ) {
+            let weights = weights.into_iter().map(|w| w as u64).collect::<Vec<_>>();
+            let buckets = split_by_weights(total, &weights).unwrap();
+            let total_weight: u64 = weights.iter().sum();
+            let sum: u128 = buckets.iter().map(|b| *b as u128).sum();
+            if total_weight == 0 {
+                prop_assert_eq!(sum, 0);
+            } else {
+                prop_assert_eq!(sum, total as u128);
+            }
+            for (bucket, weight) in buckets.iter().zip(weights.iter()) {
+                let floor = total as u128 * *weight as u128 / total_weight.max(1) as u128;
+                prop_assert!(*bucket as u128 >= floor);
+                if *weight == 0 {
+                    prop_assert_eq!(*bucket, 0);
+                }
+            }
+        }
+
+        #[test]
+        fn fees_sum_to_total_fee(
+            amount in any::<u64>(),
+            fee_percent in 0u32..=2_000,
+            bps in prop::collection::vec(1u16..=10_000, 1..=MAX_FEE_RECIPIENTS),
+        ) {
+            let total_fee = compute_fee(amount, fee_percent).unwrap();
+            let fees = split_fee(total_fee, &fee_recipients(&bps)).unwrap();
+            prop_assert_eq!(fees.iter().map(|f| *f as u128).sum::<u128>(), total_fee as u128);
+        }
+    }
+}
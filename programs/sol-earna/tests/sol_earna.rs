@@ -0,0 +1,1885 @@
+//! Integration tests, run natively against the program entrypoints with `cargo test`.
+//!
+//! Token-2022, spl-token and the associated token program are loaded by
+//! solana-program-test, the raydium clmm is replaced by programs/mock-clmm at its
+//! program id.
+
+use anchor_lang::{
+    AccountDeserialize, AnchorSerialize, Discriminator, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    token::spl_token,
+    token_2022::spl_token_2022::{
+        self,
+        extension::{transfer_hook, ExtensionType, StateWithExtensions},
+    },
+};
+use mock_clmm::MockObservation;
+use raydium_amm_v3::states::{ObservationState, PersonalPositionState, POSITION_SEED};
+use sol_earna::{
+    constants::*,
+    errors::XError,
+    states::{
+        FeeAccrual, FeeConfig, FeeRecipient, LiquidityPosition, StakerInfo, TransferLimit,
+        Treasury, TreasuryV1,
+    },
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    account::Account,
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    system_instruction, system_program, sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id, instruction::create_associated_token_account,
+};
+use spl_transfer_hook_interface::instruction::TransferHookInstruction;
+
+const DECIMALS: u8 = 9;
+const MINT_AMOUNT: u64 = 10_000_000_000_000; // 10000 tokens
+
+const FEE_PERCENT: u16 = 1000; // 10%
+// (label, bps) of the fee recipients
+const FEE_SPLIT: [(&str, u16); 3] = [("liquidity", 1000), ("marketing", 4000), ("holders", 5000)];
+
+struct TreasuryAccounts {
+    treasury: Pubkey,
+    treasury_mint: Pubkey,
+    wrapper_mint: Pubkey,
+    treasury_token_account: Pubkey,
+    reward_authority: Pubkey,
+    reward_vault: Pubkey,
+}
+
+// anchor's entrypoint borrows the accounts for 'info, the native processor only per call
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(accounts.to_vec().into_boxed_slice());
+    sol_earna::entry(program_id, accounts, data)
+}
+
+fn process_mock_clmm_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let accounts = Box::leak(accounts.to_vec().into_boxed_slice());
+    mock_clmm::entry(program_id, accounts, data)
+}
+
+fn program_test() -> ProgramTest {
+    let mut program_test =
+        ProgramTest::new("sol_earna", sol_earna::ID, processor!(process_instruction));
+    program_test.add_program(
+        "mock_clmm",
+        mock_clmm::ID,
+        processor!(process_mock_clmm_instruction),
+    );
+
+    // wsol mint of the fee config and the reward vault
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint {
+        mint_authority: COption::None,
+        supply: 0,
+        decimals: 9,
+        is_initialized: true,
+        freeze_authority: COption::None,
+    }
+    .pack_into_slice(&mut data);
+    program_test.add_account(
+        spl_token::native_mint::ID,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: spl_token::ID,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    program_test
+}
+
+fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &sol_earna::ID).0
+}
+
+async fn process(
+    context: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = context.get_new_latest_blockhash().await?;
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&context.payer.pubkey()),
+        &all_signers,
+        blockhash,
+    );
+    context.banks_client.process_transaction(transaction).await
+}
+
+// custom error code a transaction failed with
+fn error_code(result: Result<(), BanksClientError>) -> u32 {
+    match result.unwrap_err().unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => code,
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+async fn warp_to(context: &mut ProgramTestContext, unix_timestamp: i64) {
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp = unix_timestamp;
+    context.set_sysvar(&clock);
+}
+
+async fn fetch<T: AccountDeserialize>(context: &mut ProgramTestContext, address: Pubkey) -> T {
+    let account = context
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .expect("account not found");
+    T::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+// works for spl-token and token-2022 accounts
+async fn token_balance(context: &mut ProgramTestContext, address: Pubkey) -> u64 {
+    let account = context
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .expect("token account not found");
+    StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+        .unwrap()
+        .base
+        .amount
+}
+
+async fn mint_supply(context: &mut ProgramTestContext, address: Pubkey) -> u64 {
+    let account = context
+        .banks_client
+        .get_account(address)
+        .await
+        .unwrap()
+        .expect("mint not found");
+    StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&account.data)
+        .unwrap()
+        .base
+        .supply
+}
+
+async fn create_user(context: &mut ProgramTestContext) -> Keypair {
+    let user = Keypair::new();
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[system_instruction::transfer(&payer, &user.pubkey(), 1_000_000_000)],
+        &[],
+    )
+    .await
+    .unwrap();
+    user
+}
+
+// token-2022 mint, optionally with the transfer hook of this program
+async fn create_mint(context: &mut ProgramTestContext, with_transfer_hook: bool) -> Pubkey {
+    let mint = Keypair::new();
+    let payer = context.payer.pubkey();
+    let extensions: &[ExtensionType] = if with_transfer_hook {
+        &[ExtensionType::TransferHook]
+    } else {
+        &[]
+    };
+    let space =
+        ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions)
+            .unwrap();
+    let rent = context.banks_client.get_rent().await.unwrap();
+
+    let mut instructions = vec![system_instruction::create_account(
+        &payer,
+        &mint.pubkey(),
+        rent.minimum_balance(space),
+        space as u64,
+        &spl_token_2022::ID,
+    )];
+    if with_transfer_hook {
+        instructions.push(
+            transfer_hook::instruction::initialize(
+                &spl_token_2022::ID,
+                &mint.pubkey(),
+                Some(payer),
+                Some(sol_earna::ID),
+            )
+            .unwrap(),
+        );
+    }
+    instructions.push(
+        spl_token_2022::instruction::initialize_mint2(
+            &spl_token_2022::ID,
+            &mint.pubkey(),
+            &payer,
+            None,
+            DECIMALS,
+        )
+        .unwrap(),
+    );
+    process(context, &instructions, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+async fn create_token_account(
+    context: &mut ProgramTestContext,
+    owner: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+) -> Pubkey {
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[create_associated_token_account(&payer, owner, mint, token_program)],
+        &[],
+    )
+    .await
+    .unwrap();
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
+
+async fn mint_to(context: &mut ProgramTestContext, mint: &Pubkey, to: &Pubkey, amount: u64) {
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[spl_token_2022::instruction::mint_to(
+            &spl_token_2022::ID,
+            mint,
+            to,
+            &payer,
+            &[],
+            amount,
+        )
+        .unwrap()],
+        &[],
+    )
+    .await
+    .unwrap();
+}
+
+async fn create_treasury(context: &mut ProgramTestContext, treasury_mint: Pubkey) -> TreasuryAccounts {
+    let payer = context.payer.pubkey();
+    let wrapper_mint = Keypair::new();
+    let treasury = pda(&[TREASURY_TAG, treasury_mint.as_ref()]);
+    let treasury_token_account =
+        create_token_account(context, &treasury, &treasury_mint, &spl_token_2022::ID).await;
+
+    process(
+        context,
+        &[Instruction {
+            program_id: sol_earna::ID,
+            accounts: sol_earna::accounts::CreateTreasury {
+                treasury,
+                treasury_mint,
+                wrapper_mint: wrapper_mint.pubkey(),
+                treasury_token_account,
+                authority: payer,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+            }
+            .to_account_metas(None),
+            data: sol_earna::instruction::CreateTreasury {}.data(),
+        }],
+        &[&wrapper_mint],
+    )
+    .await
+    .unwrap();
+
+    // wsol vault of the holders rewards
+    let reward_authority = pda(&[REWARD_AUTHORITY_TAG, treasury.as_ref()]);
+    let reward_vault = create_token_account(
+        context,
+        &reward_authority,
+        &spl_token::native_mint::ID,
+        &spl_token::ID,
+    )
+    .await;
+
+    TreasuryAccounts {
+        treasury,
+        treasury_mint,
+        wrapper_mint: wrapper_mint.pubkey(),
+        treasury_token_account,
+        reward_authority,
+        reward_vault,
+    }
+}
+
+fn stake_instruction(t: &TreasuryAccounts, user: &Pubkey, amount: u64, lock_option: u8) -> Instruction {
+    Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::Stake {
+            treasury: t.treasury,
+            treasury_mint: t.treasury_mint,
+            treasury_token_account: t.treasury_token_account,
+            user_token_account: get_associated_token_address_with_program_id(
+                user,
+                &t.treasury_mint,
+                &spl_token_2022::ID,
+            ),
+            staker_info: pda(&[STAKER_INFO_TAG, t.treasury.as_ref(), user.as_ref()]),
+            reward_authority: t.reward_authority,
+            reward_vault: t.reward_vault,
+            wsol_mint: spl_token::native_mint::ID,
+            user: *user,
+            system_program: system_program::ID,
+            token_program_treasury: spl_token_2022::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            instructions: sysvar::instructions::ID,
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::Stake {
+            amount,
+            lock_option,
+            min_shares: 0,
+        }
+        .data(),
+    }
+}
+
+fn redeem_instruction(t: &TreasuryAccounts, user: &Pubkey, shares: u64) -> Instruction {
+    Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::Redeem {
+            treasury: t.treasury,
+            treasury_mint: t.treasury_mint,
+            treasury_token_account: t.treasury_token_account,
+            user_token_account: get_associated_token_address_with_program_id(
+                user,
+                &t.treasury_mint,
+                &spl_token_2022::ID,
+            ),
+            staker_info: pda(&[STAKER_INFO_TAG, t.treasury.as_ref(), user.as_ref()]),
+            reward_authority: t.reward_authority,
+            reward_vault: t.reward_vault,
+            wsol_mint: spl_token::native_mint::ID,
+            user: *user,
+            system_program: system_program::ID,
+            token_program_treasury: spl_token_2022::ID,
+            instructions: sysvar::instructions::ID,
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::Redeem { shares }.data(),
+    }
+}
+
+fn set_early_redeem_penalty_instruction(
+    t: &TreasuryAccounts,
+    authority: &Pubkey,
+    penalty_bps: u16,
+) -> Instruction {
+    Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::SetEarlyRedeemPenalty {
+            authority: *authority,
+            treasury: t.treasury,
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::SetEarlyRedeemPenalty { penalty_bps }.data(),
+    }
+}
+
+fn expire_lock_instruction(t: &TreasuryAccounts, user: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::ExpireLock {
+            treasury: t.treasury,
+            staker_info: pda(&[STAKER_INFO_TAG, t.treasury.as_ref(), user.as_ref()]),
+            reward_authority: t.reward_authority,
+            reward_vault: t.reward_vault,
+            wsol_mint: spl_token::native_mint::ID,
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::ExpireLock {}.data(),
+    }
+}
+
+fn set_redeem_cooldown_instruction(
+    t: &TreasuryAccounts,
+    authority: &Pubkey,
+    redeem_cooldown: i64,
+) -> Instruction {
+    Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::SetRedeemCooldown {
+            authority: *authority,
+            treasury: t.treasury,
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::SetRedeemCooldown { redeem_cooldown }.data(),
+    }
+}
+
+fn set_treasury_pause_instruction(
+    t: &TreasuryAccounts,
+    signer: &Pubkey,
+    stake_paused: bool,
+    redeem_paused: bool,
+) -> Instruction {
+    Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::SetTreasuryPause {
+            signer: *signer,
+            treasury: t.treasury,
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::SetTreasuryPause {
+            stake_paused,
+            redeem_paused,
+        }
+        .data(),
+    }
+}
+
+fn withdrawal_ticket(t: &TreasuryAccounts, user: &Pubkey) -> Pubkey {
+    pda(&[WITHDRAWAL_TICKET_TAG, t.treasury.as_ref(), user.as_ref()])
+}
+
+fn request_redeem_instruction(t: &TreasuryAccounts, user: &Pubkey, shares: u64) -> Instruction {
+    Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::RequestRedeem {
+            treasury: t.treasury,
+            staker_info: pda(&[STAKER_INFO_TAG, t.treasury.as_ref(), user.as_ref()]),
+            withdrawal_ticket: withdrawal_ticket(t, user),
+            reward_authority: t.reward_authority,
+            reward_vault: t.reward_vault,
+            wsol_mint: spl_token::native_mint::ID,
+            user: *user,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::RequestRedeem { shares }.data(),
+    }
+}
+
+fn complete_redeem_instruction(t: &TreasuryAccounts, user: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::CompleteRedeem {
+            treasury: t.treasury,
+            treasury_mint: t.treasury_mint,
+            treasury_token_account: t.treasury_token_account,
+            user_token_account: get_associated_token_address_with_program_id(
+                user,
+                &t.treasury_mint,
+                &spl_token_2022::ID,
+            ),
+            withdrawal_ticket: withdrawal_ticket(t, user),
+            user: *user,
+            token_program_treasury: spl_token_2022::ID,
+            instructions: sysvar::instructions::ID,
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::CompleteRedeem {}.data(),
+    }
+}
+
+// token-2022 transfer_checked of the hooked mint, with the extra accounts resolved from the
+// program's meta list
+async fn hooked_transfer_checked(
+    context: &mut ProgramTestContext,
+    mint: Pubkey,
+    source: Pubkey,
+    destination: Pubkey,
+    authority: Pubkey,
+    amount: u64,
+) -> Instruction {
+    let extra_account_meta_list = pda(&[EXTRA_ACCOUNT_METAS_TAG, mint.as_ref()]);
+    let mut accounts = vec![];
+    for (address, has_data) in [
+        (source, true),
+        (mint, false),
+        (destination, true),
+        (authority, false),
+        (extra_account_meta_list, false),
+    ] {
+        let data = match has_data {
+            true => Some(context.banks_client.get_account(address).await.unwrap().unwrap().data),
+            false => None,
+        };
+        accounts.push((address, data));
+    }
+    let instruction_data = TransferHookInstruction::Execute { amount }.pack();
+    let mut metas = vec![];
+    let fee_config = pda(&[FEE_CONFIG_TAG, mint.as_ref()]);
+    for extra_meta in sol_earna::extra_account_metas(&fee_config).unwrap() {
+        let meta = extra_meta
+            .resolve(&instruction_data, &sol_earna::ID, |index| {
+                accounts
+                    .get(index)
+                    .map(|(address, data)| (address, data.as_deref()))
+            })
+            .unwrap();
+        accounts.push((meta.pubkey, None));
+        metas.push(meta);
+    }
+    metas.push(AccountMeta::new_readonly(sol_earna::ID, false));
+    metas.push(AccountMeta::new_readonly(extra_account_meta_list, false));
+
+    let mut instruction = spl_token_2022::instruction::transfer_checked(
+        &spl_token_2022::ID,
+        &source,
+        &mint,
+        &destination,
+        &authority,
+        &[],
+        amount,
+        DECIMALS,
+    )
+    .unwrap();
+    instruction.accounts.extend(metas);
+    instruction
+}
+
+const Q64: u128 = 1 << 64;
+
+// mocked raydium clmm pool of wrapper_mint (token_0) and wsol (token_1)
+struct MockPool {
+    pool_state: Pubkey,
+    observation_state: Pubkey,
+    wrapper_vault: Pubkey,
+    wsol_vault: Pubkey,
+}
+
+// writes an spl-token account, a wsol one holds its amount in lamports
+async fn set_token_account(
+    context: &mut ProgramTestContext,
+    address: Pubkey,
+    mint: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) {
+    let rent = context
+        .banks_client
+        .get_rent()
+        .await
+        .unwrap()
+        .minimum_balance(spl_token::state::Account::LEN);
+    let is_native = mint == spl_token::native_mint::ID;
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    spl_token::state::Account {
+        mint,
+        owner,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        is_native: if is_native { COption::Some(rent) } else { COption::None },
+        ..Default::default()
+    }
+    .pack_into_slice(&mut data);
+    context.set_account(
+        &address,
+        &Account {
+            lamports: if is_native { rent + amount } else { rent },
+            data,
+            owner: spl_token::ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+        .into(),
+    );
+}
+
+async fn create_mock_pool(context: &mut ProgramTestContext, wrapper_mint: &Pubkey) -> MockPool {
+    let pool_state = Pubkey::find_program_address(&[mock_clmm::POOL_STATE_TAG], &mock_clmm::ID).0;
+    let observation_state = Pubkey::new_unique();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    context.set_account(
+        &observation_state,
+        &Account {
+            lamports: rent.minimum_balance(ObservationState::LEN),
+            data: vec![0; ObservationState::LEN],
+            owner: mock_clmm::ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+        .into(),
+    );
+    let pool = MockPool {
+        pool_state,
+        observation_state,
+        wrapper_vault: Pubkey::new_unique(),
+        wsol_vault: Pubkey::new_unique(),
+    };
+    let liquidity = 1_000 * 10u64.pow(DECIMALS as u32);
+    set_token_account(context, pool.wrapper_vault, *wrapper_mint, pool_state, liquidity).await;
+    set_token_account(context, pool.wsol_vault, spl_token::native_mint::ID, pool_state, liquidity)
+        .await;
+
+    set_observations(context, &pool, wrapper_mint, &[(600, Q64)]).await;
+    process(
+        context,
+        &[Instruction {
+            program_id: mock_clmm::ID,
+            accounts: mock_clmm::accounts::SetTokenVaults {
+                pool_state,
+                token_vault_0: pool.wrapper_vault,
+                token_vault_1: pool.wsol_vault,
+            }
+            .to_account_metas(None),
+            data: mock_clmm::instruction::SetTokenVaults {}.data(),
+        }],
+        &[],
+    )
+    .await
+    .unwrap();
+    pool
+}
+
+// (seconds_ago, sqrt_price_x64) of the pool, oldest first
+async fn set_observations(
+    context: &mut ProgramTestContext,
+    pool: &MockPool,
+    wrapper_mint: &Pubkey,
+    observations: &[(u32, u128)],
+) {
+    let payer = context.payer.pubkey();
+    process(
+        context,
+        &[Instruction {
+            program_id: mock_clmm::ID,
+            accounts: mock_clmm::accounts::SetObservations {
+                payer,
+                pool_state: pool.pool_state,
+                observation_state: pool.observation_state,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: mock_clmm::instruction::SetObservations {
+                token_mint_0: *wrapper_mint,
+                observations: observations
+                    .iter()
+                    .map(|&(seconds_ago, sqrt_price_x64)| MockObservation {
+                        seconds_ago,
+                        sqrt_price_x64,
+                    })
+                    .collect(),
+            }
+            .data(),
+        }],
+        &[],
+    )
+    .await
+    .unwrap();
+}
+
+// fee config of the hooked `mint` trading in `pool_state`, the payer is the authority
+async fn create_fee_config(
+    context: &mut ProgramTestContext,
+    t: &TreasuryAccounts,
+    pool_state: Pubkey,
+) -> Pubkey {
+    let payer = context.payer.pubkey();
+    let mint = t.treasury_mint;
+    let fee_config = pda(&[FEE_CONFIG_TAG, mint.as_ref()]);
+    process(
+        context,
+        &[Instruction {
+            program_id: sol_earna::ID,
+            accounts: sol_earna::accounts::InitializeExtraAccountMetaList {
+                payer,
+                extra_account_meta_list: pda(&[EXTRA_ACCOUNT_METAS_TAG, mint.as_ref()]),
+                mint,
+                token_program: spl_token_2022::ID,
+                token_program_org: spl_token::ID,
+                associated_token_program: spl_associated_token_account::ID,
+                system_program: system_program::ID,
+                fee_config,
+                fee_accrual: pda(&[FEE_ACCRUAL_TAG, mint.as_ref()]),
+                treasury: t.treasury,
+                wsol_mint: spl_token::native_mint::ID,
+                wrapper_mint: t.wrapper_mint,
+                pool_state,
+            }
+            .to_account_metas(None),
+            data: sol_earna::instruction::InitializeExtraAccountMetaList {
+                fee_percent: FEE_PERCENT,
+                fee_recipients: FEE_SPLIT
+                    .iter()
+                    .map(|(label, bps)| FeeRecipient {
+                        recipient: Pubkey::new_unique(),
+                        bps: *bps,
+                        label: label.to_string(),
+                    })
+                    .collect(),
+                accrual_threshold: 0,
+            }
+            .data(),
+        }],
+        &[],
+    )
+    .await
+    .unwrap();
+    fee_config
+}
+
+#[tokio::test]
+async fn create_treasury_sets_mints() {
+    let mut context = program_test().start_with_context().await;
+    let treasury_mint = create_mint(&mut context, false).await;
+    let t = create_treasury(&mut context, treasury_mint).await;
+
+    let treasury: Treasury = fetch(&mut context, t.treasury).await;
+    assert_eq!(treasury.version, TREASURY_VERSION);
+    assert_eq!(treasury.authority, context.payer.pubkey());
+    assert_eq!(treasury.treasury_mint, treasury_mint);
+    assert_eq!(treasury.wrapper_mint, t.wrapper_mint);
+    assert_eq!(treasury.treasury_token_account, t.treasury_token_account);
+}
+
+#[tokio::test]
+async fn migrate_treasury_upgrades_v1_layout() {
+    let mut context = program_test().start_with_context().await;
+    let payer = context.payer.pubkey();
+    let treasury_mint = create_mint(&mut context, false).await;
+    let treasury = pda(&[TREASURY_TAG, treasury_mint.as_ref()]);
+
+    // a treasury as the unversioned program created it
+    let treasury_v1 = TreasuryV1 {
+        authority: payer,
+        treasury_mint,
+        wrapper_mint: Pubkey::new_unique(),
+        treasury_token_account: Pubkey::new_unique(),
+    };
+    let mut data = Treasury::DISCRIMINATOR.to_vec();
+    treasury_v1.serialize(&mut data).unwrap();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    context.set_account(
+        &treasury,
+        &Account {
+            lamports: rent.minimum_balance(data.len()),
+            data,
+            owner: sol_earna::ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+        .into(),
+    );
+
+    let instruction = Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::MigrateTreasury {
+            authority: payer,
+            treasury_mint,
+            treasury,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::MigrateTreasury {}.data(),
+    };
+    process(&mut context, std::slice::from_ref(&instruction), &[]).await.unwrap();
+
+    let account = context.banks_client.get_account(treasury).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), Treasury::SPACE);
+    assert!(account.lamports >= rent.minimum_balance(Treasury::SPACE));
+
+    let migrated: Treasury = fetch(&mut context, treasury).await;
+    assert_eq!(migrated.version, TREASURY_VERSION);
+    assert_eq!(migrated.authority, treasury_v1.authority);
+    assert_eq!(migrated.wrapper_mint, treasury_v1.wrapper_mint);
+    assert_eq!(migrated.treasury_token_account, treasury_v1.treasury_token_account);
+    assert_eq!(migrated.total_staked, 0);
+
+    // the account is no longer v1
+    let result = process(&mut context, &[instruction], &[]).await;
+    assert_eq!(error_code(result), u32::from(XError::InvalidAccountVersion));
+}
+
+#[tokio::test]
+async fn stake_and_redeem() {
+    let mut context = program_test().start_with_context().await;
+    let treasury_mint = create_mint(&mut context, false).await;
+    let t = create_treasury(&mut context, treasury_mint).await;
+
+    let user = create_user(&mut context).await;
+    let user_token_account =
+        create_token_account(&mut context, &user.pubkey(), &treasury_mint, &spl_token_2022::ID)
+            .await;
+    mint_to(&mut context, &treasury_mint, &user_token_account, MINT_AMOUNT).await;
+
+    // empty treasury, VIRTUAL_SHARES shares per token
+    let stake_amount = 100_000_000_000;
+    let shares = stake_amount * VIRTUAL_SHARES;
+
+    // fewer shares than requested
+    let instruction = Instruction {
+        data: sol_earna::instruction::Stake {
+            amount: stake_amount,
+            lock_option: 0,
+            min_shares: shares + 1,
+        }
+        .data(),
+        ..stake_instruction(&t, &user.pubkey(), stake_amount, 0)
+    };
+    let result = process(&mut context, &[instruction], &[&user]).await;
+    assert_eq!(error_code(result), u32::from(XError::SharesBelowMinimum));
+
+    let instruction = Instruction {
+        data: sol_earna::instruction::Stake {
+            amount: stake_amount,
+            lock_option: 0,
+            min_shares: shares,
+        }
+        .data(),
+        ..stake_instruction(&t, &user.pubkey(), stake_amount, 0)
+    };
+    process(&mut context, &[instruction], &[&user]).await.unwrap();
+    assert_eq!(token_balance(&mut context, t.treasury_token_account).await, stake_amount);
+    // shares are only the treasury ledger, wrapper_mint is left to the fees
+    assert_eq!(mint_supply(&mut context, t.wrapper_mint).await, 0);
+
+    let staker_info: StakerInfo = fetch(
+        &mut context,
+        pda(&[STAKER_INFO_TAG, t.treasury.as_ref(), user.pubkey().as_ref()]),
+    )
+    .await;
+    assert_eq!(staker_info.amount, shares);
+    let treasury: Treasury = fetch(&mut context, t.treasury).await;
+    assert_eq!(treasury.total_staked, shares);
+
+    let redeem_shares = shares / 10;
+    process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), redeem_shares)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    let redeem_amount = stake_amount / 10;
+    assert_eq!(
+        token_balance(&mut context, t.treasury_token_account).await,
+        stake_amount - redeem_amount
+    );
+    assert_eq!(
+        token_balance(&mut context, user_token_account).await,
+        MINT_AMOUNT - stake_amount + redeem_amount
+    );
+    let treasury: Treasury = fetch(&mut context, t.treasury).await;
+    assert_eq!(treasury.total_staked, shares - redeem_shares);
+
+    // more than the stake
+    let result = process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), shares)],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::InsufficientStake));
+
+    // tokens arriving in the treasury raise the value of every share
+    let backing = stake_amount - redeem_amount;
+    mint_to(&mut context, &treasury_mint, &t.treasury_token_account, backing).await;
+    let user_balance = token_balance(&mut context, user_token_account).await;
+    process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), redeem_shares)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    let redeemed = token_balance(&mut context, user_token_account).await - user_balance;
+    assert_eq!(redeemed, 2 * redeem_amount - 1);
+}
+
+#[tokio::test]
+async fn locked_shares_stay_with_the_staker() {
+    let mut context = program_test().start_with_context().await;
+    let treasury_mint = create_mint(&mut context, false).await;
+    let t = create_treasury(&mut context, treasury_mint).await;
+
+    let user = create_user(&mut context).await;
+    let user_token_account =
+        create_token_account(&mut context, &user.pubkey(), &treasury_mint, &spl_token_2022::ID)
+            .await;
+    mint_to(&mut context, &treasury_mint, &user_token_account, MINT_AMOUNT).await;
+
+    let stake_amount = 100_000_000_000;
+    process(
+        &mut context,
+        &[stake_instruction(&t, &user.pubkey(), stake_amount, 1)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+
+    // the shares are recorded on the staker info of the user, there is no token to move
+    let staker_info_address = pda(&[STAKER_INFO_TAG, t.treasury.as_ref(), user.pubkey().as_ref()]);
+    let staker_info: StakerInfo = fetch(&mut context, staker_info_address).await;
+    let shares = staker_info.amount;
+    assert_eq!(mint_supply(&mut context, t.wrapper_mint).await, 0);
+
+    let stranger = create_user(&mut context).await;
+    // another wallet has no stake to redeem
+    create_token_account(&mut context, &stranger.pubkey(), &treasury_mint, &spl_token_2022::ID)
+        .await;
+    let result = process(
+        &mut context,
+        &[redeem_instruction(&t, &stranger.pubkey(), shares)],
+        &[&stranger],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::InsufficientStake));
+
+    // and the owner is held to the lock
+    let result = process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), shares)],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::StakeLocked));
+    let staker_info: StakerInfo = fetch(&mut context, staker_info_address).await;
+    assert_eq!(staker_info.amount, shares);
+}
+
+#[tokio::test]
+async fn lock_options_boost_and_hold_the_stake() {
+    let mut context = program_test().start_with_context().await;
+    let payer = context.payer.pubkey();
+    let treasury_mint = create_mint(&mut context, false).await;
+    let t = create_treasury(&mut context, treasury_mint).await;
+
+    let user = create_user(&mut context).await;
+    let user_token_account =
+        create_token_account(&mut context, &user.pubkey(), &treasury_mint, &spl_token_2022::ID)
+            .await;
+    mint_to(&mut context, &treasury_mint, &user_token_account, MINT_AMOUNT).await;
+    let staker_info_address = pda(&[STAKER_INFO_TAG, t.treasury.as_ref(), user.pubkey().as_ref()]);
+
+    let stake_amount = 100_000_000_000;
+    let result = process(
+        &mut context,
+        &[stake_instruction(&t, &user.pubkey(), stake_amount, LOCK_OPTIONS.len() as u8)],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::InvalidLockOption));
+
+    let now = 1_700_000_000;
+    warp_to(&mut context, now).await;
+    process(
+        &mut context,
+        &[stake_instruction(&t, &user.pubkey(), stake_amount, 2)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    let (lock_duration, lock_multiplier_bps) = LOCK_OPTIONS[2];
+    let staker_info: StakerInfo = fetch(&mut context, staker_info_address).await;
+    let shares = staker_info.amount;
+    assert_eq!(staker_info.lock_end, now + lock_duration);
+    assert_eq!(staker_info.lock_multiplier_bps, lock_multiplier_bps);
+    assert_eq!(
+        staker_info.weight,
+        shares * lock_multiplier_bps as u64 / BASE_LOCK_MULTIPLIER_BPS as u64
+    );
+
+    // the boost can not be dropped and the stake not redeemed before lock_end
+    warp_to(&mut context, now + lock_duration - 1).await;
+    let result = process(&mut context, &[expire_lock_instruction(&t, &user.pubkey())], &[]).await;
+    assert_eq!(error_code(result), u32::from(XError::StakeLocked));
+    let result = process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), shares / 2)],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::StakeLocked));
+
+    // with a penalty the locked stake is redeemable, the penalty stays in the treasury
+    let penalty_bps = 1000;
+    process(
+        &mut context,
+        &[set_early_redeem_penalty_instruction(&t, &payer, penalty_bps)],
+        &[],
+    )
+    .await
+    .unwrap();
+    process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), shares / 2)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    let assets = stake_amount / 2;
+    let penalty = assets * penalty_bps as u64 / FEE_PERCENT_DENOMINATOR as u64;
+    assert_eq!(
+        token_balance(&mut context, user_token_account).await,
+        MINT_AMOUNT - stake_amount + assets - penalty
+    );
+    assert_eq!(
+        token_balance(&mut context, t.treasury_token_account).await,
+        stake_amount - assets + penalty
+    );
+
+    // once the lock is over anyone can drop the boost
+    warp_to(&mut context, now + lock_duration).await;
+    process(&mut context, &[expire_lock_instruction(&t, &user.pubkey())], &[]).await.unwrap();
+    let staker_info: StakerInfo = fetch(&mut context, staker_info_address).await;
+    assert_eq!(staker_info.lock_end, 0);
+    assert_eq!(staker_info.lock_multiplier_bps, BASE_LOCK_MULTIPLIER_BPS);
+    assert_eq!(staker_info.weight, staker_info.amount);
+    let treasury: Treasury = fetch(&mut context, t.treasury).await;
+    assert_eq!(treasury.total_weight, staker_info.amount);
+
+    // and the rest is redeemed without a penalty, including the penalty of the first redeem
+    let balance = token_balance(&mut context, user_token_account).await;
+    process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), staker_info.amount)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    let redeemed = token_balance(&mut context, user_token_account).await - balance;
+    assert!(redeemed > stake_amount - assets);
+    assert!(redeemed <= stake_amount - assets + penalty);
+}
+
+#[tokio::test]
+async fn redeem_waits_for_the_cooldown() {
+    let mut context = program_test().start_with_context().await;
+    let payer = context.payer.pubkey();
+    let treasury_mint = create_mint(&mut context, false).await;
+    let t = create_treasury(&mut context, treasury_mint).await;
+
+    let user = create_user(&mut context).await;
+    let user_token_account =
+        create_token_account(&mut context, &user.pubkey(), &treasury_mint, &spl_token_2022::ID)
+            .await;
+    mint_to(&mut context, &treasury_mint, &user_token_account, MINT_AMOUNT).await;
+
+    let stake_amount = 100_000_000_000;
+    let shares = stake_amount * VIRTUAL_SHARES;
+    process(
+        &mut context,
+        &[stake_instruction(&t, &user.pubkey(), stake_amount, 0)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+
+    let redeem_cooldown = 7 * 24 * 60 * 60;
+    process(
+        &mut context,
+        &[set_redeem_cooldown_instruction(&t, &payer, redeem_cooldown)],
+        &[],
+    )
+    .await
+    .unwrap();
+
+    // an instant redeem is no longer possible
+    let result = process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), shares / 2)],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::RedeemCooldownActive));
+
+    let now = 1_700_000_000;
+    warp_to(&mut context, now).await;
+    process(
+        &mut context,
+        &[request_redeem_instruction(&t, &user.pubkey(), shares / 2)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    let treasury: Treasury = fetch(&mut context, t.treasury).await;
+    assert_eq!(treasury.total_staked, shares / 2);
+    assert_eq!(treasury.total_pending_redeem, shares / 2);
+
+    // not before claimable_at
+    warp_to(&mut context, now + redeem_cooldown - 1).await;
+    let result = process(
+        &mut context,
+        &[complete_redeem_instruction(&t, &user.pubkey())],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::CooldownNotElapsed));
+
+    // pays the value of the ticket shares at completion
+    warp_to(&mut context, now + redeem_cooldown).await;
+    process(
+        &mut context,
+        &[complete_redeem_instruction(&t, &user.pubkey())],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token_balance(&mut context, user_token_account).await,
+        MINT_AMOUNT - stake_amount / 2
+    );
+    let treasury: Treasury = fetch(&mut context, t.treasury).await;
+    assert_eq!(treasury.total_pending_redeem, 0);
+    let withdrawal_ticket = withdrawal_ticket(&t, &user.pubkey());
+    assert!(context.banks_client.get_account(withdrawal_ticket).await.unwrap().is_none());
+
+    // the ticket is closed, it can not be completed twice
+    let result = process(
+        &mut context,
+        &[complete_redeem_instruction(&t, &user.pubkey())],
+        &[&user],
+    )
+    .await;
+    assert_eq!(
+        error_code(result),
+        u32::from(anchor_lang::error::ErrorCode::AccountNotInitialized)
+    );
+    assert_eq!(
+        token_balance(&mut context, user_token_account).await,
+        MINT_AMOUNT - stake_amount / 2
+    );
+}
+
+#[tokio::test]
+async fn pause_blocks_stake_and_redeem() {
+    let mut context = program_test().start_with_context().await;
+    let payer = context.payer.pubkey();
+    let treasury_mint = create_mint(&mut context, false).await;
+    let t = create_treasury(&mut context, treasury_mint).await;
+
+    let user = create_user(&mut context).await;
+    let user_token_account =
+        create_token_account(&mut context, &user.pubkey(), &treasury_mint, &spl_token_2022::ID)
+            .await;
+    mint_to(&mut context, &treasury_mint, &user_token_account, MINT_AMOUNT).await;
+
+    let stake_amount = 100_000_000_000;
+    let shares = stake_amount * VIRTUAL_SHARES;
+    process(
+        &mut context,
+        &[stake_instruction(&t, &user.pubkey(), stake_amount, 0)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+
+    // the guardian may pause besides the authority, nobody else
+    let guardian = create_user(&mut context).await;
+    process(
+        &mut context,
+        &[Instruction {
+            program_id: sol_earna::ID,
+            accounts: sol_earna::accounts::SetTreasuryGuardian {
+                authority: payer,
+                treasury: t.treasury,
+            }
+            .to_account_metas(None),
+            data: sol_earna::instruction::SetTreasuryGuardian {
+                guardian: guardian.pubkey(),
+            }
+            .data(),
+        }],
+        &[],
+    )
+    .await
+    .unwrap();
+    let result = process(
+        &mut context,
+        &[set_treasury_pause_instruction(&t, &user.pubkey(), true, true)],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::NotAllowed));
+    process(
+        &mut context,
+        &[set_treasury_pause_instruction(&t, &guardian.pubkey(), true, true)],
+        &[&guardian],
+    )
+    .await
+    .unwrap();
+
+    let result = process(
+        &mut context,
+        &[stake_instruction(&t, &user.pubkey(), stake_amount, 0)],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::Paused));
+    let result = process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), shares)],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::Paused));
+
+    // both work again once the flags are cleared
+    process(
+        &mut context,
+        &[set_treasury_pause_instruction(&t, &payer, false, false)],
+        &[],
+    )
+    .await
+    .unwrap();
+    process(
+        &mut context,
+        &[stake_instruction(&t, &user.pubkey(), stake_amount, 0)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), shares)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    let treasury: Treasury = fetch(&mut context, t.treasury).await;
+    assert_eq!(treasury.total_staked, shares);
+    assert_eq!(
+        token_balance(&mut context, user_token_account).await,
+        MINT_AMOUNT - stake_amount
+    );
+}
+
+#[tokio::test]
+async fn weight_follows_the_staked_shares() {
+    let mut context = program_test().start_with_context().await;
+    let treasury_mint = create_mint(&mut context, false).await;
+    let t = create_treasury(&mut context, treasury_mint).await;
+
+    let mut stakers = vec![];
+    for lock_option in [0, 3] {
+        let user = create_user(&mut context).await;
+        let user_token_account = create_token_account(
+            &mut context,
+            &user.pubkey(),
+            &treasury_mint,
+            &spl_token_2022::ID,
+        )
+        .await;
+        mint_to(&mut context, &treasury_mint, &user_token_account, MINT_AMOUNT).await;
+        process(
+            &mut context,
+            &[stake_instruction(&t, &user.pubkey(), 100_000_000_000, lock_option)],
+            &[&user],
+        )
+        .await
+        .unwrap();
+        stakers.push((user, LOCK_OPTIONS[lock_option as usize].1));
+    }
+
+    let mut staked = 0;
+    let mut total_weight = 0;
+    for (user, lock_multiplier_bps) in &stakers {
+        let staker_info: StakerInfo = fetch(
+            &mut context,
+            pda(&[STAKER_INFO_TAG, t.treasury.as_ref(), user.pubkey().as_ref()]),
+        )
+        .await;
+        assert_eq!(
+            staker_info.weight,
+            staker_info.amount * *lock_multiplier_bps as u64 / BASE_LOCK_MULTIPLIER_BPS as u64
+        );
+        staked += staker_info.amount;
+        total_weight += staker_info.weight;
+    }
+    let treasury: Treasury = fetch(&mut context, t.treasury).await;
+    assert_eq!(treasury.total_staked, staked);
+    assert_eq!(treasury.total_weight, total_weight);
+
+    // a redeem drops the weight of the redeemed shares only
+    let (user, _) = &stakers[0];
+    let redeem_amount = 40_000_000_000;
+    process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), redeem_amount)],
+        &[user],
+    )
+    .await
+    .unwrap();
+    let treasury: Treasury = fetch(&mut context, t.treasury).await;
+    assert_eq!(treasury.total_weight, total_weight - redeem_amount);
+    assert_eq!(treasury.total_staked, staked - redeem_amount);
+}
+
+// the runtime rejects the hook call of a transfer cpi of the program as reentrant, the
+// treasury transfers of its own hooked mint are top level instructions next to ours
+#[tokio::test]
+async fn stake_and_redeem_hooked_mint() {
+    let mut context = program_test().start_with_context().await;
+    let payer = context.payer.pubkey();
+    let mint = create_mint(&mut context, true).await;
+    let t = create_treasury(&mut context, mint).await;
+    let fee_config = create_fee_config(&mut context, &t, Pubkey::new_unique()).await;
+    let fee_accrual = pda(&[FEE_ACCRUAL_TAG, mint.as_ref()]);
+    process(
+        &mut context,
+        &[Instruction {
+            program_id: sol_earna::ID,
+            accounts: sol_earna::accounts::EnableTrading {
+                authority: payer,
+                mint,
+                fee_config,
+            }
+            .to_account_metas(None),
+            data: sol_earna::instruction::EnableTrading {
+                trading_enabled_at: 0,
+                sniper_fee: 0,
+                sniper_fee_duration: 0,
+            }
+            .data(),
+        }],
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let user = create_user(&mut context).await;
+    let user_token_account =
+        create_token_account(&mut context, &user.pubkey(), &mint, &spl_token_2022::ID).await;
+    mint_to(&mut context, &mint, &user_token_account, MINT_AMOUNT).await;
+
+    // the deposit is the user's transfer_checked right before stake
+    let stake_amount = 100_000_000_000;
+    let result = process(
+        &mut context,
+        &[stake_instruction(&t, &user.pubkey(), stake_amount, 0)],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::InvalidTopLevelTransfer));
+    let deposit = hooked_transfer_checked(
+        &mut context,
+        mint,
+        user_token_account,
+        t.treasury_token_account,
+        user.pubkey(),
+        stake_amount,
+    )
+    .await;
+    let result = process(
+        &mut context,
+        &[deposit.clone(), stake_instruction(&t, &user.pubkey(), stake_amount + 1, 0)],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::InvalidTopLevelTransfer));
+    process(
+        &mut context,
+        &[deposit, stake_instruction(&t, &user.pubkey(), stake_amount, 0)],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    let shares = stake_amount * VIRTUAL_SHARES;
+    assert_eq!(token_balance(&mut context, t.treasury_token_account).await, stake_amount);
+    let treasury: Treasury = fetch(&mut context, t.treasury).await;
+    assert_eq!(treasury.total_staked, shares);
+    let stake_fee = stake_amount * FEE_PERCENT as u64 / 10000;
+    let accrual: FeeAccrual = fetch(&mut context, fee_accrual).await;
+    assert_eq!(accrual.pending_fee, stake_fee);
+
+    // the user moves the redeemed amount as delegate of the treasury right after redeem
+    let redeem_shares = shares / 2;
+    let redeem_amount = stake_amount / 2;
+    let result = process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), redeem_shares)],
+        &[&user],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::InvalidTopLevelTransfer));
+    let withdrawal = hooked_transfer_checked(
+        &mut context,
+        mint,
+        t.treasury_token_account,
+        user_token_account,
+        user.pubkey(),
+        redeem_amount,
+    )
+    .await;
+    process(
+        &mut context,
+        &[redeem_instruction(&t, &user.pubkey(), redeem_shares), withdrawal],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token_balance(&mut context, user_token_account).await,
+        MINT_AMOUNT - stake_amount + redeem_amount
+    );
+    let accrual: FeeAccrual = fetch(&mut context, fee_accrual).await;
+    assert_eq!(
+        accrual.pending_fee,
+        stake_fee + redeem_amount * FEE_PERCENT as u64 / 10000
+    );
+    assert_eq!(accrual.total_accrued, accrual.pending_fee);
+
+    // the withdrawal used up the approval
+    let account = context
+        .banks_client
+        .get_account(t.treasury_token_account)
+        .await
+        .unwrap()
+        .unwrap();
+    let treasury_token_account =
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data).unwrap();
+    assert_eq!(treasury_token_account.base.delegate, COption::None);
+
+    // and of the completed withdrawal
+    let redeem_cooldown = 60;
+    // after trading_enabled_at, which is the current time
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    let now = clock.unix_timestamp + 1;
+    warp_to(&mut context, now).await;
+    process(
+        &mut context,
+        &[
+            set_redeem_cooldown_instruction(&t, &payer, redeem_cooldown),
+            request_redeem_instruction(&t, &user.pubkey(), shares - redeem_shares),
+        ],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    warp_to(&mut context, now + redeem_cooldown).await;
+    let withdrawal = hooked_transfer_checked(
+        &mut context,
+        mint,
+        t.treasury_token_account,
+        user_token_account,
+        user.pubkey(),
+        stake_amount - redeem_amount,
+    )
+    .await;
+    process(
+        &mut context,
+        &[complete_redeem_instruction(&t, &user.pubkey()), withdrawal],
+        &[&user],
+    )
+    .await
+    .unwrap();
+    assert_eq!(token_balance(&mut context, user_token_account).await, MINT_AMOUNT);
+    assert_eq!(token_balance(&mut context, t.treasury_token_account).await, 0);
+    let accrual: FeeAccrual = fetch(&mut context, fee_accrual).await;
+    assert_eq!(accrual.pending_fee, 2 * stake_fee);
+}
+
+#[tokio::test]
+async fn transfer_checked_triggers_hook() {
+    let mut context = program_test().start_with_context().await;
+    let payer = context.payer.pubkey();
+    let mint = create_mint(&mut context, true).await;
+    let t = create_treasury(&mut context, mint).await;
+
+    let extra_account_meta_list = pda(&[EXTRA_ACCOUNT_METAS_TAG, mint.as_ref()]);
+    let fee_config = pda(&[FEE_CONFIG_TAG, mint.as_ref()]);
+    let fee_accrual = pda(&[FEE_ACCRUAL_TAG, mint.as_ref()]);
+    let fee_recipients = FEE_SPLIT
+        .iter()
+        .map(|(label, bps)| FeeRecipient {
+            recipient: Pubkey::new_unique(),
+            bps: *bps,
+            label: label.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    let initialize = |payer: Pubkey, wrapper_mint: Pubkey| Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::InitializeExtraAccountMetaList {
+            payer,
+            extra_account_meta_list,
+            mint,
+            token_program: spl_token_2022::ID,
+            token_program_org: spl_token::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            system_program: system_program::ID,
+            fee_config,
+            fee_accrual,
+            treasury: t.treasury,
+            wsol_mint: spl_token::native_mint::ID,
+            wrapper_mint,
+            pool_state: Pubkey::new_unique(),
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::InitializeExtraAccountMetaList {
+            fee_percent: FEE_PERCENT,
+            fee_recipients: fee_recipients.clone(),
+            accrual_threshold: 0,
+        }
+        .data(),
+    };
+    // only the treasury authority, with the wrapper mint of the treasury
+    let stranger = create_user(&mut context).await;
+    let result = process(
+        &mut context,
+        &[initialize(stranger.pubkey(), t.wrapper_mint)],
+        &[&stranger],
+    )
+    .await;
+    assert_eq!(error_code(result), u32::from(XError::NotAllowed));
+    let result = process(&mut context, &[initialize(payer, spl_token::native_mint::ID)], &[]).await;
+    assert_eq!(error_code(result), u32::from(XError::InvalidWrapperMint));
+    process(&mut context, &[initialize(payer, t.wrapper_mint)], &[]).await.unwrap();
+
+    let config: FeeConfig = fetch(&mut context, fee_config).await;
+    assert_eq!(config.authority, payer);
+    assert_eq!(config.wrapper_mint_address, t.wrapper_mint);
+    assert_eq!(config.transfer_fee, FEE_PERCENT);
+    assert_eq!(config.fee_recipients, fee_recipients);
+    assert_eq!(config.max_slippage_bps, DEFAULT_MAX_SLIPPAGE_BPS);
+    assert_eq!(config.price_window, DEFAULT_PRICE_WINDOW);
+    assert_eq!(config.max_tx_amount, TransferLimit::Unlimited);
+
+    let sender = create_user(&mut context).await;
+    let recipient = Pubkey::new_unique();
+    let source = create_token_account(&mut context, &sender.pubkey(), &mint, &spl_token_2022::ID).await;
+    let destination = create_token_account(&mut context, &recipient, &mint, &spl_token_2022::ID).await;
+    mint_to(&mut context, &mint, &source, MINT_AMOUNT).await;
+
+    // extra accounts in the order of the ExtraAccountMetaList (5..=14)
+    let amount = 1_000_000_000;
+    let mut instruction = spl_token_2022::instruction::transfer_checked(
+        &spl_token_2022::ID,
+        &source,
+        &mint,
+        &destination,
+        &sender.pubkey(),
+        &[],
+        amount,
+        DECIMALS,
+    )
+    .unwrap();
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(fee_config, false),
+        AccountMeta::new_readonly(t.treasury, false),
+        AccountMeta::new(fee_accrual, false),
+        AccountMeta::new_readonly(
+            pda(&[FEE_EXEMPTION_TAG, mint.as_ref(), sender.pubkey().as_ref()]),
+            false,
+        ),
+        AccountMeta::new_readonly(pda(&[FEE_EXEMPTION_TAG, mint.as_ref(), recipient.as_ref()]), false),
+        AccountMeta::new_readonly(pda(&[AMM_POOL_VAULT_TAG, mint.as_ref(), source.as_ref()]), false),
+        AccountMeta::new_readonly(
+            pda(&[AMM_POOL_VAULT_TAG, mint.as_ref(), destination.as_ref()]),
+            false,
+        ),
+        AccountMeta::new(
+            pda(&[SELL_COOLDOWN_TAG, mint.as_ref(), sender.pubkey().as_ref()]),
+            false,
+        ),
+        AccountMeta::new_readonly(
+            pda(&[BLOCKLIST_TAG, mint.as_ref(), sender.pubkey().as_ref()]),
+            false,
+        ),
+        AccountMeta::new_readonly(pda(&[BLOCKLIST_TAG, mint.as_ref(), recipient.as_ref()]), false),
+        AccountMeta::new_readonly(sol_earna::ID, false),
+        AccountMeta::new_readonly(extra_account_meta_list, false),
+    ]);
+    // only exempt wallets transfer until trading is enabled
+    let result = process(&mut context, &[instruction.clone()], &[&sender]).await;
+    assert_eq!(error_code(result), u32::from(XError::TradingNotEnabled));
+    process(
+        &mut context,
+        &[Instruction {
+            program_id: sol_earna::ID,
+            accounts: sol_earna::accounts::EnableTrading {
+                authority: payer,
+                mint,
+                fee_config,
+            }
+            .to_account_metas(None),
+            data: sol_earna::instruction::EnableTrading {
+                trading_enabled_at: 0,
+                sniper_fee: 0,
+                sniper_fee_duration: 0,
+            }
+            .data(),
+        }],
+        &[],
+    )
+    .await
+    .unwrap();
+    process(&mut context, &[instruction.clone()], &[&sender]).await.unwrap();
+
+    // the whole amount arrives, the fee is only recorded by the hook
+    assert_eq!(token_balance(&mut context, destination).await, amount);
+    let accrual: FeeAccrual = fetch(&mut context, fee_accrual).await;
+    assert_eq!(accrual.pending_fee, amount * FEE_PERCENT as u64 / 10000);
+    assert_eq!(accrual.total_accrued, accrual.pending_fee);
+
+    // anti-whale limits
+    let set_transfer_limits = move |max_tx_amount, max_wallet_balance| Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::SetTransferLimits {
+            authority: payer,
+            mint,
+            fee_config,
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::SetTransferLimits {
+            max_tx_amount,
+            max_wallet_balance,
+        }
+        .data(),
+    };
+    process(
+        &mut context,
+        &[set_transfer_limits(TransferLimit::Amount(amount - 1), TransferLimit::Unlimited)],
+        &[],
+    )
+    .await
+    .unwrap();
+    let result = process(&mut context, &[instruction.clone()], &[&sender]).await;
+    assert_eq!(error_code(result), u32::from(XError::MaxTxAmountExceeded));
+
+    // 0.01% of the supply is exactly the current destination balance
+    process(
+        &mut context,
+        &[set_transfer_limits(TransferLimit::Unlimited, TransferLimit::SupplyBps(1))],
+        &[],
+    )
+    .await
+    .unwrap();
+    let result = process(&mut context, &[instruction.clone()], &[&sender]).await;
+    assert_eq!(error_code(result), u32::from(XError::MaxWalletBalanceExceeded));
+
+    process(
+        &mut context,
+        &[set_transfer_limits(TransferLimit::Unlimited, TransferLimit::Unlimited)],
+        &[],
+    )
+    .await
+    .unwrap();
+    process(&mut context, &[instruction.clone()], &[&sender]).await.unwrap();
+    assert_eq!(token_balance(&mut context, destination).await, 2 * amount);
+
+    // calling the hook outside of a transfer neither accrues a fee nor records a sell
+    let mut execute = spl_transfer_hook_interface::instruction::execute(
+        &sol_earna::ID,
+        &source,
+        &mint,
+        &destination,
+        &sender.pubkey(),
+        &extra_account_meta_list,
+        amount,
+    );
+    execute.accounts.extend_from_slice(&instruction.accounts[4..14]);
+    let result = process(&mut context, &[execute], &[]).await;
+    assert_eq!(error_code(result), u32::from(XError::IsNotCurrentlyTransferring));
+}
+
+#[tokio::test]
+async fn add_auto_liquidity_bounds_the_swap_and_the_deposit() {
+    let mut context = program_test().start_with_context().await;
+    let payer = context.payer.pubkey();
+    let mint = create_mint(&mut context, true).await;
+    let t = create_treasury(&mut context, mint).await;
+    let pool = create_mock_pool(&mut context, &t.wrapper_mint).await;
+    let fee_config = create_fee_config(&mut context, &t, pool.pool_state).await;
+    process(
+        &mut context,
+        &[Instruction {
+            program_id: sol_earna::ID,
+            accounts: sol_earna::accounts::SetSlippageConfig {
+                authority: payer,
+                mint,
+                fee_config,
+            }
+            .to_account_metas(None),
+            data: sol_earna::instruction::SetSlippageConfig {
+                max_slippage_bps: 100,
+                price_window: 300,
+                price_oracle: Pubkey::default(),
+            }
+            .data(),
+        }],
+        &[],
+    )
+    .await
+    .unwrap();
+
+    let liquidity_authority = pda(&[LIQUIDITY_AUTHORITY_TAG, mint.as_ref()]);
+    let liquidity_wsol_token_account = get_associated_token_address_with_program_id(
+        &liquidity_authority,
+        &spl_token::native_mint::ID,
+        &spl_token::ID,
+    );
+    let liquidity_wrapper_token_account = get_associated_token_address_with_program_id(
+        &liquidity_authority,
+        &t.wrapper_mint,
+        &spl_token::ID,
+    );
+    let wsol_amount = 1_000_000_000;
+    set_token_account(
+        &mut context,
+        liquidity_wsol_token_account,
+        spl_token::native_mint::ID,
+        liquidity_authority,
+        wsol_amount,
+    )
+    .await;
+    set_token_account(
+        &mut context,
+        liquidity_wrapper_token_account,
+        t.wrapper_mint,
+        liquidity_authority,
+        0,
+    )
+    .await;
+
+    let liquidity_position = pda(&[LIQUIDITY_POSITION_TAG, mint.as_ref()]);
+    let position_nft_mint = pda(&[POSITION_NFT_MINT_TAG, mint.as_ref()]);
+    let personal_position = Pubkey::find_program_address(
+        &[POSITION_SEED.as_bytes(), position_nft_mint.as_ref()],
+        &mock_clmm::ID,
+    )
+    .0;
+    let add_auto_liquidity = |tick_lower_index: i32, tick_upper_index: i32| Instruction {
+        program_id: sol_earna::ID,
+        accounts: sol_earna::accounts::AddAutoLiquidity {
+            payer,
+            mint,
+            fee_config,
+            wsol_mint: spl_token::native_mint::ID,
+            wrapper_mint: t.wrapper_mint,
+            liquidity_authority,
+            liquidity_wsol_token_account,
+            liquidity_wrapper_token_account,
+            liquidity_position,
+            position_nft_mint,
+            position_nft_account: get_associated_token_address_with_program_id(
+                &liquidity_authority,
+                &position_nft_mint,
+                &spl_token::ID,
+            ),
+            metadata_account: Pubkey::new_unique(),
+            amm_program: mock_clmm::ID,
+            amm_config: Pubkey::new_unique(),
+            pool_state: pool.pool_state,
+            protocol_position: Pubkey::new_unique(),
+            personal_position,
+            tick_array_lower: Pubkey::new_unique(),
+            tick_array_upper: Pubkey::new_unique(),
+            token_vault_0: pool.wrapper_vault,
+            token_vault_1: pool.wsol_vault,
+            observation_state: pool.observation_state,
+            swap_tick_array: Pubkey::new_unique(),
+            rent: solana_sdk::sysvar::rent::ID,
+            system_program: system_program::ID,
+            token_program: spl_token::ID,
+            associated_token_program: spl_associated_token_account::ID,
+            metadata_program: Pubkey::new_unique(),
+            price_oracle: None,
+        }
+        .to_account_metas(None),
+        data: sol_earna::instruction::AddAutoLiquidity {
+            tick_lower_index,
+            tick_upper_index,
+            tick_array_lower_start_index: 0,
+            tick_array_upper_start_index: 0,
+        }
+        .data(),
+    };
+
+    // open: the twap is 1:1 but the pool was moved to 1.44 wsol per token within the
+    // block. the liquidity is priced at the twap, at the pool price it takes more wsol
+    // than the max and raydium rejects the deposit
+    process(
+        &mut context,
+        &[Instruction {
+            program_id: mock_clmm::ID,
+            accounts: mock_clmm::accounts::SetSqrtPrice {
+                pool_state: pool.pool_state,
+            }
+            .to_account_metas(None),
+            data: mock_clmm::instruction::SetSqrtPrice {
+                sqrt_price_x64: Q64 * 6 / 5,
+            }
+            .data(),
+        }],
+        &[],
+    )
+    .await
+    .unwrap();
+    let result = process(&mut context, &[add_auto_liquidity(-600, 600)], &[]).await;
+    assert_eq!(
+        error_code(result),
+        u32::from(mock_clmm::MockError::PriceSlippageCheck)
+    );
+
+    set_observations(&mut context, &pool, &t.wrapper_mint, &[(600, Q64)]).await;
+    process(&mut context, &[add_auto_liquidity(-600, 600)], &[]).await.unwrap();
+
+    // half of the wsol was swapped 1:1, both went in less the 1% held back
+    let position: LiquidityPosition = fetch(&mut context, liquidity_position).await;
+    assert_eq!(position.position_nft_mint, position_nft_mint);
+    assert_eq!((position.tick_lower_index, position.tick_upper_index), (-600, 600));
+    assert!(position.liquidity > 0);
+    let wsol_left = token_balance(&mut context, liquidity_wsol_token_account).await;
+    let wrapper_left = token_balance(&mut context, liquidity_wrapper_token_account).await;
+    assert_eq!(position.total_wsol_added + wsol_left, wsol_amount / 2);
+    assert_eq!(position.total_wrapper_added + wrapper_left, wsol_amount / 2);
+    assert!(wsol_left > 0 && wsol_left < wsol_amount / 100);
+    assert!(wrapper_left > 0 && wrapper_left < wsol_amount / 100);
+    let raydium_position: PersonalPositionState = fetch(&mut context, personal_position).await;
+    assert_eq!(raydium_position.liquidity, position.liquidity);
+
+    // the payer could only spend the deposit
+    for address in [liquidity_wsol_token_account, liquidity_wrapper_token_account] {
+        let account = context.banks_client.get_account(address).await.unwrap().unwrap();
+        let account = spl_token::state::Account::unpack(&account.data).unwrap();
+        assert_eq!(account.delegate, COption::None);
+    }
+
+    // increase: the pool traded at 4 tokens per wsol for the last 10 minutes, the 1:1
+    // swap is far below the twap and aborts before anything is deposited
+    let wsol_balance = wsol_left + wsol_amount;
+    set_token_account(
+        &mut context,
+        liquidity_wsol_token_account,
+        spl_token::native_mint::ID,
+        liquidity_authority,
+        wsol_balance,
+    )
+    .await;
+    set_observations(&mut context, &pool, &t.wrapper_mint, &[(600, Q64 / 2)]).await;
+    let result = process(&mut context, &[add_auto_liquidity(-1200, 1200)], &[]).await;
+    assert_eq!(error_code(result), u32::from(XError::SlippageExceeded));
+
+    // the tick arguments are ignored, the position keeps its range
+    set_observations(&mut context, &pool, &t.wrapper_mint, &[(600, Q64)]).await;
+    process(&mut context, &[add_auto_liquidity(-1200, 1200)], &[]).await.unwrap();
+    let increased: LiquidityPosition = fetch(&mut context, liquidity_position).await;
+    assert_eq!((increased.tick_lower_index, increased.tick_upper_index), (-600, 600));
+    assert!(increased.liquidity > position.liquidity);
+    let wsol_left_after = token_balance(&mut context, liquidity_wsol_token_account).await;
+    let wrapper_left_after = token_balance(&mut context, liquidity_wrapper_token_account).await;
+    assert_eq!(
+        increased.total_wsol_added - position.total_wsol_added + wsol_left_after,
+        wsol_balance - wsol_balance / 2
+    );
+    assert_eq!(
+        increased.total_wrapper_added - position.total_wrapper_added + wrapper_left_after,
+        wrapper_left + wsol_balance / 2
+    );
+    let raydium_position: PersonalPositionState = fetch(&mut context, personal_position).await;
+    assert_eq!(raydium_position.liquidity, increased.liquidity);
+}
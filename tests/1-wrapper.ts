@@ -1,11 +1,13 @@
 import * as anchor from "@coral-xyz/anchor";
 import {
   ASSOCIATED_TOKEN_PROGRAM_ID,
+  NATIVE_MINT,
   TOKEN_2022_PROGRAM_ID,
   TOKEN_PROGRAM_ID,
   createAssociatedTokenAccountInstruction,
   createMint,
   createMintToInstruction,
+  createSyncNativeInstruction,
   getAccount,
   getAssociatedTokenAddressSync
 } from "@solana/spl-token";
@@ -19,12 +21,19 @@ import { SolEarna } from "../target/types/sol_earna";
 import {
   Keypair,
   PublicKey,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
   SystemProgram,
   Transaction,
   sendAndConfirmTransaction
 } from "@solana/web3.js";
 import { pda } from "./utils";
-import { TREASURY_TAG, USER_WRAPPER_TOKEN_ACCOUNT_TAG } from "./constants";
+import {
+  REWARD_AUTHORITY_TAG,
+  STAKER_INFO_TAG,
+  TREASURY_TAG,
+  VIRTUAL_SHARES,
+  WITHDRAWAL_TICKET_TAG
+} from "./constants";
 
 chaiUse(chaiAsPromised);
 
@@ -120,6 +129,9 @@ describe('wrapper', () => {
 
   let wrapperMint: PublicKey;
   let treasuryTokenAccount: PublicKey;
+  let rewardAuthority: PublicKey;
+  let rewardVault: PublicKey;
+  let stakerInfo: PublicKey;
   it('CreateTreasury !', async () => {
     const treasury = await pda([TREASURY_TAG, treasuryTokenMint.toBuffer()], programId);
     const wrapperMintAuth = new Keypair();
@@ -169,64 +181,163 @@ describe('wrapper', () => {
     assert_true(treasuryData.treasuryMint.equals(treasuryTokenMint), "treasuryMint");
     assert_true(treasuryData.treasuryTokenAccount.equals(treasuryTokenAccount), "treasuryTokenAccount");
     assert_true(treasuryData.wrapperMint.equals(wrapperMint), "wrapperMint");
+
+    // wsol vault of the holders rewards, set as fee_recipient_holders of the fee config
+    rewardAuthority = await pda([REWARD_AUTHORITY_TAG, treasury.toBuffer()], programId);
+    rewardVault = getAssociatedTokenAddressSync(NATIVE_MINT, rewardAuthority, true);
+    stakerInfo = await pda([STAKER_INFO_TAG, treasury.toBuffer(), user.toBuffer()], programId);
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(treasuryAdmin, rewardVault, rewardAuthority, NATIVE_MINT)
+      ),
+      [wallet.payer]
+    );
   });
 
   const stakeAmount = 100_000_000_000; //100 POS
+  const stakeShares = stakeAmount * VIRTUAL_SHARES;
   it('Stake !', async () => {
     const treasury = await pda([TREASURY_TAG, treasuryTokenMint.toBuffer()], programId);
-    const userWrapperTokenAccount = await pda([USER_WRAPPER_TOKEN_ACCOUNT_TAG, wrapperMint.toBuffer(), user.toBuffer()], programId);
     const treasuryAmountBefore = (await getAccount(connection, treasuryTokenAccount, 'processed', TOKEN_2022_PROGRAM_ID)).amount
-    let userPosAmountBefore = BigInt(0);
+    let stakedSharesBefore = new anchor.BN(0);
     try {
-      userPosAmountBefore = (await getAccount(connection, userWrapperTokenAccount, 'processed', TOKEN_PROGRAM_ID)).amount;
+      stakedSharesBefore = (await program.account.stakerInfo.fetch(stakerInfo)).amount;
     } catch (e) { }
 
-    const txSig = await program.methods.stake(new anchor.BN(stakeAmount)).accounts({
+    const txSig = await program.methods.stake(new anchor.BN(stakeAmount), 0, new anchor.BN(stakeShares)).accounts({
       treasury, // treasury
-      wrapperMint, // wrapper_mint
       treasuryMint: treasuryTokenMint, // treasury_mint
       treasuryTokenAccount, // treasury_token_account
       userTokenAccount: userTreasuryTokenAccount, // user_token_account
-      userWrapperTokenAccount, // user_wrapper_token_account
+      stakerInfo, // staker_info
+      rewardAuthority, // reward_authority
+      rewardVault, // reward_vault
+      wsolMint: NATIVE_MINT, // wsol_mint
       user, // user
       systemProgram: anchor.web3.SystemProgram.programId, // system_program
-      tokenProgram: TOKEN_PROGRAM_ID, // token_program
       tokenProgramTreasury: TOKEN_2022_PROGRAM_ID, // token_program_treasury
-      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID // associated_token_program
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID, // associated_token_program
+      instructions: SYSVAR_INSTRUCTIONS_PUBKEY // instructions
     }).signers([userKeypair]).rpc();
     console.log(`Transaction Signature: ${txSig}`);
 
     let treasuryAmountAfter = (await getAccount(connection, treasuryTokenAccount, 'processed', TOKEN_2022_PROGRAM_ID)).amount;
-    let userPosAmountAfter = (await getAccount(connection, userWrapperTokenAccount, 'processed', TOKEN_PROGRAM_ID)).amount;
+    let stakedSharesAfter = (await program.account.stakerInfo.fetch(stakerInfo)).amount;
     assert_true(treasuryAmountAfter - treasuryAmountBefore === BigInt(stakeAmount), "stakeAmount treasury");
-    assert_true(userPosAmountAfter - userPosAmountBefore === BigInt(stakeAmount), "stakeAmount userPos");
+    assert_true(stakedSharesAfter.sub(stakedSharesBefore).eq(new anchor.BN(stakeShares)), "stakeShares stakerInfo");
+  });
+
+  it('Preview !', async () => {
+    const treasury = await pda([TREASURY_TAG, treasuryTokenMint.toBuffer()], programId);
+    const accounts = { treasury, treasuryTokenAccount };
+
+    // no yield yet, VIRTUAL_SHARES shares per treasury token
+    const shares = await program.methods.previewStake(new anchor.BN(stakeAmount)).accounts(accounts).view();
+    const assets = await program.methods.previewRedeem(new anchor.BN(stakeShares)).accounts(accounts).view();
+    assert_true(shares.eq(new anchor.BN(stakeShares)), "previewStake");
+    assert_true(assets.eq(new anchor.BN(stakeAmount)), "previewRedeem");
   });
 
   const redeemAmount = 10_000_000_000; //10 POS
+  const redeemShares = redeemAmount * VIRTUAL_SHARES;
   it('Redeem !', async () => {
     const treasury = await pda([TREASURY_TAG, treasuryTokenMint.toBuffer()], programId);
-    const userWrapperTokenAccount = await pda([USER_WRAPPER_TOKEN_ACCOUNT_TAG, wrapperMint.toBuffer(), user.toBuffer()], programId);
     let treasuryAmountBefore = (await getAccount(connection, treasuryTokenAccount, 'processed', TOKEN_2022_PROGRAM_ID)).amount;
-    let userPosAmountBefore = (await getAccount(connection, userWrapperTokenAccount, 'processed', TOKEN_PROGRAM_ID)).amount;
+    let stakedSharesBefore = (await program.account.stakerInfo.fetch(stakerInfo)).amount;
 
-    const txSig = await program.methods.redeem(new anchor.BN(redeemAmount)).accounts({
+    const txSig = await program.methods.redeem(new anchor.BN(redeemShares)).accounts({
       treasury,
       treasuryMint: treasuryTokenMint,
-      wrapperMint,
       treasuryTokenAccount,
       userTokenAccount: userTreasuryTokenAccount,
-      userWrapperTokenAccount,
+      stakerInfo,
+      rewardAuthority,
+      rewardVault,
+      wsolMint: NATIVE_MINT,
       user,
-      tokenProgram: TOKEN_PROGRAM_ID, // token_program
+      systemProgram: anchor.web3.SystemProgram.programId,
       tokenProgramTreasury: TOKEN_2022_PROGRAM_ID, // token_program_treasury
+      instructions: SYSVAR_INSTRUCTIONS_PUBKEY, // instructions
       // associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID
     }).signers([userKeypair]).rpc();
     console.log(`Transaction Signature: ${txSig}`);
 
     let treasuryAmountAfter = (await getAccount(connection, treasuryTokenAccount, 'processed', TOKEN_2022_PROGRAM_ID)).amount;
-    let userPosAmountAfter = (await getAccount(connection, userWrapperTokenAccount, 'processed', TOKEN_PROGRAM_ID)).amount;
+    let stakedSharesAfter = (await program.account.stakerInfo.fetch(stakerInfo)).amount;
     assert_true(treasuryAmountBefore - treasuryAmountAfter === BigInt(redeemAmount), "redeemAmount treasury");
-    assert_true(userPosAmountBefore - userPosAmountAfter === BigInt(redeemAmount), "redeemAmount userPos");
+    assert_true(stakedSharesBefore.sub(stakedSharesAfter).eq(new anchor.BN(redeemShares)), "redeemShares stakerInfo");
+  });
+
+  it('Request And Cancel Redeem !', async () => {
+    const treasury = await pda([TREASURY_TAG, treasuryTokenMint.toBuffer()], programId);
+    const withdrawalTicket = await pda([WITHDRAWAL_TICKET_TAG, treasury.toBuffer(), user.toBuffer()], programId);
+    const stakedBefore = (await program.account.stakerInfo.fetch(stakerInfo)).amount;
+
+    let txSig = await program.methods.requestRedeem(new anchor.BN(redeemShares)).accounts({
+      treasury,
+      stakerInfo,
+      withdrawalTicket,
+      rewardAuthority,
+      rewardVault,
+      wsolMint: NATIVE_MINT,
+      user,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    }).signers([userKeypair]).rpc();
+    console.log(`Transaction Signature: ${txSig}`);
+
+    // pending shares are held by the ticket and no longer staked
+    const ticket = await program.account.withdrawalTicket.fetch(withdrawalTicket);
+    assert_true(ticket.shares.eq(new anchor.BN(redeemShares)), "ticket shares");
+    assert_true((await program.account.stakerInfo.fetch(stakerInfo)).amount.eq(stakedBefore.sub(new anchor.BN(redeemShares))), "staked after request");
+
+    txSig = await program.methods.cancelRedeem().accounts({
+      treasury,
+      stakerInfo,
+      withdrawalTicket,
+      rewardAuthority,
+      rewardVault,
+      wsolMint: NATIVE_MINT,
+      user,
+    }).signers([userKeypair]).rpc();
+    console.log(`Transaction Signature: ${txSig}`);
+
+    assert_true((await program.account.stakerInfo.fetch(stakerInfo)).amount.eq(stakedBefore), "staked after cancel");
+    assert_true((await connection.getAccountInfo(withdrawalTicket)) === null, "ticket closed");
+  });
+
+  it('Claim Rewards !', async () => {
+    const treasury = await pda([TREASURY_TAG, treasuryTokenMint.toBuffer()], programId);
+    const userWsolTokenAccount = getAssociatedTokenAddressSync(NATIVE_MINT, user);
+    const rewardAmount = 1_000_000_000; // 1 SOL of holders fee
+
+    // holders fee arriving in the reward vault
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(treasuryAdmin, userWsolTokenAccount, user, NATIVE_MINT),
+        SystemProgram.transfer({ fromPubkey: treasuryAdmin, toPubkey: rewardVault, lamports: rewardAmount }),
+        createSyncNativeInstruction(rewardVault)
+      ),
+      [wallet.payer]
+    );
+
+    const txSig = await program.methods.claimRewards().accounts({
+      treasury,
+      stakerInfo,
+      rewardAuthority,
+      rewardVault,
+      wsolMint: NATIVE_MINT,
+      userWsolTokenAccount,
+      user,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).signers([userKeypair]).rpc();
+    console.log(`Transaction Signature: ${txSig}`);
+
+    // the only staker gets the whole reward, minus rounding dust
+    const claimed = (await getAccount(connection, userWsolTokenAccount, 'processed', TOKEN_PROGRAM_ID)).amount;
+    assert_true(claimed <= BigInt(rewardAmount), "claimed rewards");
+    assert_true(BigInt(rewardAmount) - claimed < BigInt(1000), "claimed rewards");
   });
 
   after(() => {
@@ -3,6 +3,7 @@ import { Program } from "@coral-xyz/anchor";
 import { pack, TokenMetadata } from "@solana/spl-token-metadata";
 
 import { SolEarna } from "../target/types/sol_earna";
+import { MockClmm } from "../target/types/mock_clmm";
 import {
   ASSOCIATED_TOKEN_PROGRAM_ID,
   createAssociatedTokenAccountInstruction,
@@ -12,11 +13,13 @@ import {
   createInitializeTransferFeeConfigInstruction,
   createInitializeTransferHookInstruction,
   createMintToInstruction,
+  createSyncNativeInstruction,
   createTransferCheckedWithTransferHookInstruction,
   createUpdateFieldInstruction,
   ExtensionType,
   getAccount,
   getAssociatedTokenAddressSync,
+  getMint,
   getMintLen,
   getOrCreateAssociatedTokenAccount,
   LENGTH_SIZE,
@@ -32,10 +35,25 @@ import {
   SystemProgram,
   Transaction,
 } from "@solana/web3.js";
+import { assert } from "chai";
 import {
+  AMM_POOL_VAULT_TAG,
+  BLOCKLIST_TAG,
+  BUYBACK_AUTHORITY_TAG,
+  DEFAULT_MAX_SLIPPAGE_BPS,
+  DEFAULT_PRICE_WINDOW,
   DELEGATE_TAG,
   EXTRA_ACCOUNT_METAS_TAG,
+  FEE_ACCRUAL_TAG,
   FEE_CONFIG_TAG,
+  FEE_EXEMPTION_TAG,
+  LIQUIDITY_AUTHORITY_TAG,
+  LIQUIDITY_POSITION_TAG,
+  POOL_STATE_TAG,
+  POSITION_NFT_MINT_TAG,
+  POSITION_SEED,
+  RAYDIUM_CLMM_PROGRAM_ID,
+  SELL_COOLDOWN_TAG,
   TREASURY_TAG,
 } from "./constants";
 import { pda } from "./utils";
@@ -47,6 +65,7 @@ describe("sol-earna", () => {
   anchor.setProvider(anchor.AnchorProvider.env());
 
   const program = anchor.workspace.SolEarna as Program<SolEarna>;
+  const mockClmm = anchor.workspace.MockClmm as Program<MockClmm>;
 
   const provider = anchor.AnchorProvider.env();
   const wallet = provider.wallet as anchor.Wallet;
@@ -63,10 +82,15 @@ describe("sol-earna", () => {
   const feeRecipientHolders = Keypair.generate();
 
   const FEE_PERCENT_HOLDERS = 500; // 5%
-  const FEE_PERCENT_MARKETING = 400; // 4%
+  const FEE_PERCENT_MARKETING = 300; // 3%
   const FEE_PERCENT_LIQUIDITY = 100; // 1%
+  const FEE_PERCENT_BUYBACK = 100; // 1%
   const TOTAL_FEE_PERCENT =
-    FEE_PERCENT_HOLDERS + FEE_PERCENT_MARKETING + FEE_PERCENT_LIQUIDITY;
+    FEE_PERCENT_HOLDERS +
+    FEE_PERCENT_MARKETING +
+    FEE_PERCENT_LIQUIDITY +
+    FEE_PERCENT_BUYBACK;
+  const ACCRUAL_THRESHOLD = 1_000_000; // 0.001 token
 
   const [extraAccountMetaListPDA] = PublicKey.findProgramAddressSync(
     [EXTRA_ACCOUNT_METAS_TAG, mint.toBuffer()],
@@ -83,6 +107,16 @@ describe("sol-earna", () => {
     program.programId
   );
 
+  const [feeAccrualPDA] = PublicKey.findProgramAddressSync(
+    [FEE_ACCRUAL_TAG, mint.toBuffer()],
+    program.programId
+  );
+
+  const [buybackAuthorityPDA] = PublicKey.findProgramAddressSync(
+    [BUYBACK_AUTHORITY_TAG, mint.toBuffer()],
+    program.programId
+  );
+
   it("Initialize!", async () => {});
   it("Create Mint Account with Transfer Hook Extension", async () => {
     const metaData: TokenMetadata = {
@@ -179,6 +213,8 @@ describe("sol-earna", () => {
   let feeLiquidityWsolTokenAccount: PublicKey;
   let feeMarketingWsolTokenAccount: PublicKey;
   let feeHoldersWsolTokenAccount: PublicKey;
+  let feeBuybackWsolTokenAccount: PublicKey;
+  let buybackWrapperTokenAccount: PublicKey;
   let treasury: PublicKey;
   let treasuryTokenAccount: PublicKey;
 
@@ -261,6 +297,17 @@ describe("sol-earna", () => {
       NATIVE_MINT,
       feeRecipientHolders.publicKey
     );
+    feeBuybackWsolTokenAccount = getAssociatedTokenAddressSync(
+      NATIVE_MINT,
+      buybackAuthorityPDA,
+      true
+    );
+    buybackWrapperTokenAccount = getAssociatedTokenAddressSync(
+      wrapperMint,
+      buybackAuthorityPDA,
+      true,
+      TOKEN_PROGRAM_ID
+    );
     await getOrCreateAssociatedTokenAccount(
       connection,
       wallet.payer,
@@ -302,6 +349,19 @@ describe("sol-earna", () => {
         feeHoldersWsolTokenAccount,
         feeRecipientHolders.publicKey,
         NATIVE_MINT
+      ),
+      createAssociatedTokenAccountInstruction(
+        wallet.publicKey,
+        feeBuybackWsolTokenAccount,
+        buybackAuthorityPDA,
+        NATIVE_MINT
+      ),
+      createAssociatedTokenAccountInstruction(
+        wallet.publicKey,
+        buybackWrapperTokenAccount,
+        buybackAuthorityPDA,
+        wrapperMint,
+        TOKEN_PROGRAM_ID
       )
     );
     const txSig = await sendAndConfirmTransaction(
@@ -313,6 +373,118 @@ describe("sol-earna", () => {
     PUT_LOG && console.log("Transaction Signature:", txSig);
   });
 
+  // mocked raydium clmm pool (wrapper_mint <-> wsol), swaps 1:1
+  const [poolState] = PublicKey.findProgramAddressSync(
+    [POOL_STATE_TAG],
+    RAYDIUM_CLMM_PROGRAM_ID
+  );
+  const ammConfig = Keypair.generate().publicKey;
+  const observationStateKeypair = Keypair.generate();
+  const observationState = observationStateKeypair.publicKey;
+  const OBSERVATION_STATE_LEN = 8 + 1 + 32 + 52 * 1000 + 16 * 5;
+  const Q64 = new anchor.BN(1).shln(64);
+
+  // price history of the mocked pool, a sqrt price per observation, oldest first
+  const setObservations = (observations: [number, anchor.BN][]) =>
+    mockClmm.methods
+      .setObservations(
+        wrapperMint,
+        observations.map(([secondsAgo, sqrtPriceX64]) => ({ secondsAgo, sqrtPriceX64 }))
+      )
+      .accounts({
+        payer: wallet.publicKey,
+        poolState,
+        observationState,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc({ commitment: "confirmed" });
+  const tickArray = Keypair.generate().publicKey;
+  let inputVault: PublicKey;
+  let outputVault: PublicKey;
+
+  it("Prepare mock CLMM pool", async () => {
+    inputVault = getAssociatedTokenAddressSync(
+      wrapperMint,
+      poolState,
+      true,
+      TOKEN_PROGRAM_ID
+    );
+    outputVault = getAssociatedTokenAddressSync(NATIVE_MINT, poolState, true);
+
+    const transaction = new Transaction().add(
+      createAssociatedTokenAccountInstruction(
+        wallet.publicKey,
+        inputVault,
+        poolState,
+        wrapperMint,
+        TOKEN_PROGRAM_ID
+      ),
+      createAssociatedTokenAccountInstruction(
+        wallet.publicKey,
+        outputVault,
+        poolState,
+        NATIVE_MINT
+      ),
+      SystemProgram.transfer({
+        fromPubkey: wallet.publicKey,
+        toPubkey: outputVault,
+        lamports: 10 * 10 ** 9,
+      }),
+      createSyncNativeInstruction(outputVault),
+      SystemProgram.createAccount({
+        fromPubkey: wallet.publicKey,
+        newAccountPubkey: observationState,
+        space: OBSERVATION_STATE_LEN,
+        lamports: await connection.getMinimumBalanceForRentExemption(
+          OBSERVATION_STATE_LEN
+        ),
+        programId: RAYDIUM_CLMM_PROGRAM_ID,
+      })
+    );
+    const txSig = await sendAndConfirmTransaction(
+      provider.connection,
+      transaction,
+      [wallet.payer, observationStateKeypair],
+      { skipPreflight: true, commitment: "confirmed" }
+    );
+    PUT_LOG && console.log("Transaction Signature:", txSig);
+
+    // the pool has traded 1:1 for the last 10 minutes
+    await setObservations([[600, Q64]]);
+  });
+
+  // share of the fee (bps) of each recipient
+  const feeRecipients = () => [
+    {
+      recipient: feeLiquidityWsolTokenAccount,
+      bps: (FEE_PERCENT_LIQUIDITY * 10000) / TOTAL_FEE_PERCENT,
+      label: "liquidity",
+    },
+    {
+      recipient: feeMarketingWsolTokenAccount,
+      bps: (FEE_PERCENT_MARKETING * 10000) / TOTAL_FEE_PERCENT,
+      label: "marketing",
+    },
+    {
+      recipient: feeHoldersWsolTokenAccount,
+      bps: (FEE_PERCENT_HOLDERS * 10000) / TOTAL_FEE_PERCENT,
+      label: "holders",
+    },
+    {
+      recipient: feeBuybackWsolTokenAccount,
+      bps: (FEE_PERCENT_BUYBACK * 10000) / TOTAL_FEE_PERCENT,
+      label: "buyback",
+    },
+  ];
+
+  // remaining accounts of the fee swap, in the order of the fee recipients
+  const feeRecipientAccounts = () =>
+    feeRecipients().map(({ recipient }) => ({
+      pubkey: recipient,
+      isSigner: false,
+      isWritable: true,
+    }));
+
   // Account to store extra accounts required by the transfer hook instruction
   it("Create ExtraAccountMetaList Account", async () => {
     const extraAccountMetasInfo = await connection.getAccountInfo(
@@ -333,19 +505,13 @@ describe("sol-earna", () => {
       feeWsolTokenAccount: feeWsolTokenAccount.toBase58(), // fee_wsol_token_account
       wrapperMint: wrapperMint.toBase58(), // wrapper_mint
       feeWrapperTokenAccount: feeWrapperTokenAccount.toBase58(), // fee_wrapper_token_account
-      feeRecipientLiquidity: feeRecipientLiquidity.publicKey.toBase58(), // fee_recipient_liquidity
-      feeLiquidityWsolTokenAccount: feeLiquidityWsolTokenAccount.toBase58(), // fee_liquidity_wsol_token_account
-      feeRecipientMarketing: feeRecipientMarketing.publicKey.toBase58(), // fee_recipient_marketing
-      feeMarketingWsolTokenAccount: feeMarketingWsolTokenAccount.toBase58(), // fee_marketing_wsol_token_account
-      feeRecipientHolders: feeRecipientHolders.publicKey.toBase58(), // fee_recipient_holders
-      feeHoldersWsolTokenAccount: feeHoldersWsolTokenAccount.toBase58(), // fee_holders_wsol_token_account
     });
 
     const initializeExtraAccountMetaListInstruction = await program.methods
       .initializeExtraAccountMetaList(
-        FEE_PERCENT_HOLDERS,
-        FEE_PERCENT_MARKETING,
-        FEE_PERCENT_LIQUIDITY
+        TOTAL_FEE_PERCENT,
+        feeRecipients(),
+        new anchor.BN(ACCRUAL_THRESHOLD)
       )
       .accounts({
         payer: wallet.publicKey, // payer
@@ -356,12 +522,11 @@ describe("sol-earna", () => {
         associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID, // associated_token_program
         systemProgram: SystemProgram.programId, // system_program
         feeConfig: feeConfigPDA, // fee_config
+        feeAccrual: feeAccrualPDA, // fee_accrual
         treasury, // treasury
         wsolMint: NATIVE_MINT, // wsol_mint
         wrapperMint, // wrapper_mint
-        feeRecipientLiquidity: feeRecipientLiquidity.publicKey, // fee_recipient_liquidity
-        feeRecipientMarketing: feeRecipientMarketing.publicKey, // fee_recipient_marketing
-        feeRecipientHolders: feeRecipientHolders.publicKey, // fee_recipient_holders
+        poolState, // pool_state
       })
       .instruction();
 
@@ -378,6 +543,165 @@ describe("sol-earna", () => {
     PUT_LOG && console.log("Transaction Signature:", txSig);
   });
 
+  it("Update Fee Config", async () => {
+    const updateFeeConfig = (
+      authority: Keypair,
+      feePercent: number,
+      feePercentCap: number
+    ) =>
+      program.methods
+        .updateFeeConfig(
+          feePercent,
+          feePercent,
+          feePercent,
+          feePercentCap,
+          new anchor.BN(ACCRUAL_THRESHOLD)
+        )
+        .accounts({
+          authority: authority.publicKey,
+          mint,
+          feeConfig: feeConfigPDA,
+        })
+        .signers([authority])
+        .rpc();
+
+    // only the fee config authority can update it
+    let failed = false;
+    try {
+      await updateFeeConfig(Keypair.generate(), TOTAL_FEE_PERCENT, 1500);
+    } catch (e) {
+      failed = true;
+    }
+    assert.isTrue(failed);
+
+    // the fee can't exceed the cap
+    failed = false;
+    try {
+      await updateFeeConfig(wallet.payer, 1600, 1500);
+    } catch (e) {
+      failed = true;
+    }
+    assert.isTrue(failed);
+
+    await updateFeeConfig(wallet.payer, TOTAL_FEE_PERCENT, 1500);
+    const feeConfig = await program.account.feeConfig.fetch(feeConfigPDA);
+    assert.isTrue(feeConfig.authority.equals(wallet.publicKey));
+    assert.equal(feeConfig.transferFee, TOTAL_FEE_PERCENT);
+    assert.equal(feeConfig.feePercentCap, 1500);
+  });
+
+  it("Set Fee Recipients", async () => {
+    const setFeeRecipients = (recipients: ReturnType<typeof feeRecipients>) =>
+      program.methods
+        .setFeeRecipients(recipients)
+        .accounts({
+          authority: wallet.publicKey,
+          mint,
+          feeConfig: feeConfigPDA,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc({ commitment: "confirmed" });
+    const feeConfigSize = async () =>
+      (await connection.getAccountInfo(feeConfigPDA, "confirmed")).data.length;
+
+    // the bps have to sum up to 100%
+    let failed = false;
+    try {
+      await setFeeRecipients(
+        feeRecipients().map((r) => ({ ...r, bps: r.bps / 2 }))
+      );
+    } catch (e) {
+      failed = true;
+    }
+    assert.isTrue(failed);
+
+    // the fee config is resized to the number of recipients
+    const sizeBefore = await feeConfigSize();
+    await setFeeRecipients([
+      { recipient: feeHoldersWsolTokenAccount, bps: 10000, label: "holders" },
+    ]);
+    assert.isBelow(await feeConfigSize(), sizeBefore);
+    let feeConfig = await program.account.feeConfig.fetch(feeConfigPDA);
+    assert.equal(feeConfig.feeRecipients.length, 1);
+
+    await setFeeRecipients(feeRecipients());
+    assert.equal(await feeConfigSize(), sizeBefore);
+    feeConfig = await program.account.feeConfig.fetch(feeConfigPDA);
+    assert.deepEqual(
+      feeConfig.feeRecipients.map((r) => r.label),
+      feeRecipients().map((r) => r.label)
+    );
+  });
+
+  it("Migrate Current Accounts", async () => {
+    const treasuryData = await program.account.treasury.fetch(treasury);
+    const feeConfig = await program.account.feeConfig.fetch(feeConfigPDA);
+    assert.equal(treasuryData.version, 2);
+    assert.equal(feeConfig.version, 2);
+
+    // only accounts from before versioning are migrated
+    let failed = false;
+    try {
+      await program.methods
+        .migrateTreasury()
+        .accounts({
+          authority: wallet.publicKey,
+          treasuryMint: mint,
+          treasury,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc({ commitment: "confirmed" });
+    } catch (e) {
+      failed = true;
+    }
+    assert.isTrue(failed);
+  });
+
+  it("Transfer Authority", async () => {
+    const newAuthority = Keypair.generate();
+
+    const handOverFeeConfig = async (from: Keypair, to: Keypair) => {
+      await program.methods
+        .proposeFeeConfigAuthority(to.publicKey)
+        .accounts({ authority: from.publicKey, mint, feeConfig: feeConfigPDA })
+        .signers([from])
+        .rpc();
+      await program.methods
+        .acceptFeeConfigAuthority()
+        .accounts({ pendingAuthority: to.publicKey, mint, feeConfig: feeConfigPDA })
+        .signers([to])
+        .rpc();
+    };
+    const handOverTreasury = async (from: Keypair, to: Keypair) => {
+      await program.methods
+        .proposeTreasuryAuthority(to.publicKey)
+        .accounts({ authority: from.publicKey, treasury })
+        .signers([from])
+        .rpc();
+      await program.methods
+        .acceptTreasuryAuthority()
+        .accounts({ pendingAuthority: to.publicKey, treasury })
+        .signers([to])
+        .rpc();
+    };
+
+    await handOverFeeConfig(wallet.payer, newAuthority);
+    await handOverTreasury(wallet.payer, newAuthority);
+    let feeConfig = await program.account.feeConfig.fetch(feeConfigPDA);
+    let treasuryData = await program.account.treasury.fetch(treasury);
+    assert.isTrue(feeConfig.authority.equals(newAuthority.publicKey));
+    assert.isTrue(feeConfig.pendingAuthority.equals(PublicKey.default));
+    assert.isTrue(treasuryData.authority.equals(newAuthority.publicKey));
+
+    // hand the authority back for the rest of the tests
+    await handOverFeeConfig(newAuthority, wallet.payer);
+    await handOverTreasury(newAuthority, wallet.payer);
+    feeConfig = await program.account.feeConfig.fetch(feeConfigPDA);
+    treasuryData = await program.account.treasury.fetch(treasury);
+    assert.isTrue(feeConfig.authority.equals(wallet.publicKey));
+    assert.isTrue(treasuryData.authority.equals(wallet.publicKey));
+  });
+
   // Sender token account address
   const sender = Keypair.generate();
   let sourceTokenAccount: PublicKey;
@@ -458,6 +782,54 @@ describe("sol-earna", () => {
     PUT_LOG && console.log(`Transaction Signature: ${txSig}`);
   });
 
+  it("Enable Trading", async () => {
+    const enableTrading = () =>
+      program.methods
+        .enableTrading(new anchor.BN(0), 0, 0) // now, without a sniper fee
+        .accounts({ authority: wallet.publicKey, mint, feeConfig: feeConfigPDA })
+        .rpc({ commitment: "confirmed" });
+
+    // non-exempt wallets cannot transfer before the launch
+    const transferInstruction =
+      await createTransferCheckedWithTransferHookInstruction(
+        connection,
+        sourceTokenAccount,
+        mint,
+        destinationTokenAccount,
+        sender.publicKey,
+        BigInt(1),
+        decimals,
+        [sender.publicKey],
+        "confirmed",
+        TOKEN_2022_PROGRAM_ID
+      );
+    let failed = false;
+    try {
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(transferInstruction),
+        [sender],
+        { commitment: "confirmed" }
+      );
+    } catch (e) {
+      failed = true;
+    }
+    assert.isTrue(failed);
+
+    await enableTrading();
+    const feeConfig = await program.account.feeConfig.fetch(feeConfigPDA);
+    assert.isTrue(feeConfig.tradingEnabledAt.gtn(0));
+
+    // only once
+    failed = false;
+    try {
+      await enableTrading();
+    } catch (e) {
+      failed = true;
+    }
+    assert.isTrue(failed);
+  });
+
   it("Transfer Token", async () => {
     const amount = 1 * 10 ** decimals;
     const bigIntAmount = BigInt(amount);
@@ -494,8 +866,834 @@ describe("sol-earna", () => {
     const balanceDestinationAfter = await getTokenBalance(destinationTokenAccount);
     console.log({ balanceSourceAfter, balanceDestinationAfter });
 
+    // the fee is only recorded, nothing is minted or swapped during the transfer
+    const feeAccrual = await program.account.feeAccrual.fetch(feeAccrualPDA);
+    console.log({ pendingFee: feeAccrual.pendingFee.toString() });
+    assert.equal(
+      BigInt(feeAccrual.pendingFee.toString()),
+      (bigIntAmount * BigInt(TOTAL_FEE_PERCENT)) / BigInt(10000)
+    );
     const wrapperBalance = await getTokenBalance(feeWrapperTokenAccount, TOKEN_PROGRAM_ID);
-    console.log({ wrapperBalance });
+    assert.equal(wrapperBalance, BigInt(0));
+  });
+
+  const swapFeeAccounts = () => ({
+    mint,
+    feeConfig: feeConfigPDA,
+    delegate: delegatePDA,
+    wsolMint: NATIVE_MINT,
+    wrapperMint,
+    feeWrapperTokenAccount,
+    feeWsolTokenAccount,
+    ammProgram: RAYDIUM_CLMM_PROGRAM_ID,
+    ammConfig,
+    poolState,
+    inputVault,
+    outputVault,
+    observationState,
+    tickArray,
+    tokenProgram: TOKEN_PROGRAM_ID,
+    priceOracle: null, // the slippage check uses the pool twap
+  });
+
+  const setSlippageConfig = (maxSlippageBps: number, priceWindow: number) =>
+    program.methods
+      .setSlippageConfig(maxSlippageBps, priceWindow, PublicKey.default)
+      .accounts({ authority: wallet.publicKey, mint, feeConfig: feeConfigPDA })
+      .rpc({ commitment: "confirmed" });
+
+  it("Set Slippage Config", async () => {
+    // the permissionless cranks are bounded from the start
+    let feeConfig = await program.account.feeConfig.fetch(feeConfigPDA);
+    assert.equal(feeConfig.maxSlippageBps, DEFAULT_MAX_SLIPPAGE_BPS);
+    assert.equal(feeConfig.priceWindow, DEFAULT_PRICE_WINDOW);
+
+    // more than 100% or an enabled check without a window
+    for (const [maxSlippageBps, priceWindow] of [[10001, 60], [100, 0]]) {
+      let failed = false;
+      try {
+        await setSlippageConfig(maxSlippageBps, priceWindow);
+      } catch (e) {
+        failed = true;
+      }
+      assert.isTrue(failed);
+    }
+
+    await setSlippageConfig(100, 300);
+    feeConfig = await program.account.feeConfig.fetch(feeConfigPDA);
+    assert.equal(feeConfig.maxSlippageBps, 100);
+    assert.equal(feeConfig.priceWindow, 300);
+    assert.isTrue(feeConfig.priceOracle.equals(PublicKey.default));
+
+    await setSlippageConfig(0, 0);
+    feeConfig = await program.account.feeConfig.fetch(feeConfigPDA);
+    assert.equal(feeConfig.maxSlippageBps, 0);
+  });
+
+  it("Process Accrued Fees", async () => {
+    const { pendingFee } = await program.account.feeAccrual.fetch(feeAccrualPDA);
+
+    // the pool traded at 4 wsol per token for the last 10 minutes, the 1:1 swap of the mock
+    // is far below the twap, so the swap aborts and the fee stays pending
+    await setObservations([[600, Q64.muln(2)]]);
+    await setSlippageConfig(100, 300);
+    let error: string;
+    try {
+      await program.methods
+        .processAccruedFees()
+        .accounts({
+          swap: swapFeeAccounts(),
+          feeAccrual: feeAccrualPDA,
+          treasury,
+        })
+        .remainingAccounts(feeRecipientAccounts())
+        .rpc();
+    } catch (e) {
+      error = e.error?.errorCode?.code;
+    }
+    assert.equal(error, "SlippageExceeded");
+    const feeAccrualDeferred = await program.account.feeAccrual.fetch(feeAccrualPDA);
+    assert.equal(feeAccrualDeferred.pendingFee.toString(), pendingFee.toString());
+
+    // back at 1:1, the swap is within the slippage
+    await setObservations([[600, Q64]]);
+
+    const wrapperBalance = BigInt(pendingFee.toString());
+    const balanceLiquidityBefore = await getTokenBalance(feeLiquidityWsolTokenAccount, TOKEN_PROGRAM_ID);
+    const balanceMarketingBefore = await getTokenBalance(feeMarketingWsolTokenAccount, TOKEN_PROGRAM_ID);
+    const balanceHoldersBefore = await getTokenBalance(feeHoldersWsolTokenAccount, TOKEN_PROGRAM_ID);
+    const balanceBuybackBefore = await getTokenBalance(feeBuybackWsolTokenAccount, TOKEN_PROGRAM_ID);
+
+    const txSig = await program.methods
+      .processAccruedFees()
+      .accounts({
+        swap: swapFeeAccounts(),
+        feeAccrual: feeAccrualPDA,
+        treasury,
+      })
+      .remainingAccounts(feeRecipientAccounts())
+      .rpc();
+    PUT_LOG && console.log("Process Signature:", txSig);
+
+    // the mocked pool swaps 1:1, so each recipient gets its share of the fee in wsol
+    const balanceLiquidityAfter = await getTokenBalance(feeLiquidityWsolTokenAccount, TOKEN_PROGRAM_ID);
+    const balanceMarketingAfter = await getTokenBalance(feeMarketingWsolTokenAccount, TOKEN_PROGRAM_ID);
+    const balanceHoldersAfter = await getTokenBalance(feeHoldersWsolTokenAccount, TOKEN_PROGRAM_ID);
+    const balanceBuybackAfter = await getTokenBalance(feeBuybackWsolTokenAccount, TOKEN_PROGRAM_ID);
+    assert.equal(
+      await getTokenBalance(feeWrapperTokenAccount, TOKEN_PROGRAM_ID),
+      BigInt(0)
+    );
+    assert.equal(
+      balanceLiquidityAfter - balanceLiquidityBefore,
+      (wrapperBalance * BigInt(FEE_PERCENT_LIQUIDITY)) / BigInt(TOTAL_FEE_PERCENT)
+    );
+    assert.equal(
+      balanceMarketingAfter - balanceMarketingBefore,
+      (wrapperBalance * BigInt(FEE_PERCENT_MARKETING)) / BigInt(TOTAL_FEE_PERCENT)
+    );
+    assert.equal(
+      balanceHoldersAfter - balanceHoldersBefore,
+      (wrapperBalance * BigInt(FEE_PERCENT_HOLDERS)) / BigInt(TOTAL_FEE_PERCENT)
+    );
+    assert.equal(
+      balanceBuybackAfter - balanceBuybackBefore,
+      (wrapperBalance * BigInt(FEE_PERCENT_BUYBACK)) / BigInt(TOTAL_FEE_PERCENT)
+    );
+
+    const feeAccrual = await program.account.feeAccrual.fetch(feeAccrualPDA);
+    assert.equal(feeAccrual.pendingFee.toString(), "0");
+    assert.equal(feeAccrual.totalProcessed.toString(), pendingFee.toString());
+    await setSlippageConfig(0, 0);
+  });
+
+  it("Buyback And Burn", async () => {
+    // the processed fee left wrapper_mint in the pool, buy half of it back
+    const wrapperVaultBalance = await getTokenBalance(inputVault, TOKEN_PROGRAM_ID);
+    const amount = wrapperVaultBalance / BigInt(2);
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: wallet.publicKey,
+          toPubkey: feeBuybackWsolTokenAccount,
+          lamports: Number(amount),
+        }),
+        createSyncNativeInstruction(feeBuybackWsolTokenAccount)
+      ),
+      [wallet.payer],
+      { commitment: "confirmed" }
+    );
+    const supplyBefore = (await getMint(connection, wrapperMint, "confirmed")).supply;
+
+    const buybackAndBurn = () =>
+      program.methods
+        .buybackAndBurn(new anchor.BN(amount.toString()))
+        .accounts({
+          mint,
+          feeConfig: feeConfigPDA,
+          feeAccrual: feeAccrualPDA,
+          wsolMint: NATIVE_MINT,
+          wrapperMint,
+          buybackAuthority: buybackAuthorityPDA,
+          buybackWsolTokenAccount: feeBuybackWsolTokenAccount,
+          buybackWrapperTokenAccount,
+          ammProgram: RAYDIUM_CLMM_PROGRAM_ID,
+          ammConfig,
+          poolState,
+          inputVault: outputVault, // wsol -> wrapper_mint
+          outputVault: inputVault,
+          observationState,
+          tickArray,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          priceOracle: null, // the slippage check uses the pool twap
+        })
+        .rpc({ commitment: "confirmed" });
+
+    // the pool traded at 4 tokens per wsol for the last 10 minutes, the 1:1 swap of the
+    // mock is far below the twap, so the buyback aborts and the wsol stays
+    await setObservations([[600, Q64.divn(2)]]);
+    await setSlippageConfig(100, 300);
+    const wsolBalanceBefore = await getTokenBalance(feeBuybackWsolTokenAccount, TOKEN_PROGRAM_ID);
+    let error: string;
+    try {
+      await buybackAndBurn();
+    } catch (e) {
+      error = e.error?.errorCode?.code;
+    }
+    assert.equal(error, "SlippageExceeded");
+    assert.equal(
+      await getTokenBalance(feeBuybackWsolTokenAccount, TOKEN_PROGRAM_ID),
+      wsolBalanceBefore
+    );
+
+    // back at 1:1, the swap is within the slippage
+    await setObservations([[600, Q64]]);
+    const txSig = await buybackAndBurn();
+    PUT_LOG && console.log("Buyback Signature:", txSig);
+    await setSlippageConfig(0, 0);
+
+    // the mocked pool swaps 1:1, everything bought back is burned
+    const supplyAfter = (await getMint(connection, wrapperMint, "confirmed")).supply;
+    assert.equal(supplyBefore - supplyAfter, amount);
+    assert.equal(
+      await getTokenBalance(buybackWrapperTokenAccount, TOKEN_PROGRAM_ID),
+      BigInt(0)
+    );
+    const feeAccrual = await program.account.feeAccrual.fetch(feeAccrualPDA);
+    assert.equal(feeAccrual.totalBuybackWsol.toString(), amount.toString());
+    assert.equal(feeAccrual.totalBuybackBurned.toString(), amount.toString());
+  });
+
+  // the liquidity authority pairs its wsol with wrapper_mint in a position of the mocked
+  // pool, the mock takes the raydium amounts at the pool price and enforces the maxes
+  const [liquidityAuthorityPDA] = PublicKey.findProgramAddressSync(
+    [LIQUIDITY_AUTHORITY_TAG, mint.toBuffer()],
+    program.programId
+  );
+  const [liquidityPositionPDA] = PublicKey.findProgramAddressSync(
+    [LIQUIDITY_POSITION_TAG, mint.toBuffer()],
+    program.programId
+  );
+  const [positionNftMint] = PublicKey.findProgramAddressSync(
+    [POSITION_NFT_MINT_TAG, mint.toBuffer()],
+    program.programId
+  );
+  const [personalPosition] = PublicKey.findProgramAddressSync(
+    [POSITION_SEED, positionNftMint.toBuffer()],
+    RAYDIUM_CLMM_PROGRAM_ID
+  );
+  const liquidityWsolTokenAccount = getAssociatedTokenAddressSync(
+    NATIVE_MINT,
+    liquidityAuthorityPDA,
+    true
+  );
+  let liquidityWrapperTokenAccount: PublicKey;
+  const TICK_LOWER_INDEX = -600;
+  const TICK_UPPER_INDEX = 600;
+
+  const fundLiquidityWsol = (lamports: bigint) =>
+    sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(
+        SystemProgram.transfer({
+          fromPubkey: wallet.publicKey,
+          toPubkey: liquidityWsolTokenAccount,
+          lamports: Number(lamports),
+        }),
+        createSyncNativeInstruction(liquidityWsolTokenAccount)
+      ),
+      [wallet.payer],
+      { commitment: "confirmed" }
+    );
+
+  const addAutoLiquidity = () =>
+    program.methods
+      .addAutoLiquidity(TICK_LOWER_INDEX, TICK_UPPER_INDEX, 0, 0)
+      .accounts({
+        payer: wallet.publicKey,
+        mint,
+        feeConfig: feeConfigPDA,
+        wsolMint: NATIVE_MINT,
+        wrapperMint,
+        liquidityAuthority: liquidityAuthorityPDA,
+        liquidityWsolTokenAccount,
+        liquidityWrapperTokenAccount,
+        liquidityPosition: liquidityPositionPDA,
+        positionNftMint,
+        positionNftAccount: getAssociatedTokenAddressSync(
+          positionNftMint,
+          liquidityAuthorityPDA,
+          true
+        ),
+        metadataAccount: Keypair.generate().publicKey,
+        ammProgram: RAYDIUM_CLMM_PROGRAM_ID,
+        ammConfig,
+        poolState,
+        protocolPosition: Keypair.generate().publicKey,
+        personalPosition,
+        tickArrayLower: Keypair.generate().publicKey,
+        tickArrayUpper: Keypair.generate().publicKey,
+        tokenVault0: inputVault, // wrapper_mint is token_0 of the mocked pool
+        tokenVault1: outputVault,
+        observationState,
+        swapTickArray: tickArray,
+        rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        systemProgram: SystemProgram.programId,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        metadataProgram: Keypair.generate().publicKey,
+        priceOracle: null, // the slippage check uses the pool twap
+      })
+      .rpc({ commitment: "confirmed" });
+
+  // the anchor error logged by the failing program, the mock's included
+  const loggedError = (e: any): string =>
+    e.logs?.find((log: string) => log.includes("Error Code:"));
+
+  it("Add Auto Liquidity (open)", async () => {
+    liquidityWrapperTokenAccount = getAssociatedTokenAddressSync(
+      wrapperMint,
+      liquidityAuthorityPDA,
+      true,
+      TOKEN_PROGRAM_ID
+    );
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(
+        createAssociatedTokenAccountInstruction(
+          wallet.publicKey,
+          liquidityWsolTokenAccount,
+          liquidityAuthorityPDA,
+          NATIVE_MINT
+        ),
+        createAssociatedTokenAccountInstruction(
+          wallet.publicKey,
+          liquidityWrapperTokenAccount,
+          liquidityAuthorityPDA,
+          wrapperMint,
+          TOKEN_PROGRAM_ID
+        )
+      ),
+      [wallet.payer],
+      { commitment: "confirmed" }
+    );
+    await mockClmm.methods
+      .setTokenVaults()
+      .accounts({ poolState, tokenVault0: inputVault, tokenVault1: outputVault })
+      .rpc({ commitment: "confirmed" });
+    // half of it is swapped against the wrapper_mint left in the pool
+    const wsolAmount = (await getTokenBalance(inputVault, TOKEN_PROGRAM_ID)) / BigInt(2);
+    await fundLiquidityWsol(wsolAmount);
+    await setSlippageConfig(100, 300);
+
+    // the twap is 1:1, but the pool was moved to 1.44 wsol per token within the block.
+    // the liquidity is priced at the twap, so the deposit at the pool price needs more
+    // wsol than the max and the mocked raydium rejects it
+    await mockClmm.methods
+      .setSqrtPrice(Q64.muln(6).divn(5))
+      .accounts({ poolState })
+      .rpc({ commitment: "confirmed" });
+    let error: string;
+    try {
+      await addAutoLiquidity();
+    } catch (e) {
+      error = loggedError(e);
+    }
+    assert.include(error, "PriceSlippageCheck");
+
+    await setObservations([[600, Q64]]);
+    const txSig = await addAutoLiquidity();
+    PUT_LOG && console.log("Add Liquidity Signature:", txSig);
+
+    const position = await program.account.liquidityPosition.fetch(liquidityPositionPDA);
+    assert.isTrue(position.positionNftMint.equals(positionNftMint));
+    assert.equal(position.tickLowerIndex, TICK_LOWER_INDEX);
+    assert.equal(position.tickUpperIndex, TICK_UPPER_INDEX);
+    assert.isTrue(position.liquidity.gtn(0));
+
+    // both halves went in at 1:1, 1% of each is held back for the slippage
+    const wsolLeft = await getTokenBalance(liquidityWsolTokenAccount, TOKEN_PROGRAM_ID);
+    const wrapperLeft = await getTokenBalance(liquidityWrapperTokenAccount, TOKEN_PROGRAM_ID);
+    assert.equal(BigInt(position.totalWsolAdded.toString()) + wsolLeft, wsolAmount - wsolAmount / BigInt(2));
+    assert.equal(BigInt(position.totalWrapperAdded.toString()) + wrapperLeft, wsolAmount / BigInt(2));
+    assert.isTrue(wsolLeft * BigInt(50) < wsolAmount);
+    assert.isTrue(wrapperLeft * BigInt(50) < wsolAmount);
+
+    // the payer was only allowed to spend the deposit
+    const wsolAccount = await getAccount(connection, liquidityWsolTokenAccount, "confirmed");
+    assert.isNull(wsolAccount.delegate);
+    await setSlippageConfig(0, 0);
+  });
+
+  it("Add Auto Liquidity (increase)", async () => {
+    const before = await program.account.liquidityPosition.fetch(liquidityPositionPDA);
+    const wsolLeft = await getTokenBalance(liquidityWsolTokenAccount, TOKEN_PROGRAM_ID);
+    const wrapperLeft = await getTokenBalance(liquidityWrapperTokenAccount, TOKEN_PROGRAM_ID);
+    const wsolAmount = (await getTokenBalance(inputVault, TOKEN_PROGRAM_ID)) / BigInt(4);
+    await fundLiquidityWsol(wsolAmount);
+    await setSlippageConfig(100, 300);
+
+    // the pool traded at 4 tokens per wsol for the last 10 minutes, the 1:1 swap of the
+    // mock is far below the twap, so the swap aborts before anything is deposited
+    await setObservations([[600, Q64.divn(2)]]);
+    let error: string;
+    try {
+      await addAutoLiquidity();
+    } catch (e) {
+      error = e.error?.errorCode?.code;
+    }
+    assert.equal(error, "SlippageExceeded");
+
+    await setObservations([[600, Q64]]);
+    const txSig = await addAutoLiquidity();
+    PUT_LOG && console.log("Add Liquidity Signature:", txSig);
+
+    // the tick arguments are ignored, the position keeps its range
+    const position = await program.account.liquidityPosition.fetch(liquidityPositionPDA);
+    assert.isTrue(position.positionNftMint.equals(positionNftMint));
+    assert.equal(position.tickLowerIndex, TICK_LOWER_INDEX);
+    assert.equal(position.tickUpperIndex, TICK_UPPER_INDEX);
+    assert.isTrue(position.liquidity.gt(before.liquidity));
+
+    const wsolAdded = BigInt(position.totalWsolAdded.sub(before.totalWsolAdded).toString());
+    const wrapperAdded = BigInt(
+      position.totalWrapperAdded.sub(before.totalWrapperAdded).toString()
+    );
+    const wsolLeftAfter = await getTokenBalance(liquidityWsolTokenAccount, TOKEN_PROGRAM_ID);
+    const wrapperLeftAfter = await getTokenBalance(liquidityWrapperTokenAccount, TOKEN_PROGRAM_ID);
+    assert.equal(
+      wsolAdded + wsolLeftAfter,
+      wsolLeft + wsolAmount - wsolAmount / BigInt(2)
+    );
+    assert.equal(wrapperAdded + wrapperLeftAfter, wrapperLeft + wsolAmount / BigInt(2));
+    await setSlippageConfig(0, 0);
+  });
+
+  it("Transfer Token From Exempt Wallet", async () => {
+    const [feeExemption] = PublicKey.findProgramAddressSync(
+      [FEE_EXEMPTION_TAG, mint.toBuffer(), sender.publicKey.toBuffer()],
+      program.programId
+    );
+    await program.methods
+      .addFeeExemption(sender.publicKey)
+      .accounts({
+        authority: wallet.publicKey,
+        mint,
+        feeConfig: feeConfigPDA,
+        feeExemption,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const pendingFeeBefore = (
+      await program.account.feeAccrual.fetch(feeAccrualPDA)
+    ).pendingFee;
+
+    const transferInstruction =
+      await createTransferCheckedWithTransferHookInstruction(
+        connection,
+        sourceTokenAccount,
+        mint,
+        destinationTokenAccount,
+        sender.publicKey,
+        BigInt(1 * 10 ** decimals),
+        decimals,
+        [sender.publicKey],
+        "confirmed",
+        TOKEN_2022_PROGRAM_ID
+      );
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(transferInstruction),
+      [sender],
+      { commitment: "confirmed" }
+    );
+
+    // no fee recorded for an exempt source owner
+    const pendingFeeAfter = (
+      await program.account.feeAccrual.fetch(feeAccrualPDA)
+    ).pendingFee;
+    assert.equal(pendingFeeAfter.toString(), pendingFeeBefore.toString());
+
+    await program.methods
+      .removeFeeExemption()
+      .accounts({
+        authority: wallet.publicKey,
+        mint,
+        feeConfig: feeConfigPDA,
+        feeExemption,
+      })
+      .rpc();
+    assert.isNull(await connection.getAccountInfo(feeExemption));
+  });
+
+  it("Transfer Token To AMM Pool Vault (sell)", async () => {
+    const SELL_FEE_PERCENT = 1500; // 15%
+    const [ammPoolVault] = PublicKey.findProgramAddressSync(
+      [AMM_POOL_VAULT_TAG, mint.toBuffer(), destinationTokenAccount.toBuffer()],
+      program.programId
+    );
+    // treat the recipient token account as a pool vault with a higher sell fee
+    await program.methods
+      .addAmmPoolVault(destinationTokenAccount)
+      .accounts({
+        authority: wallet.publicKey,
+        mint,
+        feeConfig: feeConfigPDA,
+        ammPoolVault,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+    await program.methods
+      .updateFeeConfig(
+        TOTAL_FEE_PERCENT,
+        TOTAL_FEE_PERCENT,
+        SELL_FEE_PERCENT,
+        1500,
+        new anchor.BN(ACCRUAL_THRESHOLD)
+      )
+      .accounts({
+        authority: wallet.publicKey,
+        mint,
+        feeConfig: feeConfigPDA,
+      })
+      .rpc();
+
+    const feeAccrualBefore = await program.account.feeAccrual.fetch(feeAccrualPDA);
+
+    const amount = BigInt(1 * 10 ** decimals);
+    const transferInstruction =
+      await createTransferCheckedWithTransferHookInstruction(
+        connection,
+        sourceTokenAccount,
+        mint,
+        destinationTokenAccount,
+        sender.publicKey,
+        amount,
+        decimals,
+        [sender.publicKey],
+        "confirmed",
+        TOKEN_2022_PROGRAM_ID
+      );
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(transferInstruction),
+      [sender],
+      { commitment: "confirmed" }
+    );
+
+    const feeAccrualAfter = await program.account.feeAccrual.fetch(feeAccrualPDA);
+    assert.equal(
+      BigInt(feeAccrualAfter.pendingFee.toString()) -
+        BigInt(feeAccrualBefore.pendingFee.toString()),
+      (amount * BigInt(SELL_FEE_PERCENT)) / BigInt(10000)
+    );
+
+    await program.methods
+      .removeAmmPoolVault()
+      .accounts({
+        authority: wallet.publicKey,
+        mint,
+        feeConfig: feeConfigPDA,
+        ammPoolVault,
+      })
+      .rpc();
+  });
+
+  it("Sell Within The Cooldown", async () => {
+    const [ammPoolVault] = PublicKey.findProgramAddressSync(
+      [AMM_POOL_VAULT_TAG, mint.toBuffer(), destinationTokenAccount.toBuffer()],
+      program.programId
+    );
+    const [sellCooldown] = PublicKey.findProgramAddressSync(
+      [SELL_COOLDOWN_TAG, mint.toBuffer(), sender.publicKey.toBuffer()],
+      program.programId
+    );
+    const setSellCooldown = (sellCooldown: number) =>
+      program.methods
+        .setSellCooldown(new anchor.BN(sellCooldown))
+        .accounts({ authority: wallet.publicKey, mint, feeConfig: feeConfigPDA })
+        .rpc({ commitment: "confirmed" });
+    const sell = async () => {
+      const transferInstruction =
+        await createTransferCheckedWithTransferHookInstruction(
+          connection,
+          sourceTokenAccount,
+          mint,
+          destinationTokenAccount,
+          sender.publicKey,
+          BigInt(1 * 10 ** decimals),
+          decimals,
+          [sender.publicKey],
+          "confirmed",
+          TOKEN_2022_PROGRAM_ID
+        );
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(transferInstruction),
+        [sender],
+        { commitment: "confirmed" }
+      );
+    };
+    const sellError = async () => {
+      let error: string;
+      try {
+        await sell();
+      } catch (e) {
+        error = loggedError(e);
+      }
+      return error;
+    };
+
+    await program.methods
+      .addAmmPoolVault(destinationTokenAccount)
+      .accounts({
+        authority: wallet.publicKey,
+        mint,
+        feeConfig: feeConfigPDA,
+        ammPoolVault,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+    await setSellCooldown(3600);
+
+    // the seller has no cooldown account yet
+    assert.include(await sellError(), "SellCooldownNotInitialized");
+
+    // anyone may create it, twice is a no-op
+    for (const payer of [wallet.payer, sender]) {
+      await program.methods
+        .initSellCooldown()
+        .accounts({
+          payer: payer.publicKey,
+          mint,
+          owner: sender.publicKey,
+          sellCooldown,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([payer])
+        .rpc({ commitment: "confirmed" });
+    }
+
+    await sell();
+    const { lastSellAt } = await program.account.sellCooldown.fetch(sellCooldown);
+    assert.isTrue(lastSellAt.gtn(0));
+    assert.include(await sellError(), "SellCooldownActive");
+
+    await setSellCooldown(0);
+    await sell();
+
+    await program.methods
+      .removeAmmPoolVault()
+      .accounts({
+        authority: wallet.publicKey,
+        mint,
+        feeConfig: feeConfigPDA,
+        ammPoolVault,
+      })
+      .rpc();
+  });
+
+  it("Transfer Token To A Blocklisted Wallet", async () => {
+    const [blocklistEntry] = PublicKey.findProgramAddressSync(
+      [BLOCKLIST_TAG, mint.toBuffer(), recipient.publicKey.toBuffer()],
+      program.programId
+    );
+    const transfer = async () => {
+      const transferInstruction =
+        await createTransferCheckedWithTransferHookInstruction(
+          connection,
+          sourceTokenAccount,
+          mint,
+          destinationTokenAccount,
+          sender.publicKey,
+          BigInt(1 * 10 ** decimals),
+          decimals,
+          [sender.publicKey],
+          "confirmed",
+          TOKEN_2022_PROGRAM_ID
+        );
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(transferInstruction),
+        [sender],
+        { commitment: "confirmed" }
+      );
+    };
+
+    await program.methods
+      .addToBlocklist(recipient.publicKey)
+      .accounts({
+        authority: wallet.publicKey,
+        mint,
+        feeConfig: feeConfigPDA,
+        blocklistEntry,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    let failed = false;
+    try {
+      await transfer();
+    } catch (e) {
+      failed = true;
+    }
+    assert.isTrue(failed);
+
+    await program.methods
+      .removeFromBlocklist()
+      .accounts({
+        authority: wallet.publicKey,
+        mint,
+        feeConfig: feeConfigPDA,
+        blocklistEntry,
+      })
+      .rpc({ commitment: "confirmed" });
+    assert.isNull(await connection.getAccountInfo(blocklistEntry));
+    await transfer();
+  });
+
+  it("Transfer Token While Fee Collection Is Paused", async () => {
+    const guardian = Keypair.generate();
+    await program.methods
+      .setFeeConfigGuardian(guardian.publicKey)
+      .accounts({ authority: wallet.publicKey, mint, feeConfig: feeConfigPDA })
+      .rpc();
+    await program.methods
+      .setFeeConfigPause(true, true)
+      .accounts({ signer: guardian.publicKey, mint, feeConfig: feeConfigPDA })
+      .signers([guardian])
+      .rpc();
+
+    const pendingFeeBefore = (
+      await program.account.feeAccrual.fetch(feeAccrualPDA)
+    ).pendingFee;
+    const destinationBalanceBefore = await getTokenBalance(destinationTokenAccount);
+
+    const amount = BigInt(1 * 10 ** decimals);
+    const transferInstruction =
+      await createTransferCheckedWithTransferHookInstruction(
+        connection,
+        sourceTokenAccount,
+        mint,
+        destinationTokenAccount,
+        sender.publicKey,
+        amount,
+        decimals,
+        [sender.publicKey],
+        "confirmed",
+        TOKEN_2022_PROGRAM_ID
+      );
+    await sendAndConfirmTransaction(
+      connection,
+      new Transaction().add(transferInstruction),
+      [sender],
+      { commitment: "confirmed" }
+    );
+
+    // the transfer goes through without recording a fee
+    const pendingFeeAfter = (
+      await program.account.feeAccrual.fetch(feeAccrualPDA)
+    ).pendingFee;
+    assert.equal(pendingFeeAfter.toString(), pendingFeeBefore.toString());
+    assert.equal(
+      (await getTokenBalance(destinationTokenAccount)) - destinationBalanceBefore,
+      amount
+    );
+
+    await program.methods
+      .setFeeConfigPause(false, false)
+      .accounts({ signer: wallet.publicKey, mint, feeConfig: feeConfigPDA })
+      .rpc();
+  });
+
+  it("Transfer Token Over The Limits", async () => {
+    const setTransferLimits = (maxTxAmount: object, maxWalletBalance: object) =>
+      program.methods
+        .setTransferLimits(maxTxAmount, maxWalletBalance)
+        .accounts({ authority: wallet.publicKey, mint, feeConfig: feeConfigPDA })
+        .rpc({ commitment: "confirmed" });
+    const transfer = async (amount: bigint) => {
+      const transferInstruction =
+        await createTransferCheckedWithTransferHookInstruction(
+          connection,
+          sourceTokenAccount,
+          mint,
+          destinationTokenAccount,
+          sender.publicKey,
+          amount,
+          decimals,
+          [sender.publicKey],
+          "confirmed",
+          TOKEN_2022_PROGRAM_ID
+        );
+      await sendAndConfirmTransaction(
+        connection,
+        new Transaction().add(transferInstruction),
+        [sender],
+        { commitment: "confirmed" }
+      );
+    };
+    const transferFails = async (amount: bigint) => {
+      let failed = false;
+      try {
+        await transfer(amount);
+      } catch (e) {
+        failed = true;
+      }
+      return failed;
+    };
+
+    const amount = BigInt(1 * 10 ** decimals);
+    const unlimited = { unlimited: {} };
+
+    // a zero limit would block every transfer, Unlimited removes a limit instead
+    let failed = false;
+    try {
+      await setTransferLimits({ amount: { 0: new anchor.BN(0) } }, unlimited);
+    } catch (e) {
+      failed = true;
+    }
+    assert.isTrue(failed);
+
+    await setTransferLimits(
+      { amount: { 0: new anchor.BN((amount - BigInt(1)).toString()) } },
+      unlimited
+    );
+    assert.isTrue(await transferFails(amount));
+
+    // the destination already holds more than 0.01% of the supply
+    await setTransferLimits(unlimited, { supplyBps: { 0: 1 } });
+    assert.isTrue(await transferFails(amount));
+
+    await setTransferLimits(unlimited, unlimited);
+    const destinationBalanceBefore = await getTokenBalance(destinationTokenAccount);
+    await transfer(amount);
+    assert.equal(
+      (await getTokenBalance(destinationTokenAccount)) - destinationBalanceBefore,
+      amount
+    );
   });
 
   const getTokenBalance = async (
@@ -1,7 +1,31 @@
+import { PublicKey } from "@solana/web3.js";
 
 export const TREASURY_TAG = Buffer.from("treasury");
 export const USER_WRAPPER_TOKEN_ACCOUNT_TAG = Buffer.from("user-wrapper-token-account");
+export const STAKER_INFO_TAG = Buffer.from("staker-info");
+export const REWARD_AUTHORITY_TAG = Buffer.from("reward-authority");
+export const WITHDRAWAL_TICKET_TAG = Buffer.from("withdrawal-ticket");
+
+export const VIRTUAL_SHARES = 1000; // shares per treasury token of an empty treasury
 
 export const DELEGATE_TAG = Buffer.from("delegate");
 export const EXTRA_ACCOUNT_METAS_TAG = Buffer.from("extra-account-metas");
 export const FEE_CONFIG_TAG = Buffer.from("fee-config");
+export const FEE_ACCRUAL_TAG = Buffer.from("fee-accrual");
+export const FEE_EXEMPTION_TAG = Buffer.from("fee-exemption");
+export const AMM_POOL_VAULT_TAG = Buffer.from("amm-pool-vault");
+export const SELL_COOLDOWN_TAG = Buffer.from("sell-cooldown");
+export const BLOCKLIST_TAG = Buffer.from("blocklist");
+export const DEFAULT_MAX_SLIPPAGE_BPS = 100; // 1%
+export const DEFAULT_PRICE_WINDOW = 30 * 60; // 30 minutes
+export const BUYBACK_AUTHORITY_TAG = Buffer.from("buyback-authority");
+export const LIQUIDITY_AUTHORITY_TAG = Buffer.from("liquidity-authority");
+export const LIQUIDITY_POSITION_TAG = Buffer.from("liquidity-position");
+export const POSITION_NFT_MINT_TAG = Buffer.from("position-nft-mint");
+
+// raydium clmm program (mocked by programs/mock-clmm on localnet)
+export const RAYDIUM_CLMM_PROGRAM_ID = new PublicKey(
+  "6weQP6SNcqqk8KnQGcM2rzU1Xk9o9atJD8kvASVCrN55"
+);
+export const POOL_STATE_TAG = Buffer.from("pool-state");
+export const POSITION_SEED = Buffer.from("position");